@@ -0,0 +1,38 @@
+// The overflow-safe midpoint, floor((a + b) / 2).
+// a + b may overflow when both are large, and a + (b - a) / 2 underflows when a > b.
+// The common bits of a and b contribute fully, the differing bits contribute half,
+// so (a + b) / 2 == (a & b) + ((a ^ b) >> 1), and neither part overflows.
+pub fn midpoint(a: usize, b: usize) -> usize {
+    (a & b) + ((a ^ b) >> 1)
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::{Rng, SeedableRng};
+    use rand::prelude::StdRng;
+    
+    use super::*;
+    
+    #[test]
+    fn test_midpoint() {
+        assert_eq!(midpoint(0, 0), 0);
+        assert_eq!(midpoint(0, 1), 0);
+        assert_eq!(midpoint(0, 10), 5);
+        assert_eq!(midpoint(3, 8), 5);
+        assert_eq!(midpoint(8, 3), 5);
+        
+        // near usize::MAX, where a + b overflows
+        assert_eq!(midpoint(usize::MAX, usize::MAX), usize::MAX);
+        assert_eq!(midpoint(usize::MAX - 2, usize::MAX), usize::MAX - 1);
+        assert_eq!(midpoint(usize::MAX, usize::MAX - 3), usize::MAX - 2);
+        assert_eq!(midpoint(0, usize::MAX), usize::MAX / 2);
+        
+        let mut rng: StdRng = SeedableRng::seed_from_u64(123456);
+        for _i in 0..1000 {
+            let a: usize = rng.gen();
+            let b: usize = rng.gen();
+            let expected = ((a as u128 + b as u128) / 2) as usize;
+            assert_eq!(midpoint(a, b), expected);
+        }
+    }
+}
@@ -0,0 +1,3 @@
+pub mod slice_ops;
+pub mod midpoint;
+pub mod permutation;
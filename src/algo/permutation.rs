@@ -0,0 +1,210 @@
+use std::cmp::Ordering;
+
+use crate::merge_sort::simple_merge_sort::simple_merge_sort_inplace;
+
+// Returns the indices that sort the array, without modifying the array.
+// result[i] is the index of the element that should be at i after sorting.
+// It's stable: equal elements keep their original order.
+pub fn argsort<Element, Comparator>(arr: &[Element], compare: &Comparator) -> Vec<usize>
+    where
+        Comparator: Fn(&Element, &Element) -> Ordering
+{
+    let mut indices: Vec<usize> = (0..arr.len()).collect();
+    simple_merge_sort_inplace(&mut indices, &|a: &usize, b: &usize| compare(&arr[*a], &arr[*b]));
+    indices
+}
+
+// Like argsort, but yields the elements too. Each item is (original index, element reference).
+// It doesn't modify the array. It's stable.
+pub fn sort_with_indices<'a, Element, Comparator>(
+    arr: &'a [Element], compare: &Comparator,
+) -> Vec<(usize, &'a Element)>
+    where
+        Comparator: Fn(&Element, &Element) -> Ordering
+{
+    argsort(arr, compare).into_iter().map(|index| (index, &arr[index])).collect()
+}
+
+// Reorder the array in place, so that the new arr[i] is the old arr[permutation[i]].
+// It follows the cycles of the permutation, swapping along each cycle.
+// O(n) time, O(n) extra bits for marking the visited positions.
+pub fn apply_permutation<T>(arr: &mut [T], permutation: &[usize]) {
+    let len = arr.len();
+    assert_eq!(permutation.len(), len, "the permutation length doesn't match");
+    
+    let mut done: Vec<bool> = vec![false; len];
+    
+    for start in 0..len {
+        if done[start] {
+            continue;
+        }
+        
+        // the cycle is start -> permutation[start] -> permutation[permutation[start]] -> ... -> start
+        // after swapping arr[current] with arr[next], arr[current] holds the old arr[next], which is correct,
+        // and arr[next] holds the old arr[start], which is carried along the cycle.
+        let mut current = start;
+        loop {
+            done[current] = true;
+            let next = permutation[current];
+            assert!(next < len, "invalid permutation: index {} out of bound", next);
+            if next == start {
+                break;
+            }
+            assert!(!done[next], "invalid permutation: index {} appears twice", next);
+            arr.swap(current, next);
+            current = next;
+        }
+    }
+}
+
+// Sort the keys, and reorder a and b in the same way, so that they stay aligned with the keys.
+// It's stable.
+pub fn co_sort2<K: Ord, A, B>(keys: &mut [K], a: &mut [A], b: &mut [B]) {
+    assert_eq!(keys.len(), a.len());
+    assert_eq!(keys.len(), b.len());
+    
+    let permutation = argsort(keys, &|x: &K, y: &K| x.cmp(y));
+    
+    apply_permutation(keys, &permutation);
+    apply_permutation(a, &permutation);
+    apply_permutation(b, &permutation);
+}
+
+// Sort by a key that may fail to compute, such as parsing. Each key is computed once beforehand.
+// If any key fails, it returns the first error, and the array is not modified.
+// So the comparator never needs to panic on a bad key. It's stable.
+pub fn try_sort_by_key<Element, K: Ord, E>(
+    arr: &mut [Element], key_fn: impl Fn(&Element) -> Result<K, E>,
+) -> Result<(), E> {
+    let keys: Vec<K> = arr.iter().map(key_fn).collect::<Result<Vec<K>, E>>()?;
+    
+    let permutation = argsort(&keys, &|x: &K, y: &K| x.cmp(y));
+    apply_permutation(arr, &permutation);
+    
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::{Rng, SeedableRng};
+    use rand::prelude::{SliceRandom, StdRng};
+    
+    use super::*;
+    
+    #[test]
+    fn test_argsort_and_apply_permutation() {
+        let mut rng: StdRng = SeedableRng::seed_from_u64(123456);
+        
+        for _i in 0..100 {
+            let len = rng.gen_range(0..500);
+            let mut vec: Vec<i32> = (0..len).map(|_| rng.gen_range(0..100)).collect();
+            let mut vec_for_ref = vec.clone();
+            vec_for_ref.sort();
+            
+            let permutation = argsort(&vec, &|a: &i32, b: &i32| a.cmp(b));
+            
+            // it's stable, so equal elements are in index order
+            for i in 1..len {
+                let (p0, p1) = (permutation[i - 1], permutation[i]);
+                assert!(vec[p0] < vec[p1] || (vec[p0] == vec[p1] && p0 < p1));
+            }
+            
+            apply_permutation(&mut vec, &permutation);
+            assert_eq!(vec, vec_for_ref);
+        }
+        
+        for _i in 0..100 {
+            let len = rng.gen_range(0..500);
+            let mut permutation: Vec<usize> = (0..len).collect();
+            permutation.shuffle(&mut rng);
+            
+            let original: Vec<usize> = (0..len).map(|i| i * 10).collect();
+            let mut vec = original.clone();
+            apply_permutation(&mut vec, &permutation);
+            
+            for i in 0..len {
+                assert_eq!(vec[i], original[permutation[i]]);
+            }
+        }
+    }
+    
+    #[test]
+    fn test_sort_with_indices() {
+        let mut rng: StdRng = SeedableRng::seed_from_u64(123456);
+        
+        for _i in 0..100 {
+            let len = rng.gen_range(0..1000);
+            let max = rng.gen_range(1..50);
+            let vec: Vec<i32> = (0..len).map(|_| rng.gen_range(0..max)).collect();
+            
+            let sorted = sort_with_indices(&vec, &|a: &i32, b: &i32| a.cmp(b));
+            
+            // the indices form a permutation, and each element is the original one at that index
+            let mut indices: Vec<usize> = sorted.iter().map(|(index, _)| *index).collect();
+            for (index, element) in sorted.iter() {
+                assert!(std::ptr::eq(*element, &vec[*index]));
+            }
+            
+            // non-decreasing, and stable for ties
+            for pair in sorted.windows(2) {
+                let (index1, element1) = pair[0];
+                let (index2, element2) = pair[1];
+                assert!(element1 <= element2);
+                if element1 == element2 {
+                    assert!(index1 < index2);
+                }
+            }
+            
+            indices.sort();
+            assert_eq!(indices, (0..len).collect::<Vec<usize>>());
+        }
+    }
+    
+    #[test]
+    #[should_panic(expected = "invalid permutation")]
+    fn test_apply_invalid_permutation() {
+        let mut vec = vec![1, 2, 3];
+        apply_permutation(&mut vec, &[1, 1, 0]);
+    }
+    
+    #[test]
+    fn test_try_sort_by_key() {
+        let parse = |s: &&str| s.parse::<i32>();
+        
+        let mut vec = vec!["3", "10", "2", "03", "1"];
+        assert_eq!(try_sort_by_key(&mut vec, parse), Ok(()));
+        // "3" and "03" have the same key, and it's stable
+        assert_eq!(vec, vec!["1", "2", "3", "03", "10"]);
+        
+        let mut vec = vec!["3", "10", "x", "2", "y"];
+        let result = try_sort_by_key(&mut vec, |s: &&str| s.parse::<i32>().map_err(|_| s.to_string()));
+        // the first error is from "x"
+        assert_eq!(result, Err("x".to_string()));
+        assert_eq!(vec, vec!["3", "10", "x", "2", "y"]);
+        
+        let mut vec: Vec<&str> = vec![];
+        assert_eq!(try_sort_by_key(&mut vec, parse), Ok(()));
+    }
+    
+    #[test]
+    fn test_co_sort2() {
+        let mut rng: StdRng = SeedableRng::seed_from_u64(123456);
+        
+        for _i in 0..100 {
+            let len = rng.gen_range(0..500);
+            let mut keys: Vec<i32> = (0..len).map(|_| rng.gen_range(0..50)).collect();
+            // the payloads are derived from the original index, so the alignment can be checked
+            let mut ids: Vec<usize> = (0..len).collect();
+            let mut names: Vec<String> = (0..len).map(|i| format!("{}:{}", keys[i], i)).collect();
+            
+            co_sort2(&mut keys, &mut ids, &mut names);
+            
+            for i in 0..len {
+                assert_eq!(names[i], format!("{}:{}", keys[i], ids[i]));
+            }
+            for i in 1..len {
+                assert!(keys[i - 1] < keys[i] || (keys[i - 1] == keys[i] && ids[i - 1] < ids[i]));
+            }
+        }
+    }
+}
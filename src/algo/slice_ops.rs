@@ -0,0 +1,101 @@
+use std::ops::Range;
+
+// Reverse the elements in arr[range], in place.
+pub fn reverse_range<T>(arr: &mut [T], range: Range<usize>) {
+    assert!(range.start <= range.end && range.end <= arr.len());
+    
+    if range.is_empty() {
+        return;
+    }
+    
+    let mut left = range.start;
+    let mut right = range.end - 1;
+    while left < right {
+        arr.swap(left, right);
+        left += 1;
+        right -= 1;
+    }
+}
+
+// Rotate the array so that arr[mid] becomes the first element, in place with O(1) extra space.
+// It uses the three-reversal algorithm:
+// denote the array as AB where A = arr[..mid], B = arr[mid..], we want BA.
+// reversing A and B separately gives (A^r)(B^r), then reversing the whole gives BA.
+pub fn rotate_left<T>(arr: &mut [T], mid: usize) {
+    let len = arr.len();
+    assert!(mid <= len);
+    
+    if mid == 0 || mid == len {
+        return;
+    }
+    
+    reverse_range(arr, 0..mid);
+    reverse_range(arr, mid..len);
+    reverse_range(arr, 0..len);
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::{Rng, SeedableRng};
+    use rand::prelude::StdRng;
+    
+    use super::*;
+    
+    #[test]
+    fn test_reverse_range() {
+        let mut arr = [0, 1, 2, 3, 4, 5, 6];
+        
+        reverse_range(&mut arr, 2..5);
+        assert_eq!(arr, [0, 1, 4, 3, 2, 5, 6]);
+        
+        reverse_range(&mut arr, 3..3);
+        assert_eq!(arr, [0, 1, 4, 3, 2, 5, 6]);
+        
+        reverse_range(&mut arr, 0..7);
+        assert_eq!(arr, [6, 5, 2, 3, 4, 1, 0]);
+        
+        let mut rng: StdRng = SeedableRng::seed_from_u64(123456);
+        for _i in 0..100 {
+            let len = rng.gen_range(0..100);
+            let mut arr: Vec<i32> = (0..len).map(|_| rng.gen_range(0..1000)).collect();
+            let start = rng.gen_range(0..=len);
+            let end = rng.gen_range(start..=len);
+            
+            let mut arr_for_ref = arr.clone();
+            arr_for_ref[start..end].reverse();
+            
+            reverse_range(&mut arr, start..end);
+            assert_eq!(arr, arr_for_ref);
+        }
+    }
+    
+    #[test]
+    fn test_rotate_left() {
+        let mut arr = [0, 1, 2, 3, 4];
+        
+        rotate_left(&mut arr, 0);
+        assert_eq!(arr, [0, 1, 2, 3, 4]);
+        
+        rotate_left(&mut arr, 5);
+        assert_eq!(arr, [0, 1, 2, 3, 4]);
+        
+        rotate_left(&mut arr, 2);
+        assert_eq!(arr, [2, 3, 4, 0, 1]);
+        
+        let mut empty: [i32; 0] = [];
+        rotate_left(&mut empty, 0);
+        
+        let mut rng: StdRng = SeedableRng::seed_from_u64(123456);
+        for _i in 0..100 {
+            let len = rng.gen_range(0..100);
+            let mut arr: Vec<i32> = (0..len).map(|_| rng.gen_range(0..1000)).collect();
+            let mid = rng.gen_range(0..=len);
+            
+            let mut arr_for_ref = arr.clone();
+            arr_for_ref.rotate_left(mid);
+            
+            rotate_left(&mut arr, mid);
+            assert_eq!(arr, arr_for_ref);
+        }
+    }
+}
@@ -1,4 +1,9 @@
 use std::cmp::Ordering;
+use std::ops::{Deref, DerefMut};
+use std::sync::atomic::AtomicBool;
+
+use crate::algo::midpoint::midpoint;
+use crate::util::cancellation::{Cancelled, check_cancelled, without_cancellation};
 
 // The BinaryHeap on std does not allow specifying a custom comparator.
 // A custom comparator can carry runtime information where Ord implementation cannot.
@@ -22,6 +27,21 @@ impl<'a, T, Comparator> MyMinHeap<'a, T, Comparator>
         }
     }
     
+    // Pre-allocate for capacity elements, when the number of elements is known beforehand.
+    pub fn with_capacity(capacity: usize, comparator: &'a Comparator) -> Self {
+        Self {
+            data: Vec::with_capacity(capacity),
+            comparator,
+        }
+    }
+    
+    // Take the Vec and heapify it in place. It takes O(n), while inserting one by one takes O(n log n).
+    pub fn from_vec(data: Vec<T>, comparator: &'a Comparator) -> Self {
+        let mut heap = Self { data, comparator };
+        make_heap_in_place(&mut heap.data, heap.comparator);
+        heap
+    }
+    
     // the binary heap treats an array as a tree
     // the root is at index 0
     // the left child of a node at index i is at index 2i+1
@@ -30,93 +50,27 @@ impl<'a, T, Comparator> MyMinHeap<'a, T, Comparator>
     // it needs to ensure that the parent is smaller or equal than both children
     // parent <= left_child, parent <= right_child
     
-    fn left_child_index(&self, index: usize) -> usize {
-        2 * index + 1
-    }
-    
-    fn right_child_index(&self, index: usize) -> usize {
-        2 * index + 2
-    }
-    
     fn parent_index(&self, index: usize) -> usize {
         assert!(index > 0);
-        (index - 1) / 2
-    }
-    
-    fn has_node(&self, index: usize) -> bool {
-        index < self.data.len()
-    }
-    
-    fn is_root(&self, curr_index: usize) -> bool {
-        curr_index == 0
+        midpoint(0, index - 1)
     }
     
+    // parent <= child. equal is allowed, as there may be duplicates
     fn check_valid(&self) {
         for i in 1..self.data.len() {
             let parent_index = self.parent_index(i);
-            assert!((self.comparator)(&self.data[parent_index], &self.data[i]).is_lt());
+            assert!((self.comparator)(&self.data[parent_index], &self.data[i]).is_le());
         }
     }
     
     // when the element at index is larger than its children, sift it down
     fn sift_down(&mut self, index: usize) {
-        let mut curr_parent = index;
-        
-        loop {
-            let left_child = self.left_child_index(curr_parent);
-            let right_child = self.right_child_index(curr_parent);
-            
-            // now we consider 3 nodes: curr_parent, left_child (maybe missing), right_child (maybe missing).
-            // if there is no child, the heap property is satisfied.
-            // if there is only one child, we need to ensure parent <= child, and swap if necessary.
-            // if there are two children, we need to ensure parent <= left_child and parent <= right_child,
-            // if it violates, we swap parent with the smaller child.
-            // (cannot swap parent with the larger child, as it would still violate the heap property)
-            
-            // this process is equivalent to finding the minimum of the 3 nodes,
-            // and swap it with the parent position if it's not parent.
-            // after swapping, the heap property is satisfied for the current parent and its children,
-            // but it may violate the heap property for the new child, so continue on child.
-            
-            let mut min_index = curr_parent;
-            
-            if self.has_node(left_child) &&
-                (self.comparator)(&self.data[left_child], &self.data[min_index]) == Ordering::Less {
-                min_index = left_child;
-            }
-            
-            if self.has_node(right_child) &&
-                (self.comparator)(&self.data[right_child], &self.data[min_index]) == Ordering::Less {
-                min_index = right_child;
-            }
-            
-            if min_index == curr_parent {
-                break;
-            }
-            
-            self.data.swap(curr_parent, min_index);
-            curr_parent = min_index;
-        }
+        sift_down_in_place(&mut self.data, self.comparator, index);
     }
     
     // when the element at index is smaller than its parent, sift it up
     fn sift_up(&mut self, index: usize) {
-        let mut curr_index = index;
-        
-        loop {
-            if self.is_root(curr_index) {
-                break;
-            }
-            
-            let parent_index = self.parent_index(curr_index);
-            
-            if (self.comparator)(&self.data[parent_index], &self.data[curr_index]) == Ordering::Greater {
-                self.data.swap(parent_index, curr_index);
-                curr_index = parent_index;
-            } else {
-                break;
-            }
-        }
+        sift_up_with_swap(&mut self.data, self.comparator, index, &mut swap_in_slice);
     }
     
     pub fn insert(&mut self, value: T) {
@@ -144,6 +98,33 @@ impl<'a, T, Comparator> MyMinHeap<'a, T, Comparator>
         Some(min_taken)
     }
     
+    // Take the minimum and insert the value, but only sift once.
+    // It's the same as take_min then insert, but cheaper. If the heap is empty, it just inserts.
+    pub fn replace_min(&mut self, value: T) -> Option<T> {
+        if self.data.is_empty() {
+            self.insert(value);
+            return None;
+        }
+        
+        let min_taken = std::mem::replace(&mut self.data[0], value);
+        // the new value may be larger than its children
+        self.sift_down(0);
+        
+        Some(min_taken)
+    }
+    
+    // Move all elements of the other heap into this one. It re-heapifies the whole, taking O(n + m),
+    // while inserting one by one takes O(m log(n + m)).
+    // The two heaps should use the same ordering. The other heap's comparator is not used.
+    pub fn meld(&mut self, mut other: MyMinHeap<'_, T, Comparator>) {
+        self.data.append(&mut other.data);
+        make_heap_in_place(&mut self.data, self.comparator);
+    }
+    
+    pub fn len(&self) -> usize {
+        self.data.len()
+    }
+    
     pub fn is_empty(&self) -> bool {
         self.data.is_empty()
     }
@@ -151,10 +132,366 @@ impl<'a, T, Comparator> MyMinHeap<'a, T, Comparator>
     pub fn peek_min(&self) -> Option<&T> {
         self.data.first()
     }
+    
+    pub fn drain_sorted(&mut self) -> DrainSorted<'_, 'a, T, Comparator> {
+        DrainSorted { heap: self }
+    }
+    
+    // Mutable access to the minimum. After the guard is dropped, the heap is repaired.
+    // It's cheaper than take_min then insert, which sifts twice.
+    pub fn peek_mut(&mut self) -> Option<PeekMut<'_, 'a, T, Comparator>> {
+        if self.data.is_empty() {
+            None
+        } else {
+            Some(PeekMut { heap: self })
+        }
+    }
+}
+
+// Takes the elements in ascending order lazily. Stopping early skips the sifting for the rest.
+// When it's dropped, the remaining elements are dropped without sorting, and the heap becomes empty.
+pub struct DrainSorted<'h, 'a, T, Comparator>
+    where
+        Comparator: Fn(&T, &T) -> Ordering,
+{
+    heap: &'h mut MyMinHeap<'a, T, Comparator>,
+}
+
+impl<'h, 'a, T, Comparator> Iterator for DrainSorted<'h, 'a, T, Comparator>
+    where
+        Comparator: Fn(&T, &T) -> Ordering,
+{
+    type Item = T;
+    
+    fn next(&mut self) -> Option<T> {
+        self.heap.take_min()
+    }
+    
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.heap.data.len(), Some(self.heap.data.len()))
+    }
+}
+
+impl<'h, 'a, T, Comparator> Drop for DrainSorted<'h, 'a, T, Comparator>
+    where
+        Comparator: Fn(&T, &T) -> Ordering,
+{
+    fn drop(&mut self) {
+        self.heap.data.clear();
+    }
+}
+
+// The guard of the minimum of the heap. The heap is non-empty.
+// The minimum can be changed to any value. Making it smaller keeps it the minimum,
+// making it larger requires sifting down, which is done when the guard is dropped.
+pub struct PeekMut<'h, 'a, T, Comparator>
+    where
+        Comparator: Fn(&T, &T) -> Ordering,
+{
+    heap: &'h mut MyMinHeap<'a, T, Comparator>,
+}
+
+impl<'h, 'a, T, Comparator> Deref for PeekMut<'h, 'a, T, Comparator>
+    where
+        Comparator: Fn(&T, &T) -> Ordering,
+{
+    type Target = T;
+    
+    fn deref(&self) -> &T {
+        &self.heap.data[0]
+    }
+}
+
+impl<'h, 'a, T, Comparator> DerefMut for PeekMut<'h, 'a, T, Comparator>
+    where
+        Comparator: Fn(&T, &T) -> Ordering,
+{
+    fn deref_mut(&mut self) -> &mut T {
+        &mut self.heap.data[0]
+    }
+}
+
+impl<'h, 'a, T, Comparator> Drop for PeekMut<'h, 'a, T, Comparator>
+    where
+        Comparator: Fn(&T, &T) -> Ordering,
+{
+    fn drop(&mut self) {
+        self.heap.sift_down(0);
+    }
+}
+
+// Take the minimums of two heaps in order, as if they are one heap, without melding them.
+// The taken elements are removed from the heaps, the remaining elements stay in their own heap.
+// If the minimums are equal, the one in the first heap is taken first.
+// The first heap's comparator is used for comparing between heaps.
+pub struct MinOfTwo<'h, 'a, T, Comparator>
+    where
+        Comparator: Fn(&T, &T) -> Ordering,
+{
+    first: &'h mut MyMinHeap<'a, T, Comparator>,
+    second: &'h mut MyMinHeap<'a, T, Comparator>,
+}
+
+impl<'h, 'a, T, Comparator> MinOfTwo<'h, 'a, T, Comparator>
+    where
+        Comparator: Fn(&T, &T) -> Ordering,
+{
+    pub fn new(
+        first: &'h mut MyMinHeap<'a, T, Comparator>, second: &'h mut MyMinHeap<'a, T, Comparator>,
+    ) -> Self {
+        MinOfTwo { first, second }
+    }
+    
+    pub fn take_min(&mut self) -> Option<T> {
+        let take_first = match (self.first.peek_min(), self.second.peek_min()) {
+            (None, None) => return None,
+            (Some(_), None) => true,
+            (None, Some(_)) => false,
+            (Some(a), Some(b)) => (self.first.comparator)(a, b).is_le(),
+        };
+        
+        if take_first {
+            self.first.take_min()
+        } else {
+            self.second.take_min()
+        }
+    }
+}
+
+impl<'h, 'a, T, Comparator> Iterator for MinOfTwo<'h, 'a, T, Comparator>
+    where
+        Comparator: Fn(&T, &T) -> Ordering,
+{
+    type Item = T;
+    
+    fn next(&mut self) -> Option<T> {
+        self.take_min()
+    }
+}
+
+fn left_child_index(index: usize) -> usize {
+    2 * index + 1
+}
+
+fn right_child_index(index: usize) -> usize {
+    2 * index + 2
+}
+
+fn swap_in_slice<T>(arr: &mut [T], i: usize, j: usize) {
+    arr.swap(i, j);
+}
+
+// when the element at index is larger than its children, sift it down
+fn sift_down_in_place<T, Comparator>(arr: &mut [T], compare: &Comparator, index: usize)
+    where
+        Comparator: Fn(&T, &T) -> Ordering
+{
+    sift_down_with_swap(arr, compare, index, &mut swap_in_slice);
+}
+
+// The sifting functions move the elements only by the swap function, which must swap arr[i] and arr[j].
+// A heap that tracks the position of each element (like IndexedMinHeap) updates the positions in it,
+// so it shares the sifting with MyMinHeap.
+
+// when the element at index is smaller than its parent, sift it up
+pub fn sift_up_with_swap<T, Comparator, Swap>(arr: &mut [T], compare: &Comparator, index: usize, swap: &mut Swap)
+    where
+        Comparator: Fn(&T, &T) -> Ordering,
+        Swap: FnMut(&mut [T], usize, usize),
+{
+    let mut curr_index = index;
+    
+    while curr_index > 0 {
+        let parent_index = midpoint(0, curr_index - 1);
+        
+        if compare(&arr[parent_index], &arr[curr_index]) == Ordering::Greater {
+            swap(arr, parent_index, curr_index);
+            curr_index = parent_index;
+        } else {
+            break;
+        }
+    }
+}
+
+// when the element at index is larger than its children, sift it down
+pub fn sift_down_with_swap<T, Comparator, Swap>(arr: &mut [T], compare: &Comparator, index: usize, swap: &mut Swap)
+    where
+        Comparator: Fn(&T, &T) -> Ordering,
+        Swap: FnMut(&mut [T], usize, usize),
+{
+    let mut curr_parent = index;
+    
+    loop {
+        let left_child = left_child_index(curr_parent);
+        let right_child = right_child_index(curr_parent);
+        
+        // now we consider 3 nodes: curr_parent, left_child (maybe missing), right_child (maybe missing).
+        // if there is no child, the heap property is satisfied.
+        // if there is only one child, we need to ensure parent <= child, and swap if necessary.
+        // if there are two children, we need to ensure parent <= left_child and parent <= right_child,
+        // if it violates, we swap parent with the smaller child.
+        // (cannot swap parent with the larger child, as it would still violate the heap property)
+        
+        // this process is equivalent to finding the minimum of the 3 nodes,
+        // and swap it with the parent position if it's not parent.
+        // after swapping, the heap property is satisfied for the current parent and its children,
+        // but it may violate the heap property for the new child, so continue on child.
+        
+        let mut min_index = curr_parent;
+        
+        if left_child < arr.len() &&
+            compare(&arr[left_child], &arr[min_index]) == Ordering::Less {
+            min_index = left_child;
+        }
+        
+        if right_child < arr.len() &&
+            compare(&arr[right_child], &arr[min_index]) == Ordering::Less {
+            min_index = right_child;
+        }
+        
+        if min_index == curr_parent {
+            break;
+        }
+        
+        swap(arr, curr_parent, min_index);
+        curr_parent = min_index;
+    }
+}
+
+// Heap-order the slice in place, without moving it into a MyMinHeap.
+// The leaves are already heaps. Sift down the internal nodes from the last one to the root,
+// then each subtree becomes a heap. It takes O(n).
+pub fn make_heap_in_place<T, Comparator>(arr: &mut [T], compare: &Comparator)
+    where
+        Comparator: Fn(&T, &T) -> Ordering
+{
+    // the last internal node is the parent of the last element
+    for index in (0..(arr.len() / 2)).rev() {
+        sift_down_in_place(arr, compare, index);
+    }
+}
+
+// Partial heap sort. After it, arr[..k] are the k smallest elements in sorted order,
+// and arr[k..] is a min-heap of the remaining elements.
+// It takes O(n + k log n) comparisons, cheaper than full sorting when k is small. It's not stable.
+pub fn heap_sort_top_k<T, Comparator>(arr: &mut [T], k: usize, compare: &Comparator)
+    where
+        Comparator: Fn(&T, &T) -> Ordering
+{
+    let len = arr.len();
+    let k = k.min(len);
+    
+    make_heap_in_place(arr, compare);
+    
+    // repeatedly move the minimum to the end of the heap region, then shrink the heap region.
+    // the taken minimums are at the end of arr, from large to small.
+    for heap_len in ((len - k)..len).rev() {
+        arr.swap(0, heap_len);
+        sift_down_in_place(&mut arr[..heap_len], compare, 0);
+    }
+    
+    // now arr[..(len - k)] is the heap, and arr[(len - k)..] is the k smallest in descending order.
+    // move the k smallest to the front in ascending order. moving the heap as a whole keeps it a heap.
+    arr[(len - k)..].reverse();
+    arr.rotate_right(k);
+}
+
+// In-place heap sort. It's not stable. Takes O(n log n) time and O(1) extra space.
+// It builds a max-heap by inverting the comparator, then repeatedly swaps the maximum to the end,
+// so the sorted region grows from the end and no extra reversing is needed.
+// The elements are only swapped, so they don't need to be Clone.
+pub fn heap_sort<T, Comparator>(arr: &mut [T], compare: &Comparator)
+    where
+        Comparator: Fn(&T, &T) -> Ordering
+{
+    without_cancellation(|cancel| heap_sort_cancellable(arr, compare, cancel));
+}
+
+// Same as heap_sort, but it checks the cancel flag before each sift, in both building the heap and taking the maximums.
+pub fn heap_sort_cancellable<T, Comparator>(arr: &mut [T], compare: &Comparator, cancel: &AtomicBool) -> Result<(), Cancelled>
+    where
+        Comparator: Fn(&T, &T) -> Ordering
+{
+    let inverted_compare = |a: &T, b: &T| compare(b, a);
+    
+    // same as make_heap_in_place
+    for index in (0..(arr.len() / 2)).rev() {
+        check_cancelled(cancel)?;
+        sift_down_in_place(arr, &inverted_compare, index);
+    }
+    
+    for heap_len in (1..arr.len()).rev() {
+        check_cancelled(cancel)?;
+        arr.swap(0, heap_len);
+        sift_down_in_place(&mut arr[..heap_len], &inverted_compare, 0);
+    }
+    
+    Ok(())
+}
+
+// Shortcut for Ord types, without passing a comparator.
+pub fn heap_sort_ord<T: Ord>(arr: &mut [T]) {
+    heap_sort(arr, &T::cmp);
+}
+
+// whether every parent is smaller or equal than its children
+pub fn is_min_heap<T, Comparator>(arr: &[T], compare: &Comparator) -> bool
+    where
+        Comparator: Fn(&T, &T) -> Ordering
+{
+    (1..arr.len()).all(|index| compare(&arr[midpoint(0, index - 1)], &arr[index]).is_le())
+}
+
+struct SortedIterEntry<'a, Element, Comparator> {
+    element: &'a Element,
+    index: usize,
+    compare: &'a Comparator,
+}
+
+// the comparator is a plain function, so that a reference to it can be 'static,
+// and the heap can be moved into the returned iterator.
+// the user-provided comparator is carried by the entries.
+fn compare_sorted_iter_entries<Element, Comparator>(
+    a: &SortedIterEntry<Element, Comparator>, b: &SortedIterEntry<Element, Comparator>,
+) -> Ordering
+    where
+        Comparator: Fn(&Element, &Element) -> Ordering
+{
+    // if elements are equal, the one with smaller index goes first, making it stable
+    (a.compare)(a.element, b.element).then(a.index.cmp(&b.index))
+}
+
+// Iterate the slice in sorted order lazily, without modifying the slice.
+// Building the heap takes O(n), then each step takes O(log n).
+// If only the first k elements are taken, it doesn't sort the remaining.
+pub fn sorted_iter<'a, Element, Comparator>(
+    arr: &'a [Element], compare: &'a Comparator,
+) -> impl Iterator<Item=&'a Element>
+    where
+        Comparator: Fn(&Element, &Element) -> Ordering
+{
+    let entries: Vec<SortedIterEntry<'a, Element, Comparator>> = arr.iter().enumerate()
+        .map(|(index, element)| SortedIterEntry { element, index, compare })
+        .collect();
+    
+    let mut heap = MyMinHeap::from_vec(
+        entries,
+        &(compare_sorted_iter_entries::<Element, Comparator>
+            as fn(&SortedIterEntry<'a, Element, Comparator>, &SortedIterEntry<'a, Element, Comparator>) -> Ordering),
+    );
+    
+    std::iter::from_fn(move || heap.take_min().map(|entry| entry.element))
 }
 
 #[cfg(test)]
 mod tests {
+    use std::cell::Cell;
+    
+    use rand::{Rng, SeedableRng};
+    use rand::prelude::StdRng;
+    
+    use crate::util::test_util::{check_cancellable_sort, HEAP_SORT_COMPARISONS_AFTER_CANCEL};
+    
     use super::*;
     
     #[test]
@@ -190,4 +527,406 @@ mod tests {
         assert_eq!(heap.take_min(), None);
         heap.check_valid();
     }
-}
\ No newline at end of file
+    
+    #[test]
+    fn test_len_and_with_capacity() {
+        let compare = |a: &i32, b: &i32| a.cmp(b);
+        let mut heap = MyMinHeap::with_capacity(10, &compare);
+        assert!(heap.data.capacity() >= 10);
+        assert_eq!(heap.len(), 0);
+        
+        for (i, value) in [5, 3, 8, 1].into_iter().enumerate() {
+            heap.insert(value);
+            assert_eq!(heap.len(), i + 1);
+        }
+        
+        for expected_len in (0..4).rev() {
+            heap.take_min();
+            assert_eq!(heap.len(), expected_len);
+        }
+        assert!(heap.is_empty());
+        assert_eq!(heap.take_min(), None);
+        assert_eq!(heap.len(), 0);
+    }
+    
+    #[test]
+    fn test_drain_sorted() {
+        let mut rng: StdRng = SeedableRng::seed_from_u64(123456);
+        let compare = |a: &i32, b: &i32| a.cmp(b);
+        
+        for _i in 0..100 {
+            let vec: Vec<i32> = (0..rng.gen_range(0..300)).map(|_| rng.gen_range(0..100)).collect();
+            let mut vec_for_ref = vec.clone();
+            vec_for_ref.sort();
+            
+            let mut heap = MyMinHeap::from_vec(vec.clone(), &compare);
+            let drained: Vec<i32> = heap.drain_sorted().collect();
+            assert_eq!(drained, vec_for_ref);
+            assert!(heap.is_empty());
+            
+            // stop early. the remaining are dropped
+            let mut heap = MyMinHeap::from_vec(vec, &compare);
+            let k = rng.gen_range(0..(vec_for_ref.len() + 1));
+            let drained: Vec<i32> = heap.drain_sorted().take(k).collect();
+            assert_eq!(drained, vec_for_ref[..k]);
+            assert!(heap.is_empty());
+            
+            // the heap is still usable
+            heap.insert(3);
+            assert_eq!(heap.take_min(), Some(3));
+        }
+    }
+    
+    #[test]
+    fn test_meld() {
+        let mut rng: StdRng = SeedableRng::seed_from_u64(123456);
+        let compare = |a: &i32, b: &i32| a.cmp(b);
+        
+        for _i in 0..100 {
+            let vec1: Vec<i32> = (0..rng.gen_range(0..300)).map(|_| rng.gen_range(0..100)).collect();
+            let vec2: Vec<i32> = (0..rng.gen_range(0..300)).map(|_| rng.gen_range(0..100)).collect();
+            let mut vec_for_ref: Vec<i32> = vec1.iter().chain(vec2.iter()).copied().collect();
+            vec_for_ref.sort();
+            
+            let mut heap1 = MyMinHeap::from_vec(vec1, &compare);
+            let heap2 = MyMinHeap::from_vec(vec2, &compare);
+            heap1.meld(heap2);
+            heap1.check_valid();
+            
+            let mut taken: Vec<i32> = Vec::new();
+            while let Some(min) = heap1.take_min() {
+                taken.push(min);
+            }
+            assert_eq!(taken, vec_for_ref);
+        }
+    }
+    
+    #[test]
+    fn test_replace_min() {
+        let mut rng: StdRng = SeedableRng::seed_from_u64(123456);
+        let compare = |a: &i32, b: &i32| a.cmp(b);
+        
+        for _i in 0..100 {
+            let mut heap = MyMinHeap::new(&compare);
+            let mut heap_for_ref = MyMinHeap::new(&compare);
+            
+            for _j in 0..200 {
+                let value = rng.gen_range(0..50);
+                if rng.gen_bool(0.3) {
+                    heap.insert(value);
+                    heap_for_ref.insert(value);
+                } else {
+                    let taken = heap.replace_min(value);
+                    let taken_for_ref = heap_for_ref.take_min();
+                    heap_for_ref.insert(value);
+                    assert_eq!(taken, taken_for_ref);
+                }
+                heap.check_valid();
+            }
+            
+            while let Some(min) = heap.take_min() {
+                assert_eq!(Some(min), heap_for_ref.take_min());
+            }
+            assert!(heap_for_ref.is_empty());
+        }
+    }
+    
+    #[test]
+    fn test_peek_mut() {
+        let compare = |a: &i32, b: &i32| a.cmp(b);
+        let mut heap = MyMinHeap::from_vec(vec![5, 1, 4, 2, 3, 6], &compare);
+        // the same modifications are applied to the minimum of the reference
+        let mut reference = vec![5, 1, 4, 2, 3, 6];
+        let reference_min = |reference: &[i32]| -> usize {
+            (0..reference.len()).min_by_key(|&i| reference[i]).unwrap()
+        };
+        
+        // make the minimum the largest
+        *heap.peek_mut().unwrap() = 10;
+        let i = reference_min(&reference);
+        reference[i] = 10;
+        heap.check_valid();
+        assert_eq!(heap.peek_min(), Some(&2));
+        
+        // make the minimum smaller, it stays at the root
+        *heap.peek_mut().unwrap() = 0;
+        let i = reference_min(&reference);
+        reference[i] = 0;
+        heap.check_valid();
+        assert_eq!(heap.peek_min(), Some(&0));
+        
+        // increase the minimum repeatedly
+        for _i in 0..10 {
+            *heap.peek_mut().unwrap() += 3;
+            let i = reference_min(&reference);
+            reference[i] += 3;
+            heap.check_valid();
+        }
+        
+        let mut taken: Vec<i32> = Vec::new();
+        while let Some(min) = heap.take_min() {
+            taken.push(min);
+        }
+        // sorted, and no element is lost or duplicated by the modifications
+        reference.sort();
+        assert_eq!(taken, reference);
+        
+        assert!(heap.peek_mut().is_none());
+    }
+    
+    #[test]
+    fn test_check_valid_with_duplicates() {
+        let compare = |a: &i32, b: &i32| a.cmp(b);
+        let mut heap = MyMinHeap::new(&compare);
+        for value in [1, 1, 3, 1, 2, 2, 3, 1] {
+            heap.insert(value);
+            heap.check_valid();
+        }
+        
+        let mut taken: Vec<i32> = Vec::new();
+        while let Some(min) = heap.take_min() {
+            taken.push(min);
+            heap.check_valid();
+        }
+        assert_eq!(taken, vec![1, 1, 1, 1, 2, 2, 3, 3]);
+    }
+    
+    #[test]
+    fn test_from_vec() {
+        let mut rng: StdRng = SeedableRng::seed_from_u64(123456);
+        let compare = |a: &i32, b: &i32| a.cmp(b);
+        
+        for _i in 0..300 {
+            let len = rng.gen_range(0..1000);
+            let max = rng.gen_range(1..100);
+            let vec: Vec<i32> = (0..len).map(|_| rng.gen_range(0..max)).collect();
+            let mut vec_for_ref = vec.clone();
+            vec_for_ref.sort();
+            
+            let mut heap = MyMinHeap::from_vec(vec.clone(), &compare);
+            let mut taken: Vec<i32> = Vec::new();
+            while let Some(min) = heap.take_min() {
+                taken.push(min);
+            }
+            assert_eq!(taken, vec_for_ref);
+            
+            // same as inserting one by one
+            let mut inserted_heap = MyMinHeap::new(&compare);
+            for e in vec {
+                inserted_heap.insert(e);
+            }
+            let mut taken_from_inserted: Vec<i32> = Vec::new();
+            while let Some(min) = inserted_heap.take_min() {
+                taken_from_inserted.push(min);
+            }
+            assert_eq!(taken_from_inserted, taken);
+        }
+        
+        for _i in 0..100 {
+            let len = rng.gen_range(0..1000);
+            let vec: Vec<i32> = (0..len).map(|_| rng.gen_range(0..10)).collect();
+            let heap = MyMinHeap::from_vec(vec, &compare);
+            heap.check_valid();
+        }
+    }
+    
+    #[test]
+    fn test_sorted_iter() {
+        let mut rng: StdRng = SeedableRng::seed_from_u64(123456);
+        
+        for _i in 0..300 {
+            let len = rng.gen_range(0..1000);
+            let max = rng.gen_range(1..100);
+            
+            // (key, tag), only key is compared
+            let vec: Vec<(i32, usize)> = (0..len).map(|tag| (rng.gen_range(0..max), tag)).collect();
+            let vec_copy = vec.clone();
+            
+            let mut vec_for_ref = vec.clone();
+            vec_for_ref.sort_by(|a, b| a.0.cmp(&b.0));
+            
+            let k = rng.gen_range(0..=len);
+            let compare = |a: &(i32, usize), b: &(i32, usize)| a.0.cmp(&b.0);
+            let taken: Vec<(i32, usize)> = sorted_iter(&vec, &compare).take(k).copied().collect();
+            
+            // it's stable, so it matches std's stable sort exactly
+            assert_eq!(taken, vec_for_ref[..k]);
+            
+            // the original is untouched
+            assert_eq!(vec, vec_copy);
+        }
+    }
+    
+    #[test]
+    fn test_min_of_two() {
+        let mut rng: StdRng = SeedableRng::seed_from_u64(123456);
+        
+        // (key, tag), only key is compared. tag tells which heap it's from
+        let compare = |a: &(i32, usize), b: &(i32, usize)| a.0.cmp(&b.0);
+        
+        for _i in 0..100 {
+            let mut heap1 = MyMinHeap::new(&compare);
+            let mut heap2 = MyMinHeap::new(&compare);
+            let mut union: Vec<(i32, usize)> = Vec::new();
+            
+            for _j in 0..rng.gen_range(0..100) {
+                let element = (rng.gen_range(0..50), 1);
+                heap1.insert(element);
+                union.push(element);
+            }
+            for _j in 0..rng.gen_range(0..100) {
+                let element = (rng.gen_range(0..50), 2);
+                heap2.insert(element);
+                union.push(element);
+            }
+            
+            // take some, the remaining stay in their heaps
+            let k = rng.gen_range(0..=union.len());
+            let taken: Vec<(i32, usize)> = MinOfTwo::new(&mut heap1, &mut heap2).take(k).collect();
+            
+            // ties favor the first heap, whose tag is smaller
+            union.sort_by(|a, b| a.0.cmp(&b.0).then(a.1.cmp(&b.1)));
+            assert_eq!(taken, union[..k]);
+            
+            let mut remaining: Vec<(i32, usize)> = Vec::new();
+            while let Some(e) = heap1.take_min() {
+                assert_eq!(e.1, 1);
+                remaining.push(e);
+            }
+            while let Some(e) = heap2.take_min() {
+                assert_eq!(e.1, 2);
+                remaining.push(e);
+            }
+            remaining.sort_by(|a, b| a.0.cmp(&b.0).then(a.1.cmp(&b.1)));
+            assert_eq!(remaining, union[k..]);
+        }
+    }
+    
+    #[test]
+    fn test_make_heap_in_place() {
+        let mut rng: StdRng = SeedableRng::seed_from_u64(123456);
+        let compare = |a: &i32, b: &i32| a.cmp(b);
+        
+        for _i in 0..300 {
+            let len = rng.gen_range(0..1000);
+            let max = rng.gen_range(1..100);
+            let mut vec: Vec<i32> = (0..len).map(|_| rng.gen_range(0..max)).collect();
+            let mut vec_for_ref = vec.clone();
+            
+            make_heap_in_place(&mut vec, &compare);
+            
+            assert!(is_min_heap(&vec, &compare));
+            if len > 0 {
+                assert_eq!(vec[0], *vec_for_ref.iter().min().unwrap());
+            }
+            
+            // it's a permutation of the original
+            let mut sorted = vec.clone();
+            sorted.sort();
+            vec_for_ref.sort();
+            assert_eq!(sorted, vec_for_ref);
+        }
+        
+        assert!(!is_min_heap(&[2, 1], &compare));
+    }
+    
+    #[test]
+    fn test_heap_sort_top_k() {
+        let mut rng: StdRng = SeedableRng::seed_from_u64(123456);
+        let compare = |a: &i32, b: &i32| a.cmp(b);
+        
+        for _i in 0..300 {
+            let len = rng.gen_range(0..1000);
+            let max = rng.gen_range(1..100);
+            let mut vec: Vec<i32> = (0..len).map(|_| rng.gen_range(0..max)).collect();
+            let mut vec_for_ref = vec.clone();
+            vec_for_ref.sort();
+            
+            let k = rng.gen_range(0..(len + 10));
+            heap_sort_top_k(&mut vec, k, &compare);
+            
+            let k = k.min(len);
+            assert_eq!(vec[..k], vec_for_ref[..k]);
+            assert!(is_min_heap(&vec[k..], &compare));
+            
+            // it's a permutation of the original
+            vec.sort();
+            assert_eq!(vec, vec_for_ref);
+        }
+    }
+    
+    #[test]
+    fn test_heap_sort() {
+        let mut rng: StdRng = SeedableRng::seed_from_u64(123456);
+        
+        for _i in 0..300 {
+            let len = rng.gen_range(0..1000);
+            let max = rng.gen_range(1..100);
+            let mut vec: Vec<i32> = (0..len).map(|_| rng.gen_range(0..max)).collect();
+            let mut vec_for_ref = vec.clone();
+            vec_for_ref.sort_unstable();
+            
+            heap_sort(&mut vec, &|a: &i32, b: &i32| a.cmp(b));
+            assert_eq!(vec, vec_for_ref);
+        }
+        
+        for _i in 0..100 {
+            let len = rng.gen_range(0..300);
+            let mut vec: Vec<String> = (0..len)
+                .map(|_| (0..rng.gen_range(0..10)).map(|_| rng.gen_range('a'..='z')).collect())
+                .collect();
+            let mut vec_for_ref = vec.clone();
+            vec_for_ref.sort_unstable_by_key(|s| s.len());
+            
+            heap_sort(&mut vec, &|a: &String, b: &String| a.len().cmp(&b.len()));
+            
+            // it's not stable, so the strings of the same length may be in different order
+            let lengths: Vec<usize> = vec.iter().map(|s| s.len()).collect();
+            let lengths_for_ref: Vec<usize> = vec_for_ref.iter().map(|s| s.len()).collect();
+            assert_eq!(lengths, lengths_for_ref);
+            vec.sort();
+            vec_for_ref.sort();
+            assert_eq!(vec, vec_for_ref);
+        }
+    }
+    
+    #[test]
+    fn test_heap_sort_cancellable() {
+        check_cancellable_sort("heap_sort", HEAP_SORT_COMPARISONS_AFTER_CANCEL, |arr, compare, cancel| {
+            heap_sort_cancellable(arr, &compare, cancel)
+        });
+    }
+    
+    #[test]
+    fn test_heap_sort_ord() {
+        let mut rng: StdRng = SeedableRng::seed_from_u64(123456);
+        
+        let mut vec: Vec<i32> = (0..1000).map(|_| rng.gen_range(0..100)).collect();
+        let mut vec_ref = vec.clone();
+        heap_sort_ord(&mut vec);
+        heap_sort(&mut vec_ref, &|a: &i32, b: &i32| a.cmp(b));
+        assert_eq!(vec, vec_ref);
+        
+        let mut vec: Vec<String> = vec!["pear".to_string(), "apple".to_string(), "fig".to_string(), "apple".to_string()];
+        heap_sort_ord(&mut vec);
+        assert_eq!(vec, vec!["apple", "apple", "fig", "pear"]);
+    }
+    
+    #[test]
+    fn test_heap_sort_top_k_comparison_count() {
+        let mut rng: StdRng = SeedableRng::seed_from_u64(123456);
+        let len = 100000;
+        let mut vec: Vec<i32> = (0..len).map(|_| rng.gen_range(0..1000000)).collect();
+        
+        let count: Cell<usize> = Cell::new(0);
+        let counting_compare = |a: &i32, b: &i32| {
+            count.set(count.get() + 1);
+            a.cmp(b)
+        };
+        heap_sort_top_k(&mut vec, 10, &counting_compare);
+        
+        // building heap takes less than 2n comparisons, each extraction takes about 2 log n.
+        // full sorting takes about n log n, which is about 1700000 here.
+        assert!(count.get() < 3 * len, "too many comparisons: {}", count.get());
+    }
+}
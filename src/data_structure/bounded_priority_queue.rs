@@ -0,0 +1,171 @@
+use std::cmp::Ordering;
+
+use crate::data_structure::binary_heap::MyMinHeap;
+
+// Keeps at most capacity elements, which are the smallest ones offered so far.
+// It's a max-heap, so that the largest retained element can be evicted in O(log k) when a smaller one comes.
+// The max-heap is MyMinHeap with the inverted comparator.
+pub struct BoundedPriorityQueue<'a, T, Comparator>
+    where
+        Comparator: Fn(&T, &T) -> Ordering,
+{
+    heap: MyMinHeap<'a, Entry<'a, T, Comparator>, EntryComparator<'a, T, Comparator>>,
+    capacity: usize,
+    compare: &'a Comparator,
+}
+
+struct Entry<'a, T, Comparator> {
+    value: T,
+    compare: &'a Comparator,
+}
+
+type EntryComparator<'a, T, Comparator> =
+    fn(&Entry<'a, T, Comparator>, &Entry<'a, T, Comparator>) -> Ordering;
+
+// same as StablePriorityQueue, the heap's comparator is a plain function,
+// and the user-provided comparator is carried by the entries.
+// the arguments are swapped, so the heap's minimum is the largest value.
+fn compare_entries_inverted<T, Comparator>(
+    a: &Entry<T, Comparator>, b: &Entry<T, Comparator>,
+) -> Ordering
+    where
+        Comparator: Fn(&T, &T) -> Ordering
+{
+    (a.compare)(&b.value, &a.value)
+}
+
+impl<'a, T, Comparator> BoundedPriorityQueue<'a, T, Comparator>
+    where
+        Comparator: Fn(&T, &T) -> Ordering,
+{
+    pub fn new(capacity: usize, compare: &'a Comparator) -> Self {
+        Self {
+            heap: MyMinHeap::with_capacity(
+                capacity,
+                &(compare_entries_inverted::<T, Comparator> as EntryComparator<'a, T, Comparator>),
+            ),
+            capacity,
+            compare,
+        }
+    }
+    
+    // Offer a value. If it's not full, the value is kept and it returns None.
+    // If it's full, the largest among the retained ones and the new one is evicted and returned.
+    // If the new value equals the largest retained one, the new value is evicted, so the earlier one stays.
+    pub fn offer(&mut self, value: T) -> Option<T> {
+        let compare = self.compare;
+        if self.heap.len() < self.capacity {
+            self.heap.insert(Entry { value, compare });
+            return None;
+        }
+        
+        match self.heap.peek_min() {
+            // the capacity is 0
+            None => Some(value),
+            Some(max) => {
+                if compare(&value, &max.value) == Ordering::Less {
+                    self.heap.replace_min(Entry { value, compare }).map(|entry| entry.value)
+                } else {
+                    Some(value)
+                }
+            }
+        }
+    }
+    
+    pub fn peek_max(&self) -> Option<&T> {
+        self.heap.peek_min().map(|entry| &entry.value)
+    }
+    
+    pub fn len(&self) -> usize {
+        self.heap.len()
+    }
+    
+    pub fn is_empty(&self) -> bool {
+        self.heap.is_empty()
+    }
+    
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+    
+    // the retained elements in ascending order
+    pub fn into_sorted_vec(mut self) -> Vec<T> {
+        // the heap drains from the largest
+        let mut result: Vec<T> = self.heap.drain_sorted().map(|entry| entry.value).collect();
+        result.reverse();
+        result
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::{Rng, SeedableRng};
+    use rand::prelude::StdRng;
+    
+    use super::*;
+    
+    #[test]
+    fn test_bounded_priority_queue() {
+        let compare = |a: &i32, b: &i32| a.cmp(b);
+        let mut queue = BoundedPriorityQueue::new(3, &compare);
+        
+        assert_eq!(queue.offer(5), None);
+        assert_eq!(queue.offer(2), None);
+        assert_eq!(queue.offer(8), None);
+        assert_eq!(queue.len(), 3);
+        assert_eq!(queue.capacity(), 3);
+        assert_eq!(queue.peek_max(), Some(&8));
+        
+        // smaller than the max, the max is evicted
+        assert_eq!(queue.offer(1), Some(8));
+        // not smaller than the max, the new one is evicted
+        assert_eq!(queue.offer(9), Some(9));
+        assert_eq!(queue.offer(5), Some(5));
+        
+        assert_eq!(queue.into_sorted_vec(), vec![1, 2, 5]);
+        
+        let mut queue = BoundedPriorityQueue::new(0, &compare);
+        assert_eq!(queue.offer(1), Some(1));
+        assert!(queue.is_empty());
+    }
+    
+    #[test]
+    fn test_bounded_priority_queue_random() {
+        let mut rng: StdRng = SeedableRng::seed_from_u64(123456);
+        let compare = |a: &i32, b: &i32| a.cmp(b);
+        
+        for _i in 0..100 {
+            let capacity = rng.gen_range(0..50);
+            let len = rng.gen_range(0..500);
+            let max = rng.gen_range(1..1000);
+            let values: Vec<i32> = (0..len).map(|_| rng.gen_range(0..max)).collect();
+            
+            let mut queue = BoundedPriorityQueue::new(capacity, &compare);
+            let mut evicted: Vec<i32> = Vec::new();
+            for (index, value) in values.iter().enumerate() {
+                let max_before = queue.peek_max().copied();
+                match queue.offer(*value) {
+                    None => assert!(index < capacity),
+                    Some(e) => {
+                        // the evicted one is the largest among the retained ones and the new one
+                        let expected = max_before.map_or(*value, |m| m.max(*value));
+                        assert_eq!(e, expected);
+                        evicted.push(e);
+                    }
+                }
+                assert_eq!(queue.len(), (index + 1).min(capacity));
+            }
+            
+            let retained = queue.into_sorted_vec();
+            let mut sorted = values.clone();
+            sorted.sort();
+            assert_eq!(retained, sorted[..capacity.min(len)]);
+            
+            // nothing is lost
+            let mut all = retained.clone();
+            all.extend(evicted);
+            all.sort();
+            assert_eq!(all, sorted);
+        }
+    }
+}
@@ -1,5 +1,6 @@
+use std::cmp::Ordering;
 use std::collections;
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 use std::hash::Hash;
 
 use crate::data_structure::matrix2d::Matrix2D;
@@ -28,6 +29,19 @@ impl<NodeRef: Eq + Hash + Clone, EdgeData: Clone> DAGTraverser<NodeRef, EdgeData
     }
 }
 
+// Build from (src, dst, edge_data) tuples. If an edge is repeated, the later one overrides.
+// Every node in the edges gets an entry, including the ones without edges coming out.
+pub fn hash_map_dag_from_edges<NodeRef: Eq + Hash + Clone, EdgeData>(
+    edges: impl IntoIterator<Item=(NodeRef, NodeRef, EdgeData)>,
+) -> HashMapDAG<NodeRef, EdgeData> {
+    let mut graph: HashMapDAG<NodeRef, EdgeData> = HashMap::new();
+    for (src, dst, edge_data) in edges {
+        graph.entry(dst.clone()).or_default();
+        graph.entry(src).or_default().insert(dst, edge_data);
+    }
+    graph
+}
+
 // matrix[row][col] = edge_data, where row index is src node and column index is dst node.
 pub type MatrixDAG<EdgeData> = Matrix2D<Option<EdgeData>>;
 
@@ -48,4 +62,163 @@ impl<EdgeData: Clone> DAGTraverser<usize, EdgeData> for MatrixDAG<EdgeData> {
             }
         )
     }
-}
\ No newline at end of file
+}
+
+impl<EdgeData> MatrixDAG<EdgeData> {
+    // Kahn's algorithm: repeatedly remove the nodes that have no edge coming in.
+    // If all nodes can be removed, there is no cycle. The nodes in a cycle always have edges coming in.
+    // It takes O(n^2) for n nodes, as every matrix cell is visited once.
+    pub fn is_acyclic(&self) -> bool {
+        let node_num = self.rows();
+        assert_eq!(node_num, self.cols(), "the matrix of DAG should be square");
+        
+        let mut in_degrees: Vec<usize> = (0..node_num)
+            .map(|node| self.column_iter(node).filter(|edge| edge.is_some()).count())
+            .collect();
+        
+        let mut no_incoming: Vec<usize> = (0..node_num).filter(|node| in_degrees[*node] == 0).collect();
+        let mut removed_num = 0;
+        
+        while let Some(node) = no_incoming.pop() {
+            removed_num += 1;
+            for (dst, edge) in self.borrow_row(node).iter().enumerate() {
+                if edge.is_some() {
+                    in_degrees[dst] -= 1;
+                    if in_degrees[dst] == 0 {
+                        no_incoming.push(dst);
+                    }
+                }
+            }
+        }
+        
+        removed_num == node_num
+    }
+}
+
+// Build from (src, dst, edge_data) tuples. The nodes are 0..node_num.
+// If an edge is repeated, the later one overrides, same as hash_map_dag_from_edges.
+pub fn matrix_dag_from_edges<EdgeData: Clone>(
+    node_num: usize, edges: impl IntoIterator<Item=(usize, usize, EdgeData)>,
+) -> MatrixDAG<EdgeData> {
+    let mut matrix: MatrixDAG<EdgeData> = Matrix2D::new(node_num, node_num, None);
+    for (src, dst, edge_data) in edges {
+        matrix.set(src, dst, Some(edge_data));
+    }
+    matrix
+}
+
+// Get the topological order of the nodes reachable from the given nodes.
+// In the result, if there is an edge from a to b, a is before b.
+// It does depth-first search, and the reversed post-order is a topological order.
+// Panics if there is a cycle.
+pub fn topological_order<NodeRef, EdgeData, Traverser>(
+    traverser: &Traverser, nodes: impl Iterator<Item=NodeRef>,
+) -> Vec<NodeRef>
+    where
+        NodeRef: Eq + Hash + Clone,
+        Traverser: DAGTraverser<NodeRef, EdgeData>
+{
+    // the nodes that are being visited in the current search path
+    let mut visiting: HashSet<NodeRef> = HashSet::new();
+    // the nodes that all its successors have been visited
+    let mut finished: HashSet<NodeRef> = HashSet::new();
+    let mut post_order: Vec<NodeRef> = Vec::new();
+    
+    for node in nodes {
+        visit_for_topological_order(traverser, node, &mut visiting, &mut finished, &mut post_order);
+    }
+    
+    post_order.reverse();
+    post_order
+}
+
+fn visit_for_topological_order<NodeRef, EdgeData, Traverser>(
+    traverser: &Traverser, node: NodeRef,
+    visiting: &mut HashSet<NodeRef>,
+    finished: &mut HashSet<NodeRef>,
+    post_order: &mut Vec<NodeRef>,
+)
+    where
+        NodeRef: Eq + Hash + Clone,
+        Traverser: DAGTraverser<NodeRef, EdgeData>
+{
+    if finished.contains(&node) {
+        return;
+    }
+    
+    assert!(visiting.insert(node.clone()), "the graph has a cycle");
+    
+    for (_edge_data, next_node) in traverser.get_edges_coming_out(node.clone()) {
+        visit_for_topological_order(traverser, next_node, visiting, finished, post_order);
+    }
+    
+    visiting.remove(&node);
+    finished.insert(node.clone());
+    post_order.push(node);
+}
+
+// Map each edge coming out of the node, and select the best one by the comparator
+// (the smallest one. if there are multiple, the first one).
+// If the mapping gives None, the edge is skipped.
+// Returns the edge, the node it goes to, and the mapped value. Returns None if no edge is selected.
+// It's the common step of DAG dynamic programming, such as shortest path, longest path and widest path.
+pub fn best_outgoing<NodeRef, EdgeData, Traverser, T, MapFunc, Comparator>(
+    traverser: &Traverser, node: NodeRef, mut map: MapFunc, compare: &Comparator,
+) -> Option<(EdgeData, NodeRef, T)>
+    where
+        Traverser: DAGTraverser<NodeRef, EdgeData>,
+        MapFunc: FnMut(&EdgeData, &NodeRef) -> Option<T>,
+        Comparator: Fn(&T, &T) -> Ordering
+{
+    traverser.get_edges_coming_out(node)
+        .filter_map(|(edge_data, next_node)| {
+            let mapped = map(&edge_data, &next_node)?;
+            Some((edge_data, next_node, mapped))
+        })
+        .min_by(|a, b| compare(&a.2, &b.2))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    
+    #[test]
+    fn test_best_outgoing() {
+        let graph: HashMapDAG<&str, i32> =
+            hash_map_dag_from_edges([("a", "b", 3), ("a", "c", 1), ("a", "d", 2), ("b", "d", 5)]);
+        let compare = |a: &i32, b: &i32| a.cmp(b);
+        
+        // the minimum weight edge
+        let best = best_outgoing(&graph, "a", |weight: &i32, _next: &&str| Some(*weight), &compare);
+        assert_eq!(best, Some((1, "c", 1)));
+        
+        // the maximum weight edge, by inverting the comparator
+        let best = best_outgoing(&graph, "a", |weight: &i32, _next: &&str| Some(*weight), &|a: &i32, b: &i32| b.cmp(a));
+        assert_eq!(best, Some((3, "b", 3)));
+        
+        // skip the edges by mapping to None
+        let best = best_outgoing(
+            &graph, "a", |weight: &i32, next: &&str| if *next == "c" { None } else { Some(*weight) }, &compare,
+        );
+        assert_eq!(best, Some((2, "d", 2)));
+        
+        // no edge coming out
+        assert_eq!(best_outgoing(&graph, "d", |weight: &i32, _next: &&str| Some(*weight), &compare), None);
+    }
+    
+    #[test]
+    fn test_matrix_dag_is_acyclic() {
+        let matrix = matrix_dag_from_edges(5, [(0, 1, ()), (0, 2, ()), (1, 3, ()), (2, 3, ()), (3, 4, ())]);
+        assert!(matrix.is_acyclic());
+        
+        let matrix = matrix_dag_from_edges(4, [(0, 1, ()), (1, 2, ()), (2, 1, ()), (2, 3, ())]);
+        assert!(!matrix.is_acyclic());
+        
+        // self loop
+        let matrix = matrix_dag_from_edges(2, [(0, 1, ()), (1, 1, ())]);
+        assert!(!matrix.is_acyclic());
+        
+        let matrix: MatrixDAG<()> = matrix_dag_from_edges(0, []);
+        assert!(matrix.is_acyclic());
+    }
+}
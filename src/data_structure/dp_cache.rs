@@ -0,0 +1,60 @@
+use crate::data_structure::matrix2d::Matrix2D;
+use crate::functional::lazy_eval::Cache;
+
+// A cache for dynamic programming on (usize, usize) keys, where the result is optional.
+// Using Matrix2D<Option<Option<V>>> for it works, but the two layers of Option are confusing:
+// the outer one is whether it's computed, the inner one is the result.
+// This names the two states of a cell explicitly.
+pub struct DpCache2D<V> {
+    cells: Matrix2D<CacheCell<V>>,
+}
+
+#[derive(Default)]
+enum CacheCell<V> {
+    #[default]
+    Empty,
+    Computed(Option<V>),
+}
+
+impl<V> DpCache2D<V> {
+    pub fn new(rows: usize, cols: usize) -> Self {
+        DpCache2D { cells: Matrix2D::new_defaulted(rows, cols) }
+    }
+    
+    pub fn is_computed(&self, key: &(usize, usize)) -> bool {
+        matches!(self.cells.at(key.0, key.1), CacheCell::Computed(_))
+    }
+}
+
+impl<V: Clone> Cache<(usize, usize), Option<V>> for DpCache2D<V> {
+    fn get_from_cache(&self, key: &(usize, usize)) -> Option<Option<V>> {
+        match self.cells.at(key.0, key.1) {
+            CacheCell::Empty => None,
+            CacheCell::Computed(value) => Some(value.clone()),
+        }
+    }
+    
+    fn put_to_cache(&mut self, key: &(usize, usize), value: Option<V>) {
+        self.cells.set(key.0, key.1, CacheCell::Computed(value));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    
+    #[test]
+    fn test_dp_cache_2d() {
+        let mut cache: DpCache2D<i32> = DpCache2D::new(2, 3);
+        assert_eq!(cache.get_from_cache(&(1, 2)), None);
+        assert!(!cache.is_computed(&(1, 2)));
+        
+        cache.put_to_cache(&(1, 2), None);
+        assert_eq!(cache.get_from_cache(&(1, 2)), Some(None));
+        assert!(cache.is_computed(&(1, 2)));
+        
+        cache.put_to_cache(&(0, 1), Some(5));
+        assert_eq!(cache.get_from_cache(&(0, 1)), Some(Some(5)));
+        assert_eq!(cache.get_from_cache(&(0, 0)), None);
+    }
+}
@@ -0,0 +1,280 @@
+use std::cmp::Ordering;
+
+use slotmap::{new_key_type, SlotMap};
+
+use crate::algo::midpoint::midpoint;
+use crate::data_structure::binary_heap::{sift_down_with_swap, sift_up_with_swap};
+
+// A min-heap that gives a handle for each inserted element, and allows changing the element by handle.
+// It's used in algorithms like Dijkstra, where the distance of a node in the queue may be decreased.
+// MyMinHeap cannot do that, because the position of an element changes when sifting.
+// This records the position of each handle, and updates it whenever two entries swap.
+// The sifting is shared with MyMinHeap, with a swap function that also updates the positions.
+pub struct IndexedMinHeap<'a, T, Comparator>
+    where
+        Comparator: Fn(&T, &T) -> Ordering,
+{
+    data: Vec<Entry<T>>,
+    // positions[handle] is the index of the entry in data. the handle is removed when it's taken out.
+    // the slots of the taken ones are reused, so it doesn't grow with the total number of insertions.
+    // the handle has a version, so a stale handle will not refer to another element in the reused slot.
+    positions: SlotMap<HeapHandle, usize>,
+    comparator: &'a Comparator,
+}
+
+struct Entry<T> {
+    value: T,
+    handle: HeapHandle,
+}
+
+new_key_type! {
+    pub struct HeapHandle;
+}
+
+impl<'a, T, Comparator> IndexedMinHeap<'a, T, Comparator>
+    where
+        Comparator: Fn(&T, &T) -> Ordering,
+{
+    pub fn new(comparator: &'a Comparator) -> Self {
+        Self {
+            data: Vec::new(),
+            positions: SlotMap::with_key(),
+            comparator,
+        }
+    }
+    
+    fn sift_up(&mut self, index: usize) {
+        let comparator = self.comparator;
+        let positions = &mut self.positions;
+        sift_up_with_swap(
+            &mut self.data,
+            &|a: &Entry<T>, b: &Entry<T>| comparator(&a.value, &b.value),
+            index,
+            &mut |data: &mut [Entry<T>], i, j| swap_entries(data, positions, i, j),
+        );
+    }
+    
+    fn sift_down(&mut self, index: usize) {
+        let comparator = self.comparator;
+        let positions = &mut self.positions;
+        sift_down_with_swap(
+            &mut self.data,
+            &|a: &Entry<T>, b: &Entry<T>| comparator(&a.value, &b.value),
+            index,
+            &mut |data: &mut [Entry<T>], i, j| swap_entries(data, positions, i, j),
+        );
+    }
+    
+    fn position_of(&self, handle: HeapHandle) -> usize {
+        match self.positions.get(handle) {
+            None => panic!("the element of handle {:?} has been taken out", handle),
+            Some(position) => *position,
+        }
+    }
+    
+    // parent <= child, and the positions match
+    fn check_valid(&self) {
+        for i in 1..self.data.len() {
+            assert!((self.comparator)(&self.data[midpoint(0, i - 1)].value, &self.data[i].value).is_le());
+        }
+        assert_eq!(self.positions.len(), self.data.len());
+        for (i, entry) in self.data.iter().enumerate() {
+            assert_eq!(self.positions.get(entry.handle), Some(&i));
+        }
+    }
+    
+    pub fn insert(&mut self, value: T) -> HeapHandle {
+        let handle = self.positions.insert(self.data.len());
+        self.data.push(Entry { value, handle });
+        
+        self.sift_up(self.data.len() - 1);
+        handle
+    }
+    
+    pub fn take_min(&mut self) -> Option<(HeapHandle, T)> {
+        if self.data.is_empty() {
+            return None;
+        }
+        
+        // move the last element to the root, then sift it down
+        let last_index = self.data.len() - 1;
+        swap_entries(&mut self.data, &mut self.positions, 0, last_index);
+        let Entry { value, handle } = self.data.pop().unwrap();
+        self.positions.remove(handle);
+        
+        if !self.data.is_empty() {
+            self.sift_down(0);
+        }
+        
+        Some((handle, value))
+    }
+    
+    pub fn peek_min(&self) -> Option<(HeapHandle, &T)> {
+        self.data.first().map(|entry| (entry.handle, &entry.value))
+    }
+    
+    // None if the element has been taken out
+    pub fn get(&self, handle: HeapHandle) -> Option<&T> {
+        self.positions.get(handle).map(|position| &self.data[*position].value)
+    }
+    
+    // Replace the element with a smaller or equal one. It only needs to sift up. O(log n).
+    // Panics if the new value is larger, or the element has been taken out.
+    pub fn decrease_key(&mut self, handle: HeapHandle, new_value: T) {
+        let position = self.position_of(handle);
+        assert!(
+            (self.comparator)(&new_value, &self.data[position].value).is_le(),
+            "decrease_key with a larger value"
+        );
+        
+        self.data[position].value = new_value;
+        self.sift_up(position);
+    }
+    
+    // Change the element in any way. It may go either up or down. O(log n).
+    // Panics if the element has been taken out.
+    pub fn update<F: FnOnce(&mut T)>(&mut self, handle: HeapHandle, f: F) {
+        let position = self.position_of(handle);
+        f(&mut self.data[position].value);
+        
+        // at most one of them moves it
+        self.sift_up(position);
+        let position = self.position_of(handle);
+        self.sift_down(position);
+    }
+    
+    pub fn len(&self) -> usize {
+        self.data.len()
+    }
+    
+    pub fn is_empty(&self) -> bool {
+        self.data.is_empty()
+    }
+}
+
+// all swaps go through this, so that the positions are always up to date
+fn swap_entries<T>(data: &mut [Entry<T>], positions: &mut SlotMap<HeapHandle, usize>, i: usize, j: usize) {
+    data.swap(i, j);
+    positions[data[i].handle] = i;
+    positions[data[j].handle] = j;
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::{Rng, SeedableRng};
+    use rand::prelude::StdRng;
+    
+    use super::*;
+    
+    #[test]
+    fn test_indexed_min_heap() {
+        let mut rng: StdRng = SeedableRng::seed_from_u64(123456);
+        let compare = |a: &i32, b: &i32| a.cmp(b);
+        
+        for _i in 0..100 {
+            let mut heap = IndexedMinHeap::new(&compare);
+            // the brute-force reference. the elements that are still in the heap, by handle
+            let mut reference: Vec<(HeapHandle, i32)> = Vec::new();
+            
+            for _j in 0..500 {
+                match rng.gen_range(0..4) {
+                    0 | 1 => {
+                        let value = rng.gen_range(0..1000);
+                        let handle = heap.insert(value);
+                        reference.push((handle, value));
+                    }
+                    2 => {
+                        if !reference.is_empty() {
+                            let ref_index = rng.gen_range(0..reference.len());
+                            let (handle, value) = reference[ref_index];
+                            let new_value = value - rng.gen_range(0..100);
+                            heap.decrease_key(handle, new_value);
+                            reference[ref_index].1 = new_value;
+                        }
+                    }
+                    _ => {
+                        let taken = heap.take_min();
+                        let ref_min = reference.iter().map(|(_, value)| *value).min();
+                        assert_eq!(taken.map(|(_, value)| value), ref_min);
+                        
+                        // there may be multiple minimums. the taken one should be one of them
+                        if let Some((handle, value)) = taken {
+                            let ref_index = reference.iter().position(|(h, _)| *h == handle).unwrap();
+                            assert_eq!(reference[ref_index].1, value);
+                            reference.swap_remove(ref_index);
+                            assert_eq!(heap.get(handle), None);
+                        }
+                    }
+                }
+                
+                heap.check_valid();
+                assert_eq!(heap.len(), reference.len());
+                for (handle, value) in reference.iter() {
+                    assert_eq!(heap.get(*handle), Some(value));
+                }
+            }
+        }
+    }
+    
+    #[test]
+    fn test_indexed_min_heap_update() {
+        let mut rng: StdRng = SeedableRng::seed_from_u64(123456);
+        let compare = |a: &i32, b: &i32| a.cmp(b);
+        
+        let mut heap = IndexedMinHeap::new(&compare);
+        let mut reference: Vec<(HeapHandle, i32)> = (0..300).map(|_| {
+            let value = rng.gen_range(0..1000);
+            (heap.insert(value), value)
+        }).collect();
+        
+        // both increasing and decreasing
+        for _i in 0..1000 {
+            let ref_index = rng.gen_range(0..reference.len());
+            let delta = rng.gen_range(-100..100);
+            heap.update(reference[ref_index].0, |value| *value += delta);
+            reference[ref_index].1 += delta;
+            heap.check_valid();
+        }
+        
+        let mut expected: Vec<i32> = reference.iter().map(|(_, value)| *value).collect();
+        expected.sort();
+        let mut taken: Vec<i32> = Vec::new();
+        while let Some((_, value)) = heap.take_min() {
+            taken.push(value);
+        }
+        assert_eq!(taken, expected);
+    }
+    
+    #[test]
+    fn test_indexed_min_heap_reuses_slots() {
+        let compare = |a: &i32, b: &i32| a.cmp(b);
+        let mut heap = IndexedMinHeap::new(&compare);
+        
+        let first = heap.insert(1);
+        heap.take_min();
+        
+        // a long-running queue inserts and takes many times, but holds few at a time
+        for i in 0..10000 {
+            let a = heap.insert(i);
+            let b = heap.insert(i + 1);
+            assert_eq!(heap.take_min(), Some((a, i)));
+            assert_eq!(heap.take_min(), Some((b, i + 1)));
+            heap.check_valid();
+        }
+        assert!(heap.positions.capacity() < 100, "{}", heap.positions.capacity());
+        
+        // the slot of the first handle has been reused, but the stale handle doesn't see the new element
+        let handle = heap.insert(5);
+        assert_eq!(heap.get(first), None);
+        assert_eq!(heap.get(handle), Some(&5));
+    }
+    
+    #[test]
+    #[should_panic(expected = "decrease_key with a larger value")]
+    fn test_decrease_key_with_larger_value() {
+        let compare = |a: &i32, b: &i32| a.cmp(b);
+        let mut heap = IndexedMinHeap::new(&compare);
+        let handle = heap.insert(5);
+        heap.decrease_key(handle, 6);
+    }
+}
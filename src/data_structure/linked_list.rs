@@ -2,12 +2,16 @@
 // but quick sorting on linked list requires swapping, thus require mutable borrow to LinkedList, which is not allowed.
 // Implement a linked list using SlotMap, where cursor does not borrow the list.
 
+use std::cell::RefCell;
+use std::cmp::Ordering;
 use std::collections::HashSet;
 use std::fmt::Debug;
 use std::marker::PhantomData;
 
 use slotmap::{new_key_type, SlotMap};
 
+use crate::data_structure::binary_heap::MyMinHeap;
+
 // A doubly-linked-list implemented using SlotMap.
 // Its cursor does not borrow the list, thus allowing safe quick sorting.
 pub struct MyLinkedList<T> {
@@ -25,6 +29,15 @@ struct Node<T> {
     prev: Option<NodeKey>,
 }
 
+enum GallopResult {
+    // landed on a node before the value, continue with a longer jump
+    Continue,
+    Found(NodeKey),
+    NotFound,
+    // landed on a node after the value. (the first skipped node, the number of skipped nodes)
+    JumpedOver(NodeKey, usize),
+}
+
 pub struct Cursor<T> {
     key: NodeKey,
     _phantom: PhantomData<T>,
@@ -72,6 +85,24 @@ impl<T> MyLinkedList<T> {
         }
     }
     
+    // Pre-allocate the slots for n elements. Pushing up to n elements will not reallocate.
+    // The removed nodes' slots are reused, so the capacity is about the maximum size, not the number of pushes.
+    pub fn with_capacity(n: usize) -> MyLinkedList<T> {
+        MyLinkedList {
+            nodes: SlotMap::with_capacity_and_key(n),
+            head_and_tail: None,
+        }
+    }
+    
+    // Make sure that pushing additional more elements will not reallocate.
+    pub fn reserve(&mut self, additional: usize) {
+        self.nodes.reserve(additional);
+    }
+    
+    pub fn capacity(&self) -> usize {
+        self.nodes.capacity()
+    }
+    
     pub fn push_back(&mut self, value: T) -> Cursor<T> {
         let new_node = Node { value, next: None, prev: None };
         let new_key = self.nodes.insert(new_node);
@@ -186,6 +217,21 @@ impl<T> MyLinkedList<T> {
         Some(node.value)
     }
     
+    // Remove all the elements that the predicate returns false, in one traversal. O(n) time.
+    // The cursors of the retained elements are still valid.
+    pub fn retain<Predicate>(&mut self, mut predicate: Predicate)
+        where
+            Predicate: FnMut(&T) -> bool
+    {
+        let mut curr = self.head_and_tail.map(|(head, _tail)| head);
+        while let Some(key) = curr {
+            curr = self.nodes[key].next;
+            if !predicate(&self.nodes[key].value) {
+                self.remove_at(Cursor::internal_new(key));
+            }
+        }
+    }
+    
     // it will panic if an invalid cursor is given
     pub fn borrow(&self, cursor: Cursor<T>) -> &T {
         &self.nodes[cursor.key].value
@@ -200,6 +246,9 @@ impl<T> MyLinkedList<T> {
     
     // it returns true if swap succeeded
     // it can succeed only if a and b are valid cursors and are not equal
+    // it swaps the values, not the nodes, so after swapping, a points to b's old value.
+    // sorting by swapping (e.g. the container-agnostic quick sort) changes what the cursors point to.
+    // use merge_sort_by if the cursors should keep pointing to the same values.
     pub fn swap(&mut self, a: Cursor<T>, b: Cursor<T>) -> bool {
         let keys: [NodeKey; 2] = [a.key, b.key];
         let refs: Option<[&mut Node<T>; 2]> = self.nodes.get_disjoint_mut(keys);
@@ -251,6 +300,387 @@ impl<T> MyLinkedList<T> {
     pub fn iter(&self) -> MyLinkedListIter<T> {
         MyLinkedListIter::new(self)
     }
+    
+    // iterate the cursors from head to tail
+    pub fn cursors(&self) -> MyLinkedListCursorIter<T> {
+        MyLinkedListCursorIter::new(self)
+    }
+    
+    // Iterate the values from `from` to `to`, both inclusive.
+    // `to` should be `from` or after it. Otherwise, it panics after reaching the tail without meeting `to`.
+    pub fn iter_range(&self, from: Cursor<T>, to: Cursor<T>) -> MyLinkedListRangeIter<T> {
+        MyLinkedListRangeIter::new(self, from, to)
+    }
+    
+    // Search in a sorted list. It returns the cursor of an element equal to value
+    // (if there are multiple, it's not necessarily the first one), or None if not found.
+    // Binary search is not possible without random access, so it gallops from both ends alternately:
+    // each side jumps 1, 2, 4, 8... nodes, only comparing the nodes it lands on,
+    // until it jumps past the value, then it binary-searches the nodes skipped in the last jump.
+    // For the value at distance d from the nearer end, it takes O(log d) comparisons,
+    // but walking the links still takes O(d).
+    pub fn find_sorted<Comparator>(&self, value: &T, compare: &Comparator) -> Option<Cursor<T>>
+        where
+            Comparator: Fn(&T, &T) -> Ordering
+    {
+        self.head_and_tail?;
+        
+        // (the last landed node that is before the value in its walking direction, the next jump length)
+        // the front side walks forward from the head, the back side walks backward from the tail.
+        let mut front: (Option<NodeKey>, usize) = (None, 1);
+        let mut back: (Option<NodeKey>, usize) = (None, 1);
+        
+        loop {
+            for (side, forward) in [(&mut front, true), (&mut back, false)] {
+                match self.gallop_once(side, forward, value, compare) {
+                    GallopResult::Continue => {}
+                    GallopResult::Found(key) => return Some(Cursor::internal_new(key)),
+                    GallopResult::NotFound => return None,
+                    GallopResult::JumpedOver(first_skipped, skipped_num) => {
+                        return self.search_walked_segment(first_skipped, skipped_num, forward, value, compare)
+                            .map(Cursor::internal_new);
+                    }
+                }
+            }
+        }
+    }
+    
+    fn neighbor(&self, key: Option<NodeKey>, forward: bool) -> Option<NodeKey> {
+        match (key, self.head_and_tail) {
+            // from the start of the walking direction
+            (None, Some((head, tail))) => Some(if forward { head } else { tail }),
+            (None, None) => None,
+            (Some(key), _) => if forward { self.nodes[key].next } else { self.nodes[key].prev },
+        }
+    }
+    
+    // compare along the walking direction. Less means the node is before the value in that direction.
+    fn compare_along<Comparator>(&self, key: NodeKey, forward: bool, value: &T, compare: &Comparator) -> Ordering
+        where
+            Comparator: Fn(&T, &T) -> Ordering
+    {
+        if forward {
+            compare(&self.nodes[key].value, value)
+        } else {
+            compare(value, &self.nodes[key].value)
+        }
+    }
+    
+    // one jump of a galloping side
+    fn gallop_once<Comparator>(
+        &self, side: &mut (Option<NodeKey>, usize), forward: bool, value: &T, compare: &Comparator,
+    ) -> GallopResult
+        where
+            Comparator: Fn(&T, &T) -> Ordering
+    {
+        let (base, jump_len) = *side;
+        let first_skipped = self.neighbor(base, forward);
+        
+        let mut landing = base;
+        let mut walked = 0;
+        while walked < jump_len {
+            match self.neighbor(landing, forward) {
+                None => break,
+                Some(next) => {
+                    landing = Some(next);
+                    walked += 1;
+                }
+            }
+        }
+        
+        let landing = match landing {
+            Some(landing) if walked > 0 => landing,
+            // the base is the last node in this direction, and it's before the value
+            _ => return GallopResult::NotFound,
+        };
+        
+        match self.compare_along(landing, forward, value, compare) {
+            Ordering::Equal => GallopResult::Found(landing),
+            Ordering::Less if walked < jump_len => GallopResult::NotFound,
+            Ordering::Less => {
+                *side = (Some(landing), jump_len * 2);
+                GallopResult::Continue
+            }
+            Ordering::Greater => GallopResult::JumpedOver(first_skipped.unwrap(), walked - 1),
+        }
+    }
+    
+    // binary search in the len nodes starting from first, in the walking direction.
+    // it compares O(log len) times, and walks O(len) nodes.
+    fn search_walked_segment<Comparator>(
+        &self, first: NodeKey, len: usize, forward: bool, value: &T, compare: &Comparator,
+    ) -> Option<NodeKey>
+        where
+            Comparator: Fn(&T, &T) -> Ordering
+    {
+        let mut start = first;
+        let mut len = len;
+        while len > 0 {
+            let half = len / 2;
+            let mut mid = start;
+            for _ in 0..half {
+                mid = self.neighbor(Some(mid), forward).unwrap();
+            }
+            
+            match self.compare_along(mid, forward, value, compare) {
+                Ordering::Equal => return Some(mid),
+                Ordering::Greater => len = half,
+                Ordering::Less => {
+                    len -= half + 1;
+                    if len > 0 {
+                        start = self.neighbor(Some(mid), forward).unwrap();
+                    }
+                }
+            }
+        }
+        None
+    }
+    
+    // Stable merge sort by re-linking the nodes. The values are not moved or cloned,
+    // so the cursors still point to the same values after sorting.
+    // O(n log n) time in the worst case, O(log n) stack space.
+    pub fn merge_sort_by<Comparator>(&mut self, compare: &Comparator)
+        where
+            Comparator: Fn(&T, &T) -> Ordering
+    {
+        let head = match self.head_and_tail {
+            None => return,
+            Some((head, _tail)) => head,
+        };
+        
+        // during sorting, only the next links are maintained. the prev links are fixed at the end.
+        let new_head = self.merge_sort_chain(head, compare);
+        
+        self.fix_prev_links(new_head);
+    }
+    
+    // after re-linking the next links into a chain from head, fix the prev links, the head and the tail.
+    fn fix_prev_links(&mut self, head: NodeKey) {
+        self.nodes[head].prev = None;
+        let mut tail = head;
+        while let Some(next) = self.nodes[tail].next {
+            self.nodes[next].prev = Some(tail);
+            tail = next;
+        }
+        self.head_and_tail = Some((head, tail));
+    }
+    
+    // Quick select by re-linking the nodes. After it, the element at position k (0-based) is the one
+    // that would be there if sorted, the elements before it are <= it, and the elements after it are >= it.
+    // The values are not moved, so the cursors still point to the same values.
+    // Expected O(n) time. Panics if k is out of bound.
+    pub fn select_nth<Comparator>(&mut self, k: usize, compare: &Comparator) -> &T
+        where
+            Comparator: Fn(&T, &T) -> Ordering
+    {
+        assert!(k < self.size(), "k {} out of bound, the size is {}", k, self.size());
+        
+        // the list is split into three chains: the determined part before the current chain,
+        // the current chain that contains the k-th element, and the determined part after it.
+        let mut before: Option<(NodeKey, NodeKey)> = None;
+        let mut current: (NodeKey, usize) = (self.head_and_tail.unwrap().0, self.size());
+        let mut after: Option<(NodeKey, NodeKey)> = None;
+        let mut k = k;
+        
+        let selected: NodeKey = loop {
+            let (current_head, current_len) = current;
+            
+            // the middle node as pivot. getting it takes O(n), the same as partitioning
+            let mut pivot = current_head;
+            for _ in 0..(current_len / 2) {
+                pivot = self.nodes[pivot].next.unwrap();
+            }
+            
+            let [less, equal, greater] = self.partition_chain(current_head, current_len, pivot, compare);
+            let (less_len, equal_len) = (less.1, equal.1);
+            
+            if k < less_len {
+                let equal_and_greater = self.concat_chains(equal.0, greater.0);
+                after = self.concat_chains(equal_and_greater, after);
+                current = (less.0.unwrap().0, less_len);
+            } else if k < less_len + equal_len {
+                before = self.concat_chains(before, less.0);
+                let greater_and_after = self.concat_chains(greater.0, after);
+                after = self.concat_chains(equal.0, greater_and_after);
+                
+                let mut selected = equal.0.unwrap().0;
+                for _ in 0..(k - less_len) {
+                    selected = self.nodes[selected].next.unwrap();
+                }
+                break selected;
+            } else {
+                let less_and_equal = self.concat_chains(less.0, equal.0);
+                before = self.concat_chains(before, less_and_equal);
+                current = (greater.0.unwrap().0, greater.1);
+                k -= less_len + equal_len;
+            }
+        };
+        
+        let whole = self.concat_chains(before, after).unwrap();
+        self.fix_prev_links(whole.0);
+        
+        &self.nodes[selected].value
+    }
+    
+    // split the chain of len nodes from head into three chains: < pivot, == pivot and > pivot.
+    // the order within each chain is kept. each chain is ((head, tail), len), or (None, 0) if empty.
+    #[allow(clippy::type_complexity)]
+    fn partition_chain<Comparator>(
+        &mut self, head: NodeKey, len: usize, pivot: NodeKey, compare: &Comparator,
+    ) -> [(Option<(NodeKey, NodeKey)>, usize); 3]
+        where
+            Comparator: Fn(&T, &T) -> Ordering
+    {
+        let mut chains: [(Option<(NodeKey, NodeKey)>, usize); 3] = [(None, 0); 3];
+        let mut curr = head;
+        
+        for index in 0..len {
+            let next = self.nodes[curr].next;
+            
+            let chain_index = match compare(&self.nodes[curr].value, &self.nodes[pivot].value) {
+                Ordering::Less => 0,
+                Ordering::Equal => 1,
+                Ordering::Greater => 2,
+            };
+            
+            self.nodes[curr].next = None;
+            let (chain, chain_len) = &mut chains[chain_index];
+            *chain = self.concat_chains(*chain, Some((curr, curr)));
+            *chain_len += 1;
+            
+            if index + 1 < len {
+                curr = next.unwrap();
+            }
+        }
+        
+        chains
+    }
+    
+    // link the tail of a to the head of b. the tail of b should have no next.
+    fn concat_chains(
+        &mut self, a: Option<(NodeKey, NodeKey)>, b: Option<(NodeKey, NodeKey)>,
+    ) -> Option<(NodeKey, NodeKey)> {
+        match (a, b) {
+            (None, b) => b,
+            (a, None) => a,
+            (Some((a_head, a_tail)), Some((b_head, b_tail))) => {
+                self.nodes[a_tail].next = Some(b_head);
+                Some((a_head, b_tail))
+            }
+        }
+    }
+    
+    // Merge multiple sorted lists into one, using a min heap of the lists' fronts. It's stable:
+    // if elements are equal, the one from the former list goes first.
+    // Each list has its own arena, so the nodes cannot be re-linked into another list.
+    // Instead the values are moved (not cloned) from the fronts of the lists. The old cursors become invalid.
+    pub fn merge_k_sorted<Comparator>(lists: Vec<MyLinkedList<T>>, compare: &Comparator) -> MyLinkedList<T>
+        where
+            Comparator: Fn(&T, &T) -> Ordering
+    {
+        // same as merge_sources. the heap contains the indices of non-empty lists.
+        // the comparator peeks the lists, and the merging removes from the lists, so it needs RefCell.
+        let lists = RefCell::new(lists);
+        
+        let heap_comparator = |a: &usize, b: &usize| {
+            let lists = lists.borrow();
+            let a_front = lists[*a].borrow(lists[*a].begin().unwrap());
+            let b_front = lists[*b].borrow(lists[*b].begin().unwrap());
+            compare(a_front, b_front).then(a.cmp(b))
+        };
+        let list_num = lists.borrow().len();
+        let mut min_heap: MyMinHeap<usize, _> = MyMinHeap::with_capacity(list_num, &heap_comparator);
+        
+        for list_index in 0..list_num {
+            let is_empty = lists.borrow()[list_index].begin().is_none();
+            if !is_empty {
+                min_heap.insert(list_index);
+            }
+        }
+        
+        let mut result = MyLinkedList::new();
+        
+        while let Some(list_index) = min_heap.take_min() {
+            let is_empty = {
+                let mut lists = lists.borrow_mut();
+                let list = &mut lists[list_index];
+                let front = list.begin().unwrap();
+                result.push_back(list.remove_at(front).unwrap());
+                list.begin().is_none()
+            };
+            
+            if !is_empty {
+                min_heap.insert(list_index);
+            }
+        }
+        
+        result
+    }
+    
+    // sort the chain that starts from head and ends at the node whose next is None.
+    // returns the new head of the chain.
+    fn merge_sort_chain<Comparator>(&mut self, head: NodeKey, compare: &Comparator) -> NodeKey
+        where
+            Comparator: Fn(&T, &T) -> Ordering
+    {
+        if self.nodes[head].next.is_none() {
+            return head;
+        }
+        
+        // find the middle by slow and fast pointers.
+        // when fast reaches the end, slow is at the end of the first half.
+        let mut slow = head;
+        let mut fast = self.nodes[head].next;
+        while let Some(fast_key) = fast {
+            fast = self.nodes[fast_key].next;
+            if let Some(fast_key) = fast {
+                slow = self.nodes[slow].next.unwrap();
+                fast = self.nodes[fast_key].next;
+            }
+        }
+        
+        // cut the chain into two
+        let second_head = self.nodes[slow].next.take().unwrap();
+        
+        let left = self.merge_sort_chain(head, compare);
+        let right = self.merge_sort_chain(second_head, compare);
+        
+        self.merge_chains(left, right, compare)
+    }
+    
+    // merge two sorted chains by re-linking. if elements are equal, the one in left chain goes first.
+    fn merge_chains<Comparator>(
+        &mut self, left: NodeKey, right: NodeKey, compare: &Comparator,
+    ) -> NodeKey
+        where
+            Comparator: Fn(&T, &T) -> Ordering
+    {
+        let mut left_curr = Some(left);
+        let mut right_curr = Some(right);
+        let mut head: Option<NodeKey> = None;
+        let mut tail: Option<NodeKey> = None;
+        
+        while let (Some(l), Some(r)) = (left_curr, right_curr) {
+            let taken = if compare(&self.nodes[r].value, &self.nodes[l].value) == Ordering::Less {
+                right_curr = self.nodes[r].next;
+                r
+            } else {
+                left_curr = self.nodes[l].next;
+                l
+            };
+            
+            match tail {
+                None => head = Some(taken),
+                Some(tail) => self.nodes[tail].next = Some(taken),
+            }
+            tail = Some(taken);
+        }
+        
+        // append the remaining chain. tail is not None as both chains are not empty.
+        self.nodes[tail.unwrap()].next = left_curr.or(right_curr);
+        
+        head.unwrap()
+    }
 }
 
 pub struct MyLinkedListIter<'a, T> {
@@ -278,8 +708,71 @@ impl<'a, T> Iterator for MyLinkedListIter<'a, T> {
     }
 }
 
+pub struct MyLinkedListRangeIter<'a, T> {
+    list: &'a MyLinkedList<T>,
+    // None after yielding `to`
+    cursor: Option<Cursor<T>>,
+    to: Cursor<T>,
+}
+
+impl<T> MyLinkedListRangeIter<'_, T> {
+    pub fn new(list: &MyLinkedList<T>, from: Cursor<T>, to: Cursor<T>) -> MyLinkedListRangeIter<T> {
+        MyLinkedListRangeIter {
+            list,
+            cursor: Some(from),
+            to,
+        }
+    }
+}
+
+impl<'a, T> Iterator for MyLinkedListRangeIter<'a, T> {
+    type Item = &'a T;
+    
+    fn next(&mut self) -> Option<&'a T> {
+        let cursor = self.cursor?;
+        let value = self.list.borrow(cursor);
+        self.cursor = if cursor == self.to {
+            None
+        } else {
+            let next = self.list.next_cursor(cursor);
+            assert!(next.is_some(), "reached the tail without meeting the end of range");
+            next
+        };
+        Some(value)
+    }
+}
+
+pub struct MyLinkedListCursorIter<'a, T> {
+    list: &'a MyLinkedList<T>,
+    cursor: Option<Cursor<T>>,
+}
+
+impl<T> MyLinkedListCursorIter<'_, T> {
+    pub fn new(list: &MyLinkedList<T>) -> MyLinkedListCursorIter<T> {
+        MyLinkedListCursorIter {
+            list,
+            cursor: list.begin(),
+        }
+    }
+}
+
+impl<'a, T> Iterator for MyLinkedListCursorIter<'a, T> {
+    type Item = Cursor<T>;
+    
+    fn next(&mut self) -> Option<Cursor<T>> {
+        let cursor = self.cursor?;
+        self.cursor = self.list.next_cursor(cursor);
+        Some(cursor)
+    }
+}
+
 #[cfg(test)]
 mod tests {
+    use std::cell::Cell;
+    
+    use rand::{Rng, SeedableRng};
+    use rand::prelude::StdRng;
+    
     use super::*;
     
     #[test]
@@ -309,4 +802,256 @@ mod tests {
         let h = list.insert_after(b, 8);
         list.check_valid();
     }
+    
+    #[test]
+    fn test_with_capacity() {
+        let n = 1000;
+        let mut list = MyLinkedList::with_capacity(n);
+        let capacity = list.capacity();
+        assert!(capacity >= n);
+        
+        for i in 0..n {
+            list.push_back(i);
+        }
+        list.check_valid();
+        assert_eq!(list.capacity(), capacity);
+        assert_eq!(list.iter().copied().collect::<Vec<usize>>(), (0..n).collect::<Vec<usize>>());
+        
+        list.reserve(n);
+        let capacity = list.capacity();
+        assert!(capacity >= 2 * n);
+        for i in 0..n {
+            list.push_front(i);
+        }
+        list.check_valid();
+        assert_eq!(list.capacity(), capacity);
+        assert_eq!(list.size(), 2 * n);
+    }
+    
+    #[test]
+    fn test_retain() {
+        let mut list = MyLinkedList::new();
+        let cursors: Vec<Cursor<i32>> = (0..10).map(|x| list.push_back(x)).collect();
+        list.retain(|x| x % 2 == 0);
+        list.check_valid();
+        assert_eq!(list.iter().copied().collect::<Vec<i32>>(), vec![0, 2, 4, 6, 8]);
+        assert_eq!(list.borrow(cursors[4]), &4);
+        
+        // retain everything
+        list.retain(|_| true);
+        list.check_valid();
+        assert_eq!(list.iter().copied().collect::<Vec<i32>>(), vec![0, 2, 4, 6, 8]);
+        
+        // retain nothing
+        list.retain(|_| false);
+        list.check_valid();
+        assert_eq!(list.size(), 0);
+        assert_eq!(list.begin(), None);
+        
+        // the empty list
+        list.retain(|_| false);
+        list.check_valid();
+    }
+    
+    #[test]
+    fn test_merge_k_sorted() {
+        let mut rng: StdRng = SeedableRng::seed_from_u64(123456);
+        
+        for _i in 0..100 {
+            // (key, tag), only key is compared. the tags increase across lists
+            let list_num = rng.gen_range(0..8);
+            let mut tag = 0;
+            let vecs: Vec<Vec<(i32, usize)>> = (0..list_num).map(|_| {
+                let len = rng.gen_range(0..50);
+                let mut vec: Vec<(i32, usize)> = (0..len).map(|_| {
+                    tag += 1;
+                    (rng.gen_range(0..20), tag)
+                }).collect();
+                vec.sort_by(|a, b| a.0.cmp(&b.0));
+                vec
+            }).collect();
+            
+            let lists: Vec<MyLinkedList<(i32, usize)>> = vecs.iter().map(|vec| {
+                let mut list = MyLinkedList::new();
+                for e in vec {
+                    list.push_back(*e);
+                }
+                list
+            }).collect();
+            
+            let merged = MyLinkedList::merge_k_sorted(lists, &|a: &(i32, usize), b: &(i32, usize)| a.0.cmp(&b.0));
+            merged.check_valid();
+            
+            // std's sort is stable
+            let mut vec_for_ref: Vec<(i32, usize)> = vecs.concat();
+            vec_for_ref.sort_by(|a, b| a.0.cmp(&b.0));
+            assert_eq!(merged.iter().copied().collect::<Vec<(i32, usize)>>(), vec_for_ref);
+        }
+    }
+    
+    #[test]
+    fn test_select_nth() {
+        let mut rng: StdRng = SeedableRng::seed_from_u64(123456);
+        
+        for _i in 0..300 {
+            let len = rng.gen_range(1..1000);
+            let max = rng.gen_range(1..100);
+            let vec: Vec<i32> = (0..len).map(|_| rng.gen_range(0..max)).collect();
+            let mut vec_for_ref = vec.clone();
+            vec_for_ref.sort();
+            
+            let mut list = MyLinkedList::new();
+            let cursors: Vec<Cursor<i32>> = vec.iter().map(|e| list.push_back(*e)).collect();
+            
+            let k = rng.gen_range(0..len);
+            let selected = *list.select_nth(k, &|a: &i32, b: &i32| a.cmp(b));
+            list.check_valid();
+            assert_eq!(selected, vec_for_ref[k]);
+            
+            // partitioned around the k-th
+            let result: Vec<i32> = list.iter().copied().collect();
+            assert_eq!(result[k], selected);
+            assert!(result[..k].iter().all(|e| *e <= selected));
+            assert!(result[k..].iter().all(|e| *e >= selected));
+            
+            // the cursors still point to the same values
+            for (cursor, value) in cursors.iter().zip(vec.iter()) {
+                assert_eq!(list.borrow(*cursor), value);
+            }
+        }
+    }
+    
+    #[test]
+    fn test_find_sorted() {
+        let compare = |a: &i32, b: &i32| a.cmp(b);
+        let mut list = MyLinkedList::new();
+        assert_eq!(list.find_sorted(&1, &compare), None);
+        
+        let cursors: Vec<Cursor<i32>> = [1, 3, 5, 7, 9, 11, 13].iter().map(|x| list.push_back(*x)).collect();
+        
+        // near the head
+        assert_eq!(list.find_sorted(&1, &compare), Some(cursors[0]));
+        assert_eq!(list.find_sorted(&3, &compare), Some(cursors[1]));
+        assert_eq!(list.find_sorted(&2, &compare), None);
+        assert_eq!(list.find_sorted(&0, &compare), None);
+        
+        // near the tail
+        assert_eq!(list.find_sorted(&13, &compare), Some(cursors[6]));
+        assert_eq!(list.find_sorted(&11, &compare), Some(cursors[5]));
+        assert_eq!(list.find_sorted(&12, &compare), None);
+        assert_eq!(list.find_sorted(&14, &compare), None);
+        
+        // in the middle
+        assert_eq!(list.find_sorted(&7, &compare), Some(cursors[3]));
+        assert_eq!(list.find_sorted(&6, &compare), None);
+        
+        // randomly test against linear scan
+        let mut rng: StdRng = SeedableRng::seed_from_u64(123456);
+        for _i in 0..100 {
+            let len = rng.gen_range(0..100);
+            let mut vec: Vec<i32> = (0..len).map(|_| rng.gen_range(0..50)).collect();
+            vec.sort();
+            let mut list = MyLinkedList::new();
+            vec.iter().for_each(|x| { list.push_back(*x); });
+            
+            for target in -1..51 {
+                let found = list.find_sorted(&target, &compare);
+                assert_eq!(found.is_some(), vec.contains(&target));
+                if let Some(found) = found {
+                    assert_eq!(*list.borrow(found), target);
+                }
+            }
+        }
+        
+        // galloping takes O(log d) comparisons for the value at distance d from the nearer end
+        let count = Cell::new(0);
+        let counting_compare = |a: &i32, b: &i32| {
+            count.set(count.get() + 1);
+            a.cmp(b)
+        };
+        let len = 100000;
+        let mut list = MyLinkedList::new();
+        (0..len).for_each(|x| { list.push_back(x * 2); });
+        for target in [0, 1, 2000, 2001, (len - 1000) * 2, (len - 1000) * 2 + 1, len] {
+            count.set(0);
+            let found = list.find_sorted(&target, &counting_compare);
+            assert_eq!(found.map(|cursor| *list.borrow(cursor)), if target % 2 == 0 { Some(target) } else { None });
+            assert!(count.get() <= 60, "target {} compared {} times", target, count.get());
+        }
+    }
+    
+    #[test]
+    fn test_cursors() {
+        let mut list = MyLinkedList::new();
+        assert_eq!(list.cursors().count(), 0);
+        
+        let a = list.push_back(1);
+        let b = list.push_back(2);
+        let c = list.push_front(3);
+        let d = list.insert_after(a, 4);
+        
+        let cursors: Vec<Cursor<i32>> = list.cursors().collect();
+        assert_eq!(cursors, vec![c, a, d, b]);
+        
+        let values: Vec<i32> = cursors.iter().map(|c| *list.borrow(*c)).collect();
+        let values_by_iter: Vec<i32> = list.iter().copied().collect();
+        assert_eq!(values, values_by_iter);
+    }
+    
+    #[test]
+    fn test_iter_range() {
+        let mut list = MyLinkedList::new();
+        let cursors: Vec<Cursor<i32>> = (0..10).map(|i| list.push_back(i)).collect();
+        
+        let values: Vec<i32> = list.iter_range(cursors[2], cursors[6]).copied().collect();
+        assert_eq!(values, vec![2, 3, 4, 5, 6]);
+        
+        let values: Vec<i32> = list.iter_range(cursors[4], cursors[4]).copied().collect();
+        assert_eq!(values, vec![4]);
+        
+        let values: Vec<i32> = list.iter_range(cursors[0], cursors[9]).copied().collect();
+        assert_eq!(values, (0..10).collect::<Vec<i32>>());
+    }
+    
+    #[test]
+    #[should_panic(expected = "reached the tail")]
+    fn test_iter_range_reversed() {
+        let mut list = MyLinkedList::new();
+        let cursors: Vec<Cursor<i32>> = (0..10).map(|i| list.push_back(i)).collect();
+        
+        list.iter_range(cursors[6], cursors[2]).count();
+    }
+    
+    #[test]
+    fn test_merge_sort_by() {
+        let mut rng: StdRng = SeedableRng::seed_from_u64(123456);
+        
+        for _i in 0..300 {
+            let len = rng.gen_range(0..1000);
+            let max = rng.gen_range(1..100);
+            
+            // (key, tag), only key is compared
+            let mut vec: Vec<(i32, usize)> = (0..len).map(|tag| (rng.gen_range(0..max), tag)).collect();
+            
+            let mut list = MyLinkedList::new();
+            let cursors: Vec<Cursor<(i32, usize)>> = vec.iter().map(|e| list.push_back(*e)).collect();
+            
+            list.merge_sort_by(&|a: &(i32, usize), b: &(i32, usize)| a.0.cmp(&b.0));
+            list.check_valid();
+            
+            // the cursors still point to the same values
+            for (cursor, value) in cursors.iter().zip(vec.iter()) {
+                assert_eq!(list.borrow(*cursor), value);
+            }
+            
+            // std's sort is stable
+            vec.sort_by(|a, b| a.0.cmp(&b.0));
+            assert_eq!(list.iter().copied().collect::<Vec<(i32, usize)>>(), vec);
+            
+            // the nodes are moved, so the cursors are in the sorted order. the tag is the original position
+            let cursors_in_order: Vec<Cursor<(i32, usize)>> = list.cursors().collect();
+            let expected: Vec<Cursor<(i32, usize)>> = vec.iter().map(|e| cursors[e.1]).collect();
+            assert_eq!(cursors_in_order, expected);
+        }
+    }
 }
\ No newline at end of file
@@ -30,15 +30,27 @@ impl<T> Matrix2D<T> {
     }
     
     pub fn borrow_row(&self, row: usize) -> &[T] {
-        let start = self.index(row, 0);
+        // not using index(row, 0), as it fails when there is no column
+        assert!(row < self.rows, "row index out of bound");
+        let start = row * self.cols;
         &self.data[start..(start + self.cols)]
     }
     
     pub fn borrow_row_mut(&mut self, row: usize) -> &mut [T] {
-        let start = self.index(row, 0);
+        // not using index(row, 0), as it fails when there is no column
+        assert!(row < self.rows, "row index out of bound");
+        let start = row * self.cols;
         &mut self.data[start..(start + self.cols)]
     }
     
+    pub fn rows(&self) -> usize {
+        self.rows
+    }
+    
+    pub fn cols(&self) -> usize {
+        self.cols
+    }
+    
     pub fn column_iter(&self, col: usize) -> impl Iterator<Item=&T> {
         (0..self.rows).map(move |row| self.at(row, col))
     }
@@ -1,4 +1,9 @@
 pub mod dag;
 pub mod linked_list;
 pub mod binary_heap;
-pub mod matrix2d;
\ No newline at end of file
+pub mod indexed_min_heap;
+pub mod bounded_priority_queue;
+pub mod matrix2d;
+pub mod dp_cache;
+pub mod raw_temp_buffer;
+pub mod stable_priority_queue;
@@ -0,0 +1,64 @@
+use std::alloc::Layout;
+use std::ptr::NonNull;
+use std::{alloc, slice};
+
+// A temporary buffer of uninitialized memory, used for moving elements out temporarily.
+// It frees the memory when dropped, even when panic happens, so the memory will not leak.
+// It never drops the elements inside, because the elements in it are bitwise copies,
+// and the ownership is still considered to be at the original place.
+pub struct RawTempBuffer<T> {
+    ptr: NonNull<T>,
+    capacity: usize,
+}
+
+impl<T> RawTempBuffer<T> {
+    pub fn new(capacity: usize) -> RawTempBuffer<T> {
+        let layout = Layout::array::<T>(capacity).expect("Unable to allocate temp buffer");
+        
+        // zero-sized allocation is not allowed
+        if layout.size() == 0 {
+            return RawTempBuffer { ptr: NonNull::dangling(), capacity };
+        }
+        
+        let ptr = unsafe { alloc::alloc(layout) as *mut T };
+        match NonNull::new(ptr) {
+            Some(ptr) => RawTempBuffer { ptr, capacity },
+            None => alloc::handle_alloc_error(layout),
+        }
+    }
+    
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+    
+    pub fn as_ptr(&self) -> *const T {
+        self.ptr.as_ptr()
+    }
+    
+    pub fn as_mut_ptr(&mut self) -> *mut T {
+        self.ptr.as_ptr()
+    }
+    
+    // Safety: the first `len` elements must be initialized.
+    pub unsafe fn as_slice(&self, len: usize) -> &[T] {
+        assert!(len <= self.capacity);
+        slice::from_raw_parts(self.ptr.as_ptr(), len)
+    }
+}
+
+impl<T> Drop for RawTempBuffer<T> {
+    fn drop(&mut self) {
+        let layout = Layout::array::<T>(self.capacity).unwrap();
+        if layout.size() != 0 {
+            // it only frees the memory, it will not call drop on the elements.
+            unsafe {
+                alloc::dealloc(self.ptr.as_ptr() as *mut u8, layout);
+            }
+        }
+    }
+}
+
+// it's just a piece of memory, can be sent to other thread if the element can be sent.
+unsafe impl<T: Send> Send for RawTempBuffer<T> {}
+
+unsafe impl<T: Sync> Sync for RawTempBuffer<T> {}
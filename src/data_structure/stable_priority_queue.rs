@@ -0,0 +1,127 @@
+use std::cmp::Ordering;
+
+use crate::data_structure::binary_heap::MyMinHeap;
+
+// A priority queue that pops the smallest priority first,
+// and pops the elements with equal priority in insertion order (FIFO).
+// MyMinHeap is not stable, so each element is augmented with an insertion sequence number,
+// and the ties are broken by the sequence number.
+pub struct StablePriorityQueue<'a, Priority, T, Comparator>
+    where
+        Comparator: Fn(&Priority, &Priority) -> Ordering,
+{
+    heap: MyMinHeap<'a, Entry<'a, Priority, T, Comparator>, EntryComparator<'a, Priority, T, Comparator>>,
+    next_seq: u64,
+    compare: &'a Comparator,
+}
+
+struct Entry<'a, Priority, T, Comparator> {
+    priority: Priority,
+    seq: u64,
+    value: T,
+    compare: &'a Comparator,
+}
+
+type EntryComparator<'a, Priority, T, Comparator> =
+    fn(&Entry<'a, Priority, T, Comparator>, &Entry<'a, Priority, T, Comparator>) -> Ordering;
+
+// the same approach as compare_sorted_iter_entries in binary_heap.rs,
+// here it lets the queue own the heap without borrowing from itself.
+fn compare_entries<Priority, T, Comparator>(
+    a: &Entry<Priority, T, Comparator>, b: &Entry<Priority, T, Comparator>,
+) -> Ordering
+    where
+        Comparator: Fn(&Priority, &Priority) -> Ordering
+{
+    (a.compare)(&a.priority, &b.priority).then(a.seq.cmp(&b.seq))
+}
+
+impl<'a, Priority, T, Comparator> StablePriorityQueue<'a, Priority, T, Comparator>
+    where
+        Comparator: Fn(&Priority, &Priority) -> Ordering,
+{
+    pub fn new(compare: &'a Comparator) -> Self {
+        Self {
+            heap: MyMinHeap::new(
+                &(compare_entries::<Priority, T, Comparator> as EntryComparator<'a, Priority, T, Comparator>)
+            ),
+            next_seq: 0,
+            compare,
+        }
+    }
+    
+    pub fn push(&mut self, priority: Priority, value: T) {
+        let seq = self.next_seq;
+        self.next_seq += 1;
+        self.heap.insert(Entry { priority, seq, value, compare: self.compare });
+    }
+    
+    // pop the element with the smallest priority.
+    // among the elements with the smallest priority, the earliest inserted one is popped.
+    pub fn pop(&mut self) -> Option<(Priority, T)> {
+        self.heap.take_min().map(|entry| (entry.priority, entry.value))
+    }
+    
+    pub fn peek(&self) -> Option<(&Priority, &T)> {
+        self.heap.peek_min().map(|entry| (&entry.priority, &entry.value))
+    }
+    
+    pub fn is_empty(&self) -> bool {
+        self.heap.is_empty()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::{Rng, SeedableRng};
+    use rand::prelude::StdRng;
+    
+    use super::*;
+    
+    #[test]
+    fn test_stable_priority_queue() {
+        let compare = |a: &i32, b: &i32| a.cmp(b);
+        let mut queue = StablePriorityQueue::new(&compare);
+        
+        queue.push(2, "a");
+        queue.push(1, "b");
+        queue.push(2, "c");
+        queue.push(1, "d");
+        queue.push(2, "e");
+        queue.push(1, "f");
+        
+        assert_eq!(queue.peek(), Some((&1, &"b")));
+        assert_eq!(queue.pop(), Some((1, "b")));
+        assert_eq!(queue.pop(), Some((1, "d")));
+        assert_eq!(queue.pop(), Some((1, "f")));
+        assert_eq!(queue.pop(), Some((2, "a")));
+        assert_eq!(queue.pop(), Some((2, "c")));
+        assert_eq!(queue.pop(), Some((2, "e")));
+        assert_eq!(queue.pop(), None);
+        assert!(queue.is_empty());
+    }
+    
+    #[test]
+    fn test_stable_priority_queue_random() {
+        let mut rng: StdRng = SeedableRng::seed_from_u64(123456);
+        
+        let compare = |a: &i32, b: &i32| a.cmp(b);
+        let mut queue = StablePriorityQueue::new(&compare);
+        
+        let mut pushed: Vec<(i32, usize)> = Vec::new();
+        for tag in 0..1000 {
+            let priority = rng.gen_range(0..10);
+            queue.push(priority, tag);
+            pushed.push((priority, tag));
+        }
+        
+        let mut popped: Vec<(i32, usize)> = Vec::new();
+        while let Some(e) = queue.pop() {
+            popped.push(e);
+        }
+        
+        // std's sort is stable
+        pushed.sort_by(|a, b| a.0.cmp(&b.0));
+        assert_eq!(popped, pushed);
+    }
+}
@@ -0,0 +1,180 @@
+use std::cmp::Ordering;
+use std::collections::HashMap;
+use std::hash::Hash;
+use std::marker::PhantomData;
+use std::ops::{Add, Sub};
+
+use crate::data_structure::dag::{DAGTraverser, HashMapDAG, topological_order};
+use crate::dyn_pro::dag_shortest_path::{DagShortestPathSession, DagShortestPathSolver, DistanceOps, PathInfo};
+
+// The longest path is the shortest path with the order of distances reversed: the longer one is considered "shorter".
+// It's only well-defined when there is no cycle.
+#[derive(Default)]
+pub struct LongestPathDistanceOps<Time> {
+    _phantom: PhantomData<Time>,
+}
+
+impl<Time> DistanceOps<Time, Time> for LongestPathDistanceOps<Time>
+    where
+        Time: Copy + Ord + Default + Add<Output=Time>,
+{
+    fn get_distance(&self, edge: &Time) -> Time {
+        *edge
+    }
+    
+    fn add_distance(&self, a: &Time, b: &Time) -> Time {
+        *a + *b
+    }
+    
+    fn zero_distance(&self) -> Time {
+        Time::default()
+    }
+    
+    // longer is "shorter"
+    fn compare_distance(&self, a: &Time, b: &Time) -> Ordering {
+        b.cmp(a)
+    }
+}
+
+// The task graph with an extra End node, for querying the longest paths from each task to End.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+enum ScheduleNode<NodeRef> {
+    Task(NodeRef),
+    End,
+}
+
+type ScheduleCache<NodeRef, Time> =
+HashMap<(ScheduleNode<NodeRef>, ScheduleNode<NodeRef>), Option<PathInfo<ScheduleNode<NodeRef>, Time>>>;
+
+// Critical path scheduling.
+// Each node is a task with a duration. An edge from a to b means b can only start after a finishes.
+// For each task, it computes:
+// * earliest start: the longest path length from any start task to it (excluding its own duration).
+// * latest start: the latest time it can start without delaying the whole project.
+// * slack: latest start - earliest start. the tasks on the critical path have zero slack.
+// Both are longest paths, solved by the DAG shortest path solver with LongestPathDistanceOps, on two derived graphs:
+// * forward pass: task a -> task b weighs duration(a), and every task a -> End weighs duration(a).
+//   the longest path from a task to End is the shortest time from its start to the project end,
+//   so latest start = project end - that.
+// * backward pass: the edges are reversed. task b -> task a weighs duration(a), and every task -> End weighs zero.
+//   the longest path from a task to End is its earliest start.
+// All queries go to End, so the sessions share the sub-paths, and each pass is O(V + E).
+// The project starts at Time::default() (zero for numbers).
+// All the tasks, including the ones without edges, should be in durations. Panics if there is a cycle.
+pub fn schedule<NodeRef, EdgeData, Time, Traverser>(
+    traverser: &Traverser, durations: &HashMap<NodeRef, Time>,
+) -> HashMap<NodeRef, (Time, Time, Time)>
+    where
+        NodeRef: Eq + Hash + Clone,
+        Time: Copy + Ord + Default + Add<Output=Time> + Sub<Output=Time>,
+        Traverser: DAGTraverser<NodeRef, EdgeData>
+{
+    // the solver would recurse forever on a cycle, so check it first
+    let order: Vec<NodeRef> = topological_order(traverser, durations.keys().cloned());
+    
+    let duration_of = |node: &NodeRef| -> Time {
+        *durations.get(node).expect("the duration of a task is missing")
+    };
+    
+    let mut forward_graph: HashMapDAG<ScheduleNode<NodeRef>, Time> = HashMap::new();
+    let mut backward_graph: HashMapDAG<ScheduleNode<NodeRef>, Time> = HashMap::new();
+    forward_graph.insert(ScheduleNode::End, HashMap::new());
+    backward_graph.insert(ScheduleNode::End, HashMap::new());
+    for node in order.iter() {
+        let duration = duration_of(node);
+        let task = ScheduleNode::Task(node.clone());
+        forward_graph.entry(task.clone()).or_default().insert(ScheduleNode::End, duration);
+        backward_graph.entry(task.clone()).or_default().insert(ScheduleNode::End, Time::default());
+        
+        for (_edge_data, next_node) in traverser.get_edges_coming_out(node.clone()) {
+            let next_task = ScheduleNode::Task(next_node);
+            forward_graph.entry(task.clone()).or_default().insert(next_task.clone(), duration);
+            backward_graph.entry(next_task).or_default().insert(task.clone(), duration);
+        }
+    }
+    
+    let forward_solver = DagShortestPathSolver::new(forward_graph, LongestPathDistanceOps::default());
+    let forward_cache: ScheduleCache<NodeRef, Time> = HashMap::new();
+    let mut forward_session = DagShortestPathSession::new(&forward_solver, forward_cache);
+    
+    let backward_solver = DagShortestPathSolver::new(backward_graph, LongestPathDistanceOps::default());
+    let backward_cache: ScheduleCache<NodeRef, Time> = HashMap::new();
+    let mut backward_session = DagShortestPathSession::new(&backward_solver, backward_cache);
+    
+    // every task has an edge to End, so the paths always exist
+    let times_to_end: Vec<Time> = order.iter().map(|node| {
+        *forward_session.query(ScheduleNode::Task(node.clone()), ScheduleNode::End)
+            .unwrap().distance_to_destination()
+    }).collect();
+    let project_end: Time = times_to_end.iter().copied().max().unwrap_or_default();
+    
+    order.into_iter().zip(times_to_end).map(|(node, time_to_end)| {
+        let earliest_start = *backward_session.query(ScheduleNode::Task(node.clone()), ScheduleNode::End)
+            .unwrap().distance_to_destination();
+        let latest_start = project_end - time_to_end;
+        (node, (earliest_start, latest_start, latest_start - earliest_start))
+    }).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::data_structure::dag::hash_map_dag_from_edges;
+    use crate::data_structure::matrix2d::Matrix2D;
+    use crate::dyn_pro::dag_shortest_path::debug_validate_distance_ops;
+    
+    use super::*;
+    
+    #[test]
+    fn test_longest_path_distance_ops_is_valid() {
+        debug_validate_distance_ops(&LongestPathDistanceOps::default(), &[0, 1, 3, 3, 10]);
+    }
+    
+    #[test]
+    fn test_schedule() {
+        // a -> b -> d -> f
+        // a -> c -> d
+        // a -> e -> f
+        // g has no dependency
+        let mut graph: HashMapDAG<&str, ()> = hash_map_dag_from_edges(
+            [("a", "b"), ("a", "c"), ("b", "d"), ("c", "d"), ("d", "f"), ("a", "e"), ("e", "f")]
+                .map(|(src, dst)| (src, dst, ())),
+        );
+        graph.insert("g", HashMap::new());
+        
+        let durations: HashMap<&str, i32> = HashMap::from([
+            ("a", 3), ("b", 2), ("c", 1), ("d", 4), ("e", 5), ("f", 1), ("g", 2),
+        ]);
+        
+        let result = schedule(&graph, &durations);
+        
+        // the critical path is a -> b -> d -> f, the project takes 10
+        assert_eq!(result["a"], (0, 0, 0));
+        assert_eq!(result["b"], (3, 3, 0));
+        assert_eq!(result["d"], (5, 5, 0));
+        assert_eq!(result["f"], (9, 9, 0));
+        
+        assert_eq!(result["c"], (3, 4, 1));
+        assert_eq!(result["e"], (3, 4, 1));
+        assert_eq!(result["g"], (0, 8, 8));
+    }
+    
+    #[test]
+    fn test_schedule_matrix_dag() {
+        // 0 -> 1 -> 3
+        // 0 -> 2 -> 3
+        let mut matrix: Matrix2D<Option<()>> = Matrix2D::new_defaulted(4, 4);
+        matrix.set(0, 1, Some(()));
+        matrix.set(0, 2, Some(()));
+        matrix.set(1, 3, Some(()));
+        matrix.set(2, 3, Some(()));
+        
+        let durations: HashMap<usize, i32> = HashMap::from([(0, 1), (1, 5), (2, 2), (3, 1)]);
+        
+        let result = schedule(&matrix, &durations);
+        
+        assert_eq!(result[&0], (0, 0, 0));
+        assert_eq!(result[&1], (1, 1, 0));
+        assert_eq!(result[&2], (1, 4, 3));
+        assert_eq!(result[&3], (6, 6, 0));
+    }
+}
@@ -2,10 +2,13 @@
 
 use std::cmp::Ordering;
 use std::collections::HashMap;
+use std::fmt::Debug;
 use std::marker::PhantomData;
+use std::sync::atomic::AtomicBool;
 
-use crate::data_structure::dag::DAGTraverser;
-use crate::functional::lazy_eval::FuncHavingFixedPointMut;
+use crate::data_structure::dag::{best_outgoing, DAGTraverser};
+use crate::functional::lazy_eval::{BudgetedFixedPointApplyFunc, BudgetExceeded, Cache, CancellableFixedPointApplyFunc, FuncHavingFixedPointMut, LazyEvalFixedPointApplyFunc};
+use crate::util::cancellation::Cancelled;
 
 pub trait DistanceOps<EdgeData, Distance> {
     fn get_distance(&self, edge: &EdgeData) -> Distance;
@@ -17,6 +20,55 @@ pub trait DistanceOps<EdgeData, Distance> {
     fn compare_distance(&self,a: &Distance, b: &Distance) -> Ordering;
 }
 
+// The solver assumes the distances form an ordered monoid. A wrong DistanceOps silently gives wrong paths.
+// In debug build, it checks the laws on all combinations of the samples, and panics on the first violation:
+// - identity: zero + x == x + zero == x
+// - associativity: (a + b) + c == a + (b + c)
+// - monotonicity: a <= b implies a + c <= b + c and c + a <= c + b.
+//   it's what makes choosing the shortest remaining path correct.
+// Equality is by compare_distance. It takes O(n^3) for n samples, so use a few samples in tests.
+pub fn debug_validate_distance_ops<EdgeData, Distance: Debug>(
+    ops: &impl DistanceOps<EdgeData, Distance>, samples: &[Distance],
+) {
+    if !cfg!(debug_assertions) {
+        return;
+    }
+    
+    let zero = ops.zero_distance();
+    let equals = |a: &Distance, b: &Distance| ops.compare_distance(a, b).is_eq();
+    
+    for x in samples {
+        assert!(
+            equals(&ops.add_distance(&zero, x), x) && equals(&ops.add_distance(x, &zero), x),
+            "distance ops identity violated: zero + {:?} != {:?}", x, x
+        );
+    }
+    
+    for a in samples {
+        for b in samples {
+            for c in samples {
+                assert!(
+                    equals(
+                        &ops.add_distance(&ops.add_distance(a, b), c),
+                        &ops.add_distance(a, &ops.add_distance(b, c)),
+                    ),
+                    "distance ops associativity violated: ({:?} + {:?}) + {:?} != {:?} + ({:?} + {:?})",
+                    a, b, c, a, b, c
+                );
+                
+                if ops.compare_distance(a, b).is_le() {
+                    assert!(
+                        ops.compare_distance(&ops.add_distance(a, c), &ops.add_distance(b, c)).is_le()
+                            && ops.compare_distance(&ops.add_distance(c, a), &ops.add_distance(c, b)).is_le(),
+                        "distance ops monotonicity violated: {:?} <= {:?}, but adding {:?} reverses it",
+                        a, b, c
+                    );
+                }
+            }
+        }
+    }
+}
+
 pub struct DagShortestPathSolver<
     NodeRef, EdgeData, Distance,
     Traverser: DAGTraverser<NodeRef, EdgeData>,
@@ -50,6 +102,49 @@ pub struct PathInfo<NodeRef: Clone, Distance: Clone> {
     distance_to_destination: Distance,
 }
 
+impl<NodeRef: Clone, Distance: Clone> PathInfo<NodeRef, Distance> {
+    pub fn next_node(&self) -> &NodeRef {
+        &self.next_node
+    }
+    
+    pub fn distance_to_destination(&self) -> &Distance {
+        &self.distance_to_destination
+    }
+}
+
+impl<
+    NodeRef: Clone+Eq, EdgeData, Distance:Clone,
+    Traverser: DAGTraverser<NodeRef, EdgeData>,
+    DistanceOpsImpl: DistanceOps<EdgeData, Distance>
+> DagShortestPathSolver<NodeRef, EdgeData, Distance, Traverser, DistanceOpsImpl> {
+    // on huge DAGs the query may explore too many (src, dst) sub-problems.
+    // it gives up after max_evaluations sub-problems are evaluated, to keep interactive callers responsive.
+    pub fn shortest_path_with_budget<CacheImpl>(
+        &self,
+        two_ends: &(NodeRef, NodeRef),
+        cache: CacheImpl,
+        max_evaluations: usize,
+    ) -> Result<Option<PathInfo<NodeRef, Distance>>, BudgetExceeded>
+        where CacheImpl: Cache<(NodeRef, NodeRef), Option<PathInfo<NodeRef, Distance>>>
+    {
+        let mut budgeted_solver = BudgetedFixedPointApplyFunc::new(self, cache, max_evaluations);
+        budgeted_solver.eval(two_ends)
+    }
+    
+    // gives up when the cancel flag is set, possibly from another thread by an interactive caller.
+    pub fn shortest_path_cancellable<CacheImpl>(
+        &self,
+        two_ends: &(NodeRef, NodeRef),
+        cache: CacheImpl,
+        cancel: &AtomicBool,
+    ) -> Result<Option<PathInfo<NodeRef, Distance>>, Cancelled>
+        where CacheImpl: Cache<(NodeRef, NodeRef), Option<PathInfo<NodeRef, Distance>>>
+    {
+        let mut cancellable_solver = CancellableFixedPointApplyFunc::new(self, cache, cancel);
+        cancellable_solver.eval(two_ends)
+    }
+}
+
 impl<
     NodeRef: Clone+Eq, EdgeData, Distance:Clone,
     Traverser: DAGTraverser<NodeRef, EdgeData>,
@@ -70,24 +165,18 @@ for DagShortestPathSolver<NodeRef, EdgeData, Distance, Traverser, DistanceOpsImp
                 distance_to_destination: self.distance_ops.zero_distance(),
             });
         }
-        self.traverser.get_edges_coming_out(src.clone())
-            .filter_map(|(edge_data, next_node)| -> Option<PathInfo<NodeRef, Distance>> {
-                let next_node_to_dest_info: Option<PathInfo<NodeRef, Distance>> =
-                    recursion(&(next_node.clone(), dst.clone()));
-                match next_node_to_dest_info {
-                    Some(next_node_path_info) => {
-                        let next_node_distance_to_dest = next_node_path_info.distance_to_destination.clone();
-                        let edge_distance = self.distance_ops.get_distance(&edge_data);
-                        let new_distance =
-                            self.distance_ops.add_distance(&edge_distance, &next_node_distance_to_dest);
-                        Some(PathInfo { next_node, distance_to_destination: new_distance })
-                    }
-                    None => None,
-                }
-            })
-            .min_by(|a, b| {
-                self.distance_ops.compare_distance(&a.distance_to_destination, &b.distance_to_destination)
-            })
+        let best = best_outgoing(
+            &self.traverser, src.clone(),
+            |edge_data: &EdgeData, next_node: &NodeRef| -> Option<Distance> {
+                let next_node_path_info = recursion(&(next_node.clone(), dst.clone()))?;
+                let edge_distance = self.distance_ops.get_distance(edge_data);
+                Some(self.distance_ops.add_distance(&edge_distance, &next_node_path_info.distance_to_destination))
+            },
+            &|a: &Distance, b: &Distance| self.distance_ops.compare_distance(a, b),
+        );
+        best.map(|(_edge_data, next_node, distance_to_destination)| {
+            PathInfo { next_node, distance_to_destination }
+        })
     }
 }
 
@@ -133,11 +222,103 @@ impl DistanceOps<f64, f64> for F64DistanceOps {
     }
 }
 
+// Answers many queries on the same graph.
+// The cache persists between queries, so the memoized sub-paths are shared.
+#[allow(clippy::type_complexity)]
+pub struct DagShortestPathSession<
+    'a, NodeRef: Clone + Eq, EdgeData, Distance: Clone,
+    Traverser: DAGTraverser<NodeRef, EdgeData>,
+    DistanceOpsImpl: DistanceOps<EdgeData, Distance>,
+    CacheImpl: Cache<(NodeRef, NodeRef), Option<PathInfo<NodeRef, Distance>>>
+> {
+    cached_solver: LazyEvalFixedPointApplyFunc<
+        'a, (NodeRef, NodeRef), Option<PathInfo<NodeRef, Distance>>, CacheImpl,
+        DagShortestPathSolver<NodeRef, EdgeData, Distance, Traverser, DistanceOpsImpl>
+    >,
+}
+
+impl<
+    'a, NodeRef: Clone + Eq, EdgeData, Distance: Clone,
+    Traverser: DAGTraverser<NodeRef, EdgeData>,
+    DistanceOpsImpl: DistanceOps<EdgeData, Distance>,
+    CacheImpl: Cache<(NodeRef, NodeRef), Option<PathInfo<NodeRef, Distance>>>
+> DagShortestPathSession<'a, NodeRef, EdgeData, Distance, Traverser, DistanceOpsImpl, CacheImpl> {
+    pub fn new(
+        solver: &'a DagShortestPathSolver<NodeRef, EdgeData, Distance, Traverser, DistanceOpsImpl>,
+        cache: CacheImpl,
+    ) -> Self {
+        Self { cached_solver: LazyEvalFixedPointApplyFunc::new(solver, cache) }
+    }
+    
+    pub fn query(&mut self, src: NodeRef, dst: NodeRef) -> Option<PathInfo<NodeRef, Distance>> {
+        self.cached_solver.eval(&(src, dst))
+    }
+}
+
+// the "distance" is the concatenation of edge labels along the path.
+// shorter string is considered shorter distance. if lengths equal, compare lexicographically.
+// it shows that the solver does not assume numeric distance.
+struct ConcatDistanceOps {}
+
+impl DistanceOps<String, String> for ConcatDistanceOps {
+    fn get_distance(&self, edge: &String) -> String {
+        edge.clone()
+    }
+    
+    fn add_distance(&self, a: &String, b: &String) -> String {
+        format!("{}{}", a, b)
+    }
+    
+    fn zero_distance(&self) -> String {
+        String::new()
+    }
+    
+    fn compare_distance(&self, a: &String, b: &String) -> Ordering {
+        a.len().cmp(&b.len()).then_with(|| a.cmp(b))
+    }
+}
+
+// Among the paths of equal distance, prefer the one with fewer edges (hops).
+// The distance becomes (distance, hop count), and the hop count is compared only if the distances are equal.
+// The hop count of the chosen path is in PathInfo's distance_to_destination.
+pub struct HopTieBreakDistanceOps<EdgeData, Distance, DistanceOpsImpl: DistanceOps<EdgeData, Distance>> {
+    distance_ops: DistanceOpsImpl,
+    _phantom: PhantomData<(EdgeData, Distance)>,
+}
+
+impl<EdgeData, Distance, DistanceOpsImpl: DistanceOps<EdgeData, Distance>>
+HopTieBreakDistanceOps<EdgeData, Distance, DistanceOpsImpl> {
+    pub fn new(distance_ops: DistanceOpsImpl) -> Self {
+        Self { distance_ops, _phantom: PhantomData }
+    }
+}
+
+impl<EdgeData, Distance, DistanceOpsImpl: DistanceOps<EdgeData, Distance>> DistanceOps<EdgeData, (Distance, usize)>
+for HopTieBreakDistanceOps<EdgeData, Distance, DistanceOpsImpl>
+{
+    fn get_distance(&self, edge: &EdgeData) -> (Distance, usize) {
+        (self.distance_ops.get_distance(edge), 1)
+    }
+    
+    fn add_distance(&self, a: &(Distance, usize), b: &(Distance, usize)) -> (Distance, usize) {
+        (self.distance_ops.add_distance(&a.0, &b.0), a.1 + b.1)
+    }
+    
+    fn zero_distance(&self) -> (Distance, usize) {
+        (self.distance_ops.zero_distance(), 0)
+    }
+    
+    fn compare_distance(&self, a: &(Distance, usize), b: &(Distance, usize)) -> Ordering {
+        self.distance_ops.compare_distance(&a.0, &b.0).then(a.1.cmp(&b.1))
+    }
+}
+
 #[cfg(test)]
 mod tests {
-    use crate::data_structure::dag::HashMapDAG;
+    use crate::data_structure::dag::{hash_map_dag_from_edges, HashMapDAG, matrix_dag_from_edges};
+    use crate::data_structure::dp_cache::DpCache2D;
     use crate::data_structure::matrix2d::Matrix2D;
-    use crate::functional::lazy_eval::LazyEvalFixedPointApplyFunc;
+    use std::cell::Cell;
     
     use super::*;
     
@@ -149,6 +330,47 @@ mod tests {
         graph
     }
     
+    // it compares modulo 10, like a distance that wraps around.
+    // it has identity and is associative, but it's not monotonic.
+    struct WrappingDistanceOps {}
+    
+    impl DistanceOps<i32, i32> for WrappingDistanceOps {
+        fn get_distance(&self, edge: &i32) -> i32 {
+            *edge
+        }
+        
+        fn add_distance(&self, a: &i32, b: &i32) -> i32 {
+            a + b
+        }
+        
+        fn zero_distance(&self) -> i32 {
+            0
+        }
+        
+        fn compare_distance(&self, a: &i32, b: &i32) -> Ordering {
+            a.rem_euclid(10).cmp(&b.rem_euclid(10))
+        }
+    }
+    
+    #[test]
+    fn test_debug_validate_distance_ops() {
+        debug_validate_distance_ops(&I32DistanceOps {}, &[-3, 0, 1, 2, 5, 100]);
+        debug_validate_distance_ops(&F64DistanceOps {}, &[0.0, 0.5, 1.0, 2.0, 4.0]);
+        let strings: Vec<String> = ["", "a", "b", "ab", "ba", "abc"].iter().map(|s| s.to_string()).collect();
+        debug_validate_distance_ops(&ConcatDistanceOps {}, &strings);
+        debug_validate_distance_ops(
+            &HopTieBreakDistanceOps::new(I32DistanceOps {}),
+            &[(0, 0), (1, 1), (1, 2), (2, 1), (5, 3)],
+        );
+    }
+    
+    #[test]
+    #[cfg(debug_assertions)]
+    #[should_panic(expected = "distance ops monotonicity violated")]
+    fn test_debug_validate_distance_ops_broken() {
+        debug_validate_distance_ops(&WrappingDistanceOps {}, &[0, 1, 5, 9]);
+    }
+    
     #[test]
     fn test_dag_shortest_path_1() {
         let graph = init_graph(vec![
@@ -180,4 +402,190 @@ mod tests {
         let result = cached_solver(&(0, 3));
         assert_eq!(result, Some(PathInfo { next_node: 1, distance_to_destination: 5.0 }));
     }
+    
+    #[test]
+    fn test_dag_shortest_path_hash_map_and_matrix_agree() {
+        // the same graph as test_dag_shortest_path_2, plus a node that has no edge
+        let node_num = 5;
+        let edges: Vec<(usize, usize, i32)> = vec![
+            (0, 1, 1),
+            (0, 2, 2),
+            (1, 2, 3),
+            (1, 3, 4),
+            (2, 3, 5),
+        ];
+        let mut hash_map_dag = hash_map_dag_from_edges(edges.clone());
+        // the node that has no edge is not in the edges, so it needs its own entry
+        hash_map_dag.entry(4).or_default();
+        let hash_map_solver = DagShortestPathSolver::new(hash_map_dag, I32DistanceOps {});
+        let matrix_solver = DagShortestPathSolver::new(matrix_dag_from_edges(node_num, edges), I32DistanceOps {});
+        
+        let hash_map_cache: HashMap<(usize, usize), Option<PathInfo<usize, i32>>> = HashMap::new();
+        let mut hash_map_session = DagShortestPathSession::new(&hash_map_solver, hash_map_cache);
+        let matrix_cache: Matrix2D<Option<Option<PathInfo<usize, i32>>>> = Matrix2D::new_defaulted(node_num, node_num);
+        let mut matrix_session = DagShortestPathSession::new(&matrix_solver, matrix_cache);
+        
+        for src in 0..node_num {
+            for dst in 0..node_num {
+                assert_eq!(
+                    hash_map_session.query(src, dst),
+                    matrix_session.query(src, dst),
+                    "differs from {} to {}", src, dst
+                );
+            }
+        }
+    }
+    
+    #[test]
+    fn test_dag_shortest_path_dp_cache_2d() {
+        // a chain with shortcuts. the last node is not reachable from the others
+        let node_num = 20;
+        let mut edges: Vec<(usize, usize, f64)> = Vec::new();
+        for i in 0..(node_num - 2) {
+            edges.push((i, i + 1, 1.0));
+            if i + 3 < node_num - 1 {
+                edges.push((i, i + 3, 2.5));
+            }
+        }
+        let solver = DagShortestPathSolver::new(matrix_dag_from_edges(node_num, edges), F64DistanceOps {});
+        
+        let nested_option_cache: Matrix2D<Option<Option<PathInfo<usize, f64>>>> = Matrix2D::new_defaulted(node_num, node_num);
+        let mut nested_option_session = DagShortestPathSession::new(&solver, nested_option_cache);
+        let mut dp_cache_session = DagShortestPathSession::new(&solver, DpCache2D::new(node_num, node_num));
+        
+        for src in 0..node_num {
+            for dst in 0..node_num {
+                assert_eq!(dp_cache_session.query(src, dst), nested_option_session.query(src, dst));
+            }
+        }
+        assert_eq!(dp_cache_session.query(0, node_num - 1), None);
+    }
+    
+    #[test]
+    fn test_dag_shortest_path_with_budget() {
+        // a long chain. every node links to the next two nodes.
+        let node_num = 300;
+        let mut matrix: Matrix2D<Option<f64>> = Matrix2D::new_defaulted(node_num, node_num);
+        for i in 0..(node_num - 1) {
+            matrix.set(i, i + 1, Some(1.0));
+            if i + 2 < node_num {
+                matrix.set(i, i + 2, Some(3.0));
+            }
+        }
+        let solver = DagShortestPathSolver::new(matrix, F64DistanceOps {});
+        
+        let cache: Matrix2D<Option<Option<PathInfo<usize, f64>>>> = Matrix2D::new_defaulted(node_num, node_num);
+        let result = solver.shortest_path_with_budget(&(0, node_num - 1), cache, 10);
+        assert_eq!(result, Err(BudgetExceeded));
+        
+        let cache: Matrix2D<Option<Option<PathInfo<usize, f64>>>> = Matrix2D::new_defaulted(node_num, node_num);
+        let result = solver.shortest_path_with_budget(&(0, node_num - 1), cache, node_num * node_num);
+        assert_eq!(result, Ok(Some(PathInfo { next_node: 1, distance_to_destination: (node_num - 1) as f64 })));
+    }
+    
+    #[test]
+    fn test_dag_shortest_path_cancellable() {
+        let node_num = 300;
+        let mut matrix: Matrix2D<Option<f64>> = Matrix2D::new_defaulted(node_num, node_num);
+        for i in 0..(node_num - 1) {
+            matrix.set(i, i + 1, Some(1.0));
+        }
+        let solver = DagShortestPathSolver::new(matrix, F64DistanceOps {});
+        
+        let cancel = AtomicBool::new(false);
+        let cache: Matrix2D<Option<Option<PathInfo<usize, f64>>>> = Matrix2D::new_defaulted(node_num, node_num);
+        let result = solver.shortest_path_cancellable(&(0, node_num - 1), cache, &cancel);
+        assert_eq!(result, Ok(Some(PathInfo { next_node: 1, distance_to_destination: (node_num - 1) as f64 })));
+        
+        cancel.store(true, std::sync::atomic::Ordering::Relaxed);
+        let cache: Matrix2D<Option<Option<PathInfo<usize, f64>>>> = Matrix2D::new_defaulted(node_num, node_num);
+        let result = solver.shortest_path_cancellable(&(0, node_num - 1), cache, &cancel);
+        assert_eq!(result, Err(Cancelled));
+    }
+    
+    #[test]
+    fn test_dag_shortest_path_concat() {
+        let edges = vec![
+            ("a", "b", "x"),
+            ("a", "c", "yy"),
+            ("b", "d", "zzz"),
+            ("c", "d", "w"),
+            ("b", "c", "b"),
+        ];
+        let graph: HashMapDAG<&str, String> =
+            hash_map_dag_from_edges(edges.into_iter().map(|(src, dst, label)| (src, dst, label.to_string())));
+        let solver = DagShortestPathSolver::new(graph, ConcatDistanceOps {});
+        let cache: HashMap<(&str, &str), Option<PathInfo<&str, String>>> = HashMap::new();
+        let mut cached_solver = LazyEvalFixedPointApplyFunc::new(&solver, cache);
+        
+        // a->b->d gives "xzzz", a->c->d gives "yyw", a->b->c->d gives "xbw".
+        // "xbw" and "yyw" have the same length, "xbw" is lexicographically smaller
+        let result = cached_solver(&("a", "d"));
+        assert_eq!(result, Some(PathInfo { next_node: "b", distance_to_destination: "xbw".to_string() }));
+        
+        assert_eq!(cached_solver(&("d", "a")), None);
+    }
+    
+    #[test]
+    fn test_dag_shortest_path_hop_tie_break() {
+        // a -> b -> c -> d and a -> e -> d both have distance 6.
+        // a -> d has fewer hops, but longer distance
+        let graph = init_graph(vec![
+            ("a", "b", 2),
+            ("b", "c", 2),
+            ("c", "d", 2),
+            ("a", "e", 3),
+            ("e", "d", 3),
+            ("a", "d", 7),
+        ]);
+        let solver = DagShortestPathSolver::new(graph, HopTieBreakDistanceOps::new(I32DistanceOps {}));
+        let mut cached_solver = LazyEvalFixedPointApplyFunc::new(&solver, HashMap::new());
+        
+        assert_eq!(cached_solver(&("a", "d")), Some(PathInfo { next_node: "e", distance_to_destination: (6, 2) }));
+        assert_eq!(cached_solver(&("b", "d")), Some(PathInfo { next_node: "c", distance_to_destination: (4, 2) }));
+    }
+    
+    // counts how many times the edges are traversed
+    struct CountingTraverser {
+        graph: HashMapDAG<&'static str, i32>,
+        invoke_count: Cell<usize>,
+    }
+    
+    impl DAGTraverser<&'static str, i32> for CountingTraverser {
+        type EdgeIter<'a> = <HashMapDAG<&'static str, i32> as DAGTraverser<&'static str, i32>>::EdgeIter<'a>;
+        
+        fn get_edges_coming_out<'a>(&'a self, n: &'static str) -> Self::EdgeIter<'a> {
+            self.invoke_count.set(self.invoke_count.get() + 1);
+            self.graph.get_edges_coming_out(n)
+        }
+    }
+    
+    #[test]
+    fn test_dag_shortest_path_session() {
+        let graph = init_graph(vec![
+            ("a", "b", 1),
+            ("a", "c", 2),
+            ("b", "c", 3),
+            ("b", "d", 4),
+            ("c", "d", 5),
+            ("d", "e", 1),
+        ]);
+        let traverser = CountingTraverser { graph, invoke_count: Cell::new(0) };
+        let solver = DagShortestPathSolver::new(traverser, I32DistanceOps {});
+        let cache: HashMap<(&str, &str), Option<PathInfo<&str, i32>>> = HashMap::new();
+        let mut session = DagShortestPathSession::new(&solver, cache);
+        
+        assert_eq!(session.query("b", "e"), Some(PathInfo { next_node: "d", distance_to_destination: 5 }));
+        // it evaluates (b,e), (c,e), (d,e). (e,e) doesn't traverse
+        assert_eq!(solver.traverser.invoke_count.get(), 3);
+        
+        // (b,e) and (c,e) are already memoized, only (a,e) is new
+        assert_eq!(session.query("a", "e"), Some(PathInfo { next_node: "b", distance_to_destination: 6 }));
+        assert_eq!(solver.traverser.invoke_count.get(), 4);
+        
+        // fully memoized
+        assert_eq!(session.query("c", "e"), Some(PathInfo { next_node: "d", distance_to_destination: 6 }));
+        assert_eq!(session.query("a", "e"), Some(PathInfo { next_node: "b", distance_to_destination: 6 }));
+        assert_eq!(solver.traverser.invoke_count.get(), 4);
+    }
 }
@@ -0,0 +1,121 @@
+use std::cmp::Ordering;
+use std::marker::PhantomData;
+
+use crate::dyn_pro::dag_shortest_path::{DagShortestPathSolver, DistanceOps};
+
+pub trait WidthOps<EdgeData, Width> {
+    fn get_width(&self, edge: &EdgeData) -> Width;
+    
+    // the width of an empty path. it should be wider than any edge.
+    fn infinite_width(&self) -> Width;
+    
+    fn compare_width(&self, a: &Width, b: &Width) -> Ordering;
+}
+
+// The widest path (maximum bottleneck path) is the path that maximizes the minimum edge width along it.
+// It's the same dynamic programming as the shortest path, in a different semiring:
+// - combining an edge with the remaining path uses min, instead of add.
+// - selecting between the paths uses max, instead of min.
+// So it reuses the shortest path solver, by treating the width as the "distance":
+// adding distance is taking the narrower one, and the wider one is considered "shorter".
+pub struct WidestPathDistanceOps<EdgeData, Width, WidthOpsImpl: WidthOps<EdgeData, Width>> {
+    width_ops: WidthOpsImpl,
+    _phantom: PhantomData<(EdgeData, Width)>,
+}
+
+impl<EdgeData, Width, WidthOpsImpl: WidthOps<EdgeData, Width>> WidestPathDistanceOps<EdgeData, Width, WidthOpsImpl> {
+    pub fn new(width_ops: WidthOpsImpl) -> Self {
+        Self { width_ops, _phantom: PhantomData }
+    }
+}
+
+impl<EdgeData, Width: Clone, WidthOpsImpl: WidthOps<EdgeData, Width>> DistanceOps<EdgeData, Width>
+for WidestPathDistanceOps<EdgeData, Width, WidthOpsImpl>
+{
+    fn get_distance(&self, edge: &EdgeData) -> Width {
+        self.width_ops.get_width(edge)
+    }
+    
+    // the bottleneck
+    fn add_distance(&self, a: &Width, b: &Width) -> Width {
+        if self.width_ops.compare_width(a, b).is_le() {
+            a.clone()
+        } else {
+            b.clone()
+        }
+    }
+    
+    fn zero_distance(&self) -> Width {
+        self.width_ops.infinite_width()
+    }
+    
+    // wider is "shorter"
+    fn compare_distance(&self, a: &Width, b: &Width) -> Ordering {
+        self.width_ops.compare_width(a, b).reverse()
+    }
+}
+
+// The PathInfo given by this solver contains the width of the path to the destination.
+pub type DagWidestPathSolver<NodeRef, EdgeData, Width, Traverser, WidthOpsImpl> =
+DagShortestPathSolver<NodeRef, EdgeData, Width, Traverser, WidestPathDistanceOps<EdgeData, Width, WidthOpsImpl>>;
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+    
+    use crate::data_structure::dag::{hash_map_dag_from_edges, HashMapDAG};
+    use crate::dyn_pro::dag_shortest_path::{DagShortestPathSession, debug_validate_distance_ops, PathInfo};
+    
+    use super::*;
+    
+    struct I32WidthOps {}
+    
+    impl WidthOps<i32, i32> for I32WidthOps {
+        fn get_width(&self, edge: &i32) -> i32 {
+            *edge
+        }
+        
+        fn infinite_width(&self) -> i32 {
+            i32::MAX
+        }
+        
+        fn compare_width(&self, a: &i32, b: &i32) -> Ordering {
+            a.cmp(b)
+        }
+    }
+    
+    #[test]
+    fn test_widest_path_distance_ops_is_valid() {
+        debug_validate_distance_ops(&WidestPathDistanceOps::new(I32WidthOps {}), &[i32::MAX, 0, 1, 3, 3, 10]);
+    }
+    
+    #[test]
+    fn test_dag_widest_path() {
+        let graph: HashMapDAG<&str, i32> = hash_map_dag_from_edges([
+            ("a", "b", 5),
+            ("a", "c", 3),
+            ("b", "d", 2),
+            ("c", "d", 4),
+            ("b", "c", 10),
+        ]);
+        
+        let solver: DagWidestPathSolver<&str, i32, i32, _, _> =
+            DagWidestPathSolver::new(graph, WidestPathDistanceOps::new(I32WidthOps {}));
+        let cache: HashMap<(&str, &str), Option<PathInfo<&str, i32>>> = HashMap::new();
+        let mut session = DagShortestPathSession::new(&solver, cache);
+        
+        // a->b->d has width 2, a->c->d has width 3, a->b->c->d has width 4
+        let result = session.query("a", "d").unwrap();
+        assert_eq!(*result.distance_to_destination(), 4);
+        
+        // follow the next nodes to get the whole path
+        let mut path = vec!["a"];
+        while *path.last().unwrap() != "d" {
+            let info = session.query(path.last().unwrap(), "d").unwrap();
+            path.push(*info.next_node());
+        }
+        assert_eq!(path, vec!["a", "b", "c", "d"]);
+        
+        assert_eq!(session.query("d", "a"), None);
+    }
+}
@@ -1,2 +1,4 @@
 pub mod dag_shortest_path;
+pub mod dag_critical_path;
 pub mod longest_increasing_subsequence;
+pub mod dag_widest_path;
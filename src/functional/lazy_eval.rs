@@ -2,6 +2,9 @@ use std::cell::Cell;
 use std::collections::{BTreeMap, HashMap};
 use std::hash::Hash;
 use std::marker::PhantomData;
+use std::sync::atomic::{AtomicBool, Ordering as AtomicOrdering};
+
+use crate::util::cancellation::Cancelled;
 
 // The Value type is Clone,
 // because if not, it needs to return reference of value in cache, which indirectly borrows the cache,
@@ -274,6 +277,150 @@ impl<'a, Input, Output: Clone, CacheImpl, FixedPointFuncImpl> FnMut<(&Input, )>
     }
 }
 
+// Decides when eval_until_stopped gives up.
+trait StopCondition {
+    // called before each evaluation of the function. cache hits are not counted.
+    fn on_evaluation(&mut self) {}
+    
+    fn is_stopped(&self) -> bool;
+}
+
+// The shared driver of BudgetedFixedPointApplyFunc and CancellableFixedPointApplyFunc.
+// the recursion argument has to return an Output, so after it's stopped,
+// the remaining recursive calls return Output::default() without evaluating, to unwind quickly.
+// these dummy results are not put into the cache, and the caller should discard the final result.
+fn eval_until_stopped<Input, Output, CacheImpl, FixedPointFuncImpl, Stop>(
+    fixed_point_func: &FixedPointFuncImpl, cache: &mut CacheImpl, stop: &mut Stop, input: &Input,
+) -> Output
+    where Output: Clone + Default,
+          FixedPointFuncImpl: FuncHavingFixedPointMut<Input, Output>,
+          CacheImpl: Cache<Input, Output>,
+          Stop: StopCondition
+{
+    if stop.is_stopped() {
+        return Output::default();
+    }
+    
+    if let Some(value) = cache.get_from_cache(input) {
+        return value;
+    }
+    
+    stop.on_evaluation();
+    if stop.is_stopped() {
+        return Output::default();
+    }
+    
+    let new_value: Output = fixed_point_func.eval(
+        &mut |input2: &Input| eval_until_stopped(fixed_point_func, cache, stop, input2), input,
+    );
+    
+    // the value may depend on dummy results
+    if !stop.is_stopped() {
+        cache.put_to_cache(input, new_value.clone());
+    }
+    new_value
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct BudgetExceeded;
+
+struct EvaluationBudget {
+    max_evaluations: usize,
+    evaluation_count: usize,
+}
+
+impl StopCondition for EvaluationBudget {
+    fn on_evaluation(&mut self) {
+        self.evaluation_count += 1;
+    }
+    
+    fn is_stopped(&self) -> bool {
+        self.evaluation_count > self.max_evaluations
+    }
+}
+
+// same as LazyEvalFixedPointApplyFunc, but gives up after evaluating the function max_evaluations times.
+// cache hits are not counted. after giving up, the result becomes Err(BudgetExceeded).
+pub struct BudgetedFixedPointApplyFunc<'a, Input, Output, CacheImpl, FixedPointFuncImpl>
+    where FixedPointFuncImpl: FuncHavingFixedPointMut<Input, Output>,
+          CacheImpl: Cache<Input, Output>
+{
+    fixed_point_func: &'a FixedPointFuncImpl,
+    cache: CacheImpl,
+    budget: EvaluationBudget,
+    __phantom: PhantomData<(Input, Output)>,
+}
+
+impl<'a, Input, Output: Clone + Default, CacheImpl, FixedPointFuncImpl> BudgetedFixedPointApplyFunc<'a, Input, Output, CacheImpl, FixedPointFuncImpl>
+    where FixedPointFuncImpl: FuncHavingFixedPointMut<Input, Output>,
+          CacheImpl: Cache<Input, Output>
+{
+    pub fn new(fixed_point_func: &'a FixedPointFuncImpl, cache: CacheImpl, max_evaluations: usize) -> Self {
+        BudgetedFixedPointApplyFunc {
+            fixed_point_func,
+            cache,
+            budget: EvaluationBudget { max_evaluations, evaluation_count: 0 },
+            __phantom: PhantomData,
+        }
+    }
+    
+    pub fn evaluation_count(&self) -> usize {
+        self.budget.evaluation_count
+    }
+    
+    pub fn is_budget_exceeded(&self) -> bool {
+        self.budget.is_stopped()
+    }
+    
+    pub fn eval(&mut self, input: &Input) -> Result<Output, BudgetExceeded> {
+        let result = eval_until_stopped(self.fixed_point_func, &mut self.cache, &mut self.budget, input);
+        if self.is_budget_exceeded() {
+            return Err(BudgetExceeded);
+        }
+        Ok(result)
+    }
+}
+
+impl StopCondition for &AtomicBool {
+    fn is_stopped(&self) -> bool {
+        self.load(AtomicOrdering::Relaxed)
+    }
+}
+
+// same as BudgetedFixedPointApplyFunc, but it gives up when the cancel flag is set, possibly from another thread.
+// the flag is checked before each evaluation.
+pub struct CancellableFixedPointApplyFunc<'a, Input, Output, CacheImpl, FixedPointFuncImpl>
+    where FixedPointFuncImpl: FuncHavingFixedPointMut<Input, Output>,
+          CacheImpl: Cache<Input, Output>
+{
+    fixed_point_func: &'a FixedPointFuncImpl,
+    cache: CacheImpl,
+    cancel: &'a AtomicBool,
+    __phantom: PhantomData<(Input, Output)>,
+}
+
+impl<'a, Input, Output: Clone + Default, CacheImpl, FixedPointFuncImpl> CancellableFixedPointApplyFunc<'a, Input, Output, CacheImpl, FixedPointFuncImpl>
+    where FixedPointFuncImpl: FuncHavingFixedPointMut<Input, Output>,
+          CacheImpl: Cache<Input, Output>
+{
+    pub fn new(fixed_point_func: &'a FixedPointFuncImpl, cache: CacheImpl, cancel: &'a AtomicBool) -> Self {
+        CancellableFixedPointApplyFunc {
+            fixed_point_func,
+            cache,
+            cancel,
+            __phantom: PhantomData,
+        }
+    }
+    
+    pub fn eval(&mut self, input: &Input) -> Result<Output, Cancelled> {
+        let result = eval_until_stopped(self.fixed_point_func, &mut self.cache, &mut self.cancel, input);
+        if self.cancel.is_stopped() {
+            return Err(Cancelled);
+        }
+        Ok(result)
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -349,4 +496,35 @@ mod tests {
         
         assert_eq!(cache_vec[123].unwrap(), 456);
     }
+    
+    #[test]
+    fn test_budgeted_fibonacci() {
+        let fibonacci_func = FibonacciFunc { invoke_count: Cell::new(0) };
+        let cache: Vec<Option<usize>> = Vec::new();
+        let mut budgeted = BudgetedFixedPointApplyFunc::new(&fibonacci_func, cache, 5);
+        assert_eq!(budgeted.eval(&30), Err(BudgetExceeded));
+        assert_eq!(fibonacci_func.invoke_count.get(), 5);
+        
+        let fibonacci_func = FibonacciFunc { invoke_count: Cell::new(0) };
+        let cache: Vec<Option<usize>> = Vec::new();
+        let mut budgeted = BudgetedFixedPointApplyFunc::new(&fibonacci_func, cache, 31);
+        assert_eq!(budgeted.eval(&30), Ok(832040));
+        assert_eq!(budgeted.evaluation_count(), 31);
+    }
+    
+    #[test]
+    fn test_cancellable_fibonacci() {
+        let cancel = AtomicBool::new(false);
+        let fibonacci_func = FibonacciFunc { invoke_count: Cell::new(0) };
+        let cache: Vec<Option<usize>> = Vec::new();
+        let mut cancellable = CancellableFixedPointApplyFunc::new(&fibonacci_func, cache, &cancel);
+        assert_eq!(cancellable.eval(&30), Ok(832040));
+        
+        cancel.store(true, AtomicOrdering::Relaxed);
+        let fibonacci_func = FibonacciFunc { invoke_count: Cell::new(0) };
+        let cache: Vec<Option<usize>> = Vec::new();
+        let mut cancellable = CancellableFixedPointApplyFunc::new(&fibonacci_func, cache, &cancel);
+        assert_eq!(cancellable.eval(&30), Err(Cancelled));
+        assert_eq!(fibonacci_func.invoke_count.get(), 0);
+    }
 }
\ No newline at end of file
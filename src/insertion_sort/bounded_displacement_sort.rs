@@ -0,0 +1,111 @@
+use std::cmp::Ordering;
+
+use crate::algo::slice_ops::rotate_left;
+use crate::search::binary_search::binary_search_rightmost;
+
+// Sort an array in which every element is at most k positions away from its sorted position.
+// It's an insertion sort, but as the element arr[i] can only be inserted into arr[(i-k)..=i],
+// it only binary-searches within the k-sized window before it.
+// It takes O(n log k) comparisons and O(n k) moves.
+// It's stable, as the element is inserted after the equal elements.
+// If the assumption doesn't hold, the result will not be sorted.
+pub fn bounded_displacement_sort<Element, Comparator>(
+    arr: &mut [Element], compare: &Comparator, k: usize,
+)
+    where
+        Comparator: Fn(&Element, &Element) -> Ordering
+{
+    for i in 1..arr.len() {
+        // arr[0..i] is sorted, insert arr[i] into it
+        
+        // fast path: it's already in place
+        if compare(&arr[i - 1], &arr[i]) != Ordering::Greater {
+            continue;
+        }
+        
+        let window_start = i.saturating_sub(k);
+        let (window, rest) = arr[window_start..].split_at(i - window_start);
+        let insert_index = window_start + binary_search_rightmost(window, compare, &rest[0]);
+        
+        // move arr[i] to insert_index, shifting arr[insert_index..i] to the right by one.
+        // it's rotating arr[insert_index..=i] left so that arr[i] becomes the first.
+        rotate_left(&mut arr[insert_index..=i], i - insert_index);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::cell::Cell;
+    
+    use rand::{Rng, SeedableRng};
+    use rand::prelude::{SliceRandom, StdRng};
+    
+    use crate::quick_sort::simple_quick_sort::normal_quick_sort;
+    
+    use super::*;
+    
+    // (key, tag), sorted by key, then shuffled within each chunk of k+1 elements,
+    // so every element is at most k positions away from its sorted position.
+    fn random_displaced_vec(rng: &mut StdRng, len: usize, k: usize) -> Vec<(i32, usize)> {
+        let mut keys: Vec<i32> = (0..len).map(|_| rng.gen_range(0..1000)).collect();
+        keys.sort();
+        let mut vec: Vec<(i32, usize)> = keys.into_iter().enumerate().map(|(tag, key)| (key, tag)).collect();
+        for chunk in vec.chunks_mut(k + 1) {
+            chunk.shuffle(rng);
+        }
+        vec
+    }
+    
+    #[test]
+    fn test_bounded_displacement_sort() {
+        let mut rng: StdRng = SeedableRng::seed_from_u64(123456);
+        
+        for _i in 0..300 {
+            let len = rng.gen_range(0..1000);
+            let k = rng.gen_range(0..20);
+            let mut vec = random_displaced_vec(&mut rng, len, k);
+            let mut vec_for_ref = vec.clone();
+            
+            bounded_displacement_sort(&mut vec, &|a: &(i32, usize), b: &(i32, usize)| a.0.cmp(&b.0), k);
+            // std's sort is stable
+            vec_for_ref.sort_by(|a, b| a.0.cmp(&b.0));
+            
+            assert_eq!(vec, vec_for_ref);
+        }
+    }
+    
+    #[test]
+    fn test_bounded_displacement_sort_comparison_count() {
+        let mut rng: StdRng = SeedableRng::seed_from_u64(123456);
+        
+        let k = 8;
+        let vec = random_displaced_vec(&mut rng, 10000, k);
+        
+        let bounded_count = Cell::new(0);
+        let mut vec1 = vec.clone();
+        bounded_displacement_sort(
+            &mut vec1,
+            &|a: &(i32, usize), b: &(i32, usize)| {
+                bounded_count.set(bounded_count.get() + 1);
+                a.0.cmp(&b.0)
+            },
+            k,
+        );
+        
+        let quick_sort_count = Cell::new(0);
+        let mut vec2 = vec.clone();
+        normal_quick_sort(
+            &mut vec2,
+            &|a: &(i32, usize), b: &(i32, usize)| {
+                quick_sort_count.set(quick_sort_count.get() + 1);
+                a.0.cmp(&b.0)
+            },
+        );
+        
+        assert!(
+            bounded_count.get() < quick_sort_count.get(),
+            "bounded displacement sort used {} comparisons, quick sort used {}",
+            bounded_count.get(), quick_sort_count.get()
+        );
+    }
+}
@@ -0,0 +1,2 @@
+pub mod simple_insertion_sort;
+pub mod bounded_displacement_sort;
@@ -0,0 +1,46 @@
+use std::cmp::Ordering;
+
+// Insertion sort. It's stable.
+// O(n^2) in the worst case, but it's fast for small arrays, so it's often used as the base case of other sorts.
+pub fn insertion_sort<Element, Comparator>(
+    arr: &mut [Element], compare: &Comparator,
+)
+    where
+        Comparator: Fn(&Element, &Element) -> Ordering
+{
+    for i in 1..arr.len() {
+        // arr[0..i] is sorted, move arr[i] left until the left element is not greater than it
+        let mut j = i;
+        while j > 0 && compare(&arr[j - 1], &arr[j]) == Ordering::Greater {
+            arr.swap(j - 1, j);
+            j -= 1;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::{Rng, SeedableRng};
+    use rand::prelude::StdRng;
+    
+    use super::*;
+    
+    #[test]
+    fn test_insertion_sort() {
+        let mut rng: StdRng = SeedableRng::seed_from_u64(123456);
+        
+        for _i in 0..300 {
+            let len = rng.gen_range(0..200);
+            let max = rng.gen_range(1..50);
+            
+            // (key, tag), only key is compared
+            let mut vec: Vec<(i32, usize)> = (0..len).map(|tag| (rng.gen_range(0..max), tag)).collect();
+            let mut vec_for_ref = vec.clone();
+            
+            insertion_sort(&mut vec, &|a: &(i32, usize), b: &(i32, usize)| a.0.cmp(&b.0));
+            vec_for_ref.sort_by(|a, b| a.0.cmp(&b.0));
+            
+            assert_eq!(vec, vec_for_ref);
+        }
+    }
+}
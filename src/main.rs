@@ -5,6 +5,11 @@ mod dyn_pro;
 mod data_structure;
 mod merge_sort;
 mod functional;
+mod search;
+mod util;
+mod select;
+mod algo;
+mod insertion_sort;
 
 fn main() {
     // see the unit tests in the other modules for examples.
@@ -1,10 +1,17 @@
-use std::{alloc, ptr, slice};
-use std::alloc::Layout;
 use std::cmp::Ordering;
+use std::mem;
 use std::ops::Range;
+use std::ptr;
+use std::sync::atomic::AtomicBool;
 
+use crate::data_structure::binary_heap::heap_sort_cancellable;
+use crate::data_structure::raw_temp_buffer::RawTempBuffer;
 use crate::merge_sort::merge::merge_multiple_sorted_sequences_smart;
-use crate::merge_sort::simple_merge_sort::simple_merge_sort_inplace;
+use crate::merge_sort::simple_merge_sort::{simple_merge_sort_inplace, simple_merge_sort_inplace_cancellable};
+use crate::quick_sort::simple_quick_sort::normal_quick_sort_cancellable;
+use crate::search::binary_search::binary_search_leftmost;
+use crate::search::sorted_slice::first_unsorted_index;
+use crate::util::cancellation::{Cancelled, check_cancelled, without_cancellation};
 
 // represents a partition of a range or sub-range.
 pub struct RangePartition {
@@ -104,9 +111,11 @@ impl RangePartition {
     
     // given n pivots, separate the range into n+1 parts.
     // the array in the given range is sorted. the pivots are in order.
-    // the elements at part i < pivot[i], the elements at part i+1 >= pivot[i]
+    // if stable, the elements at part i < pivot[i], the elements at part i+1 >= pivot[i].
+    // if unstable, the elements equal to pivot[i] may be in either part i or part i+1.
     fn find_partition_by_pivots<Element, Comparator>(
         arr: &[Element], range: Range<usize>, compare: &Comparator, pivots: &[&Element],
+        stability: SortStability,
     ) -> RangePartition
         where
             Comparator: Fn(&Element, &Element) -> Ordering
@@ -119,7 +128,13 @@ impl RangePartition {
         let mut curr_searching_range: Range<usize> = range.clone();
         
         for pivot in pivots {
-            let pivot_pos = binary_search_leftmost(&arr[curr_searching_range.clone()], compare, pivot);
+            let searching_arr = &arr[curr_searching_range.clone()];
+            let pivot_pos = match stability {
+                SortStability::Stable => binary_search_leftmost(searching_arr, compare, pivot),
+                // it can stop as soon as it finds an equal element
+                SortStability::Unstable => searching_arr.binary_search_by(|e| compare(e, pivot))
+                    .unwrap_or_else(|insert_pos| insert_pos),
+            };
             endpoints.push(curr_searching_range.start + pivot_pos);
             curr_searching_range.start += pivot_pos;
         }
@@ -148,39 +163,164 @@ impl RangePartition {
 // - copy to temp buffers phase: O( n / M )
 // - final M-way merge: O( (n / M) * log M )
 // M is much smaller than n, the overall average time complexity is O( (n / M) log (n / M) ).
-fn concurrent_merge_sort<Element, Comparator>(
+// It's stable:
+// - each part is sorted by stable merge sort.
+// - the pivots come from the first part, and in other parts, the elements equal to a pivot all go to the
+//   subpart after it (by leftmost binary search). so the equal elements of different parts
+//   are merged by the same thread, and the multi-way merge is stable.
+pub fn concurrent_merge_sort<Element, Comparator>(
     arr: &mut [Element], compare: &Comparator,
     parallelism: usize,
 )
     where
         Element: Send + Sync,
         Comparator: Fn(&Element, &Element) -> Ordering + Send + Sync
+{
+    concurrent_merge_sort_with_options(
+        arr, compare, parallelism, default_sequential_threshold(parallelism), SortStability::Stable, BaseSort::Merge,
+    );
+}
+
+// Same as concurrent_merge_sort, but it can be aborted by setting the cancel flag from another thread.
+// If it's cancelled, it returns Err(Cancelled) soon, and the array is a permutation of the input, possibly unsorted.
+// The base sorts check the flag at each recursion, and it's checked between the phases.
+// Once the elements are copied into the temp buffers, the final merge must complete to move them back,
+// so the flag is checked right before the copy, and after it the sort always completes.
+pub fn concurrent_merge_sort_cancellable<Element, Comparator>(
+    arr: &mut [Element], compare: &Comparator,
+    parallelism: usize, cancel: &AtomicBool,
+) -> Result<(), Cancelled>
+    where
+        Element: Send + Sync,
+        Comparator: Fn(&Element, &Element) -> Ordering + Send + Sync
+{
+    concurrent_merge_sort_with_options_cancellable(
+        arr, compare, parallelism, default_sequential_threshold(parallelism), SortStability::Stable, BaseSort::Merge,
+        cancel,
+    )
+}
+
+// Below the threshold, the overhead of spawning threads and the extra merge phase outweighs the gain,
+// so the input is sorted by the base sort in the current thread.
+pub fn default_sequential_threshold(parallelism: usize) -> usize {
+    parallelism * 200
+}
+
+// The number of threads that can run in parallel on this machine. 1 if it cannot be known.
+pub fn default_parallelism() -> usize {
+    match std::thread::available_parallelism() {
+        Ok(parallelism) => parallelism.get(),
+        Err(_) => 1,
+    }
+}
+
+// Shortcut for Ord types, without passing a comparator.
+pub fn concurrent_merge_sort_ord<Element: Ord + Send + Sync>(arr: &mut [Element], parallelism: usize) {
+    concurrent_merge_sort(arr, &Element::cmp, parallelism);
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SortStability {
+    Stable,
+    // it skips the tie handling when partitioning by pivots, which is slightly faster
+    // when there are many duplicates. the order of equal elements is not preserved.
+    Unstable,
+}
+
+// The sequential sort used for each part, and for small inputs.
+// The merge sort allocates temporary buffers when merging. With many threads, the allocation may contend.
+// The others are in-place and don't allocate, but they are not stable,
+// so they can only be used with SortStability::Unstable.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum BaseSort {
+    Merge,
+    Heap,
+    Quick,
+}
+
+impl BaseSort {
+    pub fn is_stable(self) -> bool {
+        match self {
+            BaseSort::Merge => true,
+            BaseSort::Heap | BaseSort::Quick => false,
+        }
+    }
+    
+    fn sort<Element, Comparator>(self, arr: &mut [Element], compare: &Comparator, cancel: &AtomicBool) -> Result<(), Cancelled>
+        where
+            Comparator: Fn(&Element, &Element) -> Ordering
+    {
+        match self {
+            BaseSort::Merge => simple_merge_sort_inplace_cancellable(arr, compare, cancel),
+            BaseSort::Heap => heap_sort_cancellable(arr, compare, cancel),
+            BaseSort::Quick => normal_quick_sort_cancellable(arr, compare, cancel),
+        }
+    }
+}
+
+// The inputs not longer than sequential_threshold are sorted by the base sort without spawning threads.
+// SortStability::Stable requires a stable base sort (BaseSort::Merge). It panics otherwise,
+// because the result would silently be unstable.
+pub fn concurrent_merge_sort_with_options<Element, Comparator>(
+    arr: &mut [Element], compare: &Comparator,
+    parallelism: usize, sequential_threshold: usize, stability: SortStability, base_sort: BaseSort,
+)
+    where
+        Element: Send + Sync,
+        Comparator: Fn(&Element, &Element) -> Ordering + Send + Sync
+{
+    without_cancellation(|cancel| concurrent_merge_sort_with_options_cancellable(
+        arr, compare, parallelism, sequential_threshold, stability, base_sort, cancel,
+    ));
+}
+
+fn concurrent_merge_sort_with_options_cancellable<Element, Comparator>(
+    arr: &mut [Element], compare: &Comparator,
+    parallelism: usize, sequential_threshold: usize, stability: SortStability, base_sort: BaseSort,
+    cancel: &AtomicBool,
+) -> Result<(), Cancelled>
+    where
+        Element: Send + Sync,
+        Comparator: Fn(&Element, &Element) -> Ordering + Send + Sync
 {
     assert!(parallelism > 0);
+    assert!(
+        stability == SortStability::Unstable || base_sort.is_stable(),
+        "stable sort requires a stable base sort, got {:?}", base_sort
+    );
     
     let len: usize = arr.len();
     if len <= 1 {
-        return;
+        return Ok(());
     }
     
-    if parallelism == 1 || len <= parallelism * 200 {
-        simple_merge_sort_inplace(arr, compare);
-        return;
+    // evenly_partition gives empty parts when there are more parts than elements,
+    // but the pivots are taken from the first part, so each part must be non-empty.
+    // it only matters when the threshold is lower than the default.
+    let parallelism = parallelism.min(len);
+    
+    if parallelism == 1 || len <= sequential_threshold {
+        return base_sort.sort(arr, compare, cancel);
     }
     
+    check_cancelled(cancel)?;
+    
     let outer_partition = RangePartition::evenly_partition(0..len, parallelism);
     
     // sort each part in each thread concurrently
-    crossbeam::thread::scope(|s| {
+    let base_sort_results: Vec<Result<(), Cancelled>> = crossbeam::thread::scope(|s| {
         let parts = outer_partition.split_borrow(arr);
         
-        for part in parts {
-            s.spawn(move |_| {
-                simple_merge_sort_inplace(part, compare);
-            });
-        }
+        let handles: Vec<_> = parts.into_iter().map(|part| {
+            s.spawn(move |_| base_sort.sort(part, compare, cancel))
+        }).collect();
+        
+        handles.into_iter().map(|handle| handle.join().unwrap()).collect()
     }).unwrap();
     
+    // if it's cancelled, all threads see the flag and stop
+    base_sort_results.into_iter().collect::<Result<(), Cancelled>>()?;
+    
     // select the pivots from the first part
     let first_part = &arr[outer_partition.part_at(0)];
     let first_part_pivot_partitions: RangePartition = RangePartition::evenly_partition(0..first_part.len(), parallelism);
@@ -196,7 +336,7 @@ fn concurrent_merge_sort<Element, Comparator>(
     
     for thread_index in 1..parallelism {
         let part_partition = RangePartition::find_partition_by_pivots(
-            arr, outer_partition.part_at(thread_index), compare, pivots.as_slice(),
+            arr, outer_partition.part_at(thread_index), compare, pivots.as_slice(), stability,
         );
         sub_partitions.push(part_partition);
     }
@@ -226,63 +366,22 @@ fn concurrent_merge_sort<Element, Comparator>(
         0,
     );
     
+    // the last chance to stop. after the copy, the merge must complete to move the elements back
+    check_cancelled(cancel)?;
+    
     // do a parallel copy from arr to allocated per-thread temporary buffers.
     // for thread k, it copies sub_partitions[i][k] to temp_partitions[k][i]
-    let arr_ptr = SendablePtrWrapper::new(arr.as_mut_ptr());
-    let sub_partitions_ref = &sub_partitions; // this should not be inlined
-    let temp_partitions_ref = &temp_partitions; // this should not be inlined
-    let temps: Vec<*mut Element> = crossbeam::thread::scope(|s| {
-        let mut handles = Vec::with_capacity(parallelism);
-        
-        for thread_index in 0..parallelism {
-            let handle = s.spawn(move |_| {
-                let alloc_layout = Layout::array::<Element>(
-                    temp_partitions_ref[thread_index].total_range().len()
-                ).unwrap();
-                
-                let temp_ptr = unsafe { alloc::alloc(alloc_layout) as *mut Element };
-                
-                for subpart_index in 0..parallelism {
-                    let src = unsafe {
-                        arr_ptr.as_mut_ptr().add(sub_partitions_ref[subpart_index].part_start(thread_index))
-                    };
-                    let dst = unsafe {
-                        temp_ptr.add(temp_partitions_ref[thread_index].part_start(subpart_index))
-                    };
-                    let len = sub_partitions_ref[subpart_index].part_length(thread_index);
-                    unsafe {
-                        ptr::copy_nonoverlapping(src, dst, len);
-                    }
-                }
-                
-                return SendablePtrWrapper::new(temp_ptr);
-            });
-            
-            handles.push(handle);
-        }
-        
-        handles.into_iter().map(
-            |handle| {
-                let wrapper: SendablePtrWrapper<Element> = handle.join().unwrap();
-                wrapper.as_mut_ptr()
-            }
-        ).collect()
-    }).unwrap();
+    let temps: Vec<RawTempBuffer<Element>> = parallel_scatter(arr, &sub_partitions, parallelism);
     
     // for thread k, do a multi-way merge for the k-th subpart of each part
     // from temps[k] to arr[result_partitions[k-1]..result_partitions[k]]
     
-    crossbeam::thread::scope(|s| {
-        for thread_index in 0..parallelism {
+    let written_counts: Vec<usize> = crossbeam::thread::scope(|s| {
+        let handles: Vec<_> = (0..parallelism).map(|thread_index| {
+            // the temp buffers are fully initialized by the scatter
+            let temp: &[Element] = unsafe { temps[thread_index].as_slice(temps[thread_index].capacity()) };
             let merge_srcs: Vec<&[Element]> = (0..parallelism).map(
-                |subpart_index| {
-                    unsafe {
-                        let range = temp_partitions[thread_index].part_at(subpart_index);
-                        slice::from_raw_parts(
-                            temps[thread_index].add(range.start), range.len(),
-                        )
-                    }
-                }
+                |subpart_index| &temp[temp_partitions[thread_index].part_at(subpart_index)]
             ).collect();
             
             let merge_dst: SendablePtrWrapper<Element> = unsafe {
@@ -293,58 +392,183 @@ fn concurrent_merge_sort<Element, Comparator>(
             let merge_region_len = result_partitions.part_length(thread_index);
             
             s.spawn(move |_| {
+                let mut written_count = 0;
+                let mut tracker = DebugMergeTracker::new(thread_index, temp, merge_region_len);
                 merge_multiple_sorted_sequences_smart(
                     merge_srcs.as_slice(), compare,
                     &mut |index, element| {
+                        tracker.record(index, element);
                         unsafe {
                             assert!(index < merge_region_len);
                             ptr::write(merge_dst.as_mut_ptr().add(index), ptr::read(element));
                         }
+                        written_count += 1;
                     },
                 );
-            });
-        }
+                written_count
+            })
+        }).collect();
+        
+        handles.into_iter().map(|handle| handle.join().unwrap()).collect()
     }).unwrap();
     
-    // free temp buffers
-    for i in 0..parallelism {
-        let layout = Layout::array::<Element>(temp_partitions[i].total_range().len()).unwrap();
-        unsafe {
-            alloc::dealloc(temps[i] as *mut u8, layout);
+    // the elements in temp buffers have been moved back into arr.
+    // dropping the temp buffers only frees the memory.
+    
+    debug_check_merge_result(arr, compare, &result_partitions, &written_counts);
+    
+    Ok(())
+}
+
+// In debug build, it records which temp elements each merging thread has consumed,
+// and which destination slots it has written, and panics when one is used twice.
+// With the written counts checked by debug_check_merge_result, each temp element is moved into arr exactly once.
+// In release build, it records nothing.
+struct DebugMergeTracker {
+    thread_index: usize,
+    temp_address: usize,
+    element_size: usize,
+    consumed: Vec<bool>,
+    written: Vec<bool>,
+}
+
+impl DebugMergeTracker {
+    fn new<Element>(thread_index: usize, temp: &[Element], merge_region_len: usize) -> Self {
+        let enabled = cfg!(debug_assertions);
+        Self {
+            thread_index,
+            temp_address: temp.as_ptr() as usize,
+            element_size: mem::size_of::<Element>(),
+            consumed: if enabled { vec![false; temp.len()] } else { Vec::new() },
+            written: if enabled { vec![false; merge_region_len] } else { Vec::new() },
+        }
+    }
+    
+    // the element is a reference into the temp buffer, its position is known from its address
+    fn record<Element>(&mut self, index: usize, element: &Element) {
+        if !cfg!(debug_assertions) {
+            return;
+        }
+        
+        assert!(
+            !self.written[index],
+            "thread {} writes destination slot {} twice", self.thread_index, index
+        );
+        self.written[index] = true;
+        
+        // zero-sized elements all have the same address, and are indistinguishable anyway
+        if self.element_size != 0 {
+            let temp_index = (element as *const Element as usize - self.temp_address) / self.element_size;
+            assert!(
+                !self.consumed[temp_index],
+                "thread {} merges temp element {} twice", self.thread_index, temp_index
+            );
+            self.consumed[temp_index] = true;
         }
     }
 }
 
-// when binary_search in std found consecutive equal elements, it may not return the leftmost one.
-// this function will return the leftmost one.
-fn binary_search_leftmost<Element, Comparator>(
-    arr: &[Element], compare: &Comparator, target: &Element,
-) -> usize
+// The phases rely on the partitions being consistent. An off-by-one there silently duplicates or loses elements.
+// In debug build, it checks that the merge destinations exactly cover the array,
+// and each destination is fully written. DebugMergeTracker has checked that no slot is written twice
+// and no temp element is consumed twice, and the temp buffer of a thread is as long as its destination,
+// so each element is moved from temp buffers exactly once, and the result is a permutation of the input.
+// And the result is sorted.
+fn debug_check_merge_result<Element, Comparator>(
+    arr: &[Element], compare: &Comparator, result_partitions: &RangePartition, written_counts: &[usize],
+)
     where
         Comparator: Fn(&Element, &Element) -> Ordering
 {
-    match arr.binary_search_by(|probe| compare(probe, target)) {
-        Ok(pos) => {
-            // if there are no more element on the left, it's the leftmost
-            if pos == 0 {
-                return pos;
-            }
-            
-            // if the left element is not equal, it's the leftmost
-            if compare(&arr[pos - 1], target) != Ordering::Equal {
-                return pos;
-            }
-            
-            // the left element exists and is equal. 
-            // there may be many equal elements on the left,
-            // so use a recursive binary search, instead of a linear search.
-            return binary_search_leftmost(&arr[0..pos], compare, target);
+    if cfg!(debug_assertions) {
+        assert_eq!(result_partitions.total_range(), 0..arr.len(), "merge destinations don't cover the array");
+        
+        for (thread_index, written_count) in written_counts.iter().enumerate() {
+            assert_eq!(
+                *written_count, result_partitions.part_length(thread_index),
+                "thread {} wrote {} elements into a destination of length {}",
+                thread_index, written_count, result_partitions.part_length(thread_index)
+            );
+        }
+        
+        if let Some(index) = first_unsorted_index(arr, compare) {
+            panic!("concurrent merge sort result is not sorted: arr[{}] > arr[{}]", index, index + 1);
         }
-        // equal element not found, return the insertion index
-        Err(pos) => pos
     }
 }
 
+// Sort each segment of the partition independently, concurrently. The elements don't cross segment boundaries.
+// The segments are distributed to threads in round-robin, each segment is sorted by stable merge sort.
+pub fn sort_segments<Element, Comparator>(
+    arr: &mut [Element], partition: &RangePartition, compare: &Comparator, parallelism: usize,
+)
+    where
+        Element: Send,
+        Comparator: Fn(&Element, &Element) -> Ordering + Sync
+{
+    assert!(parallelism > 0);
+    assert!(partition.total_end_index_exclusive() <= arr.len());
+    
+    let segments: Vec<&mut [Element]> = partition.split_borrow(&mut arr[partition.total_range()]);
+    
+    let mut segments_of_threads: Vec<Vec<&mut [Element]>> = (0..parallelism).map(|_| Vec::new()).collect();
+    for (segment_index, segment) in segments.into_iter().enumerate() {
+        segments_of_threads[segment_index % parallelism].push(segment);
+    }
+    
+    crossbeam::thread::scope(|s| {
+        for segments_of_thread in segments_of_threads {
+            s.spawn(move |_| {
+                for segment in segments_of_thread {
+                    simple_merge_sort_inplace(segment, compare);
+                }
+            });
+        }
+    }).unwrap();
+}
+
+// Parallel scatter.
+// partitions[i] separates a range of src into parallelism parts,
+// thread k copies partitions[i][k] of each i into its own temp buffer, in the order of i.
+// The elements are copied bitwise, so the ownership of the elements is not transferred,
+// the caller should ensure that each element is eventually dropped only once
+// (e.g. by moving the elements in temp buffers back).
+// The buffers are allocated by the current thread, and the other threads only read src, so it only needs Sync.
+pub fn parallel_scatter<Element: Sync>(
+    src: &[Element], partitions: &[RangePartition], parallelism: usize,
+) -> Vec<RawTempBuffer<Element>> {
+    for partition in partitions {
+        assert_eq!(partition.part_num(), parallelism);
+        assert!(partition.total_end_index_exclusive() <= src.len());
+    }
+    
+    let mut buffers: Vec<RawTempBuffer<Element>> = (0..parallelism).map(|thread_index| {
+        RawTempBuffer::new(partitions.iter().map(|p| p.part_length(thread_index)).sum())
+    }).collect();
+    
+    crossbeam::thread::scope(|s| {
+        for (thread_index, buffer) in buffers.iter_mut().enumerate() {
+            let buffer_ptr = SendablePtrWrapper::new(buffer.as_mut_ptr());
+            s.spawn(move |_| {
+                let mut offset = 0;
+                for partition in partitions {
+                    let range = partition.part_at(thread_index);
+                    unsafe {
+                        ptr::copy_nonoverlapping(
+                            src[range.clone()].as_ptr(),
+                            buffer_ptr.as_mut_ptr().add(offset),
+                            range.len(),
+                        );
+                    }
+                    offset += range.len();
+                }
+            });
+        }
+    }).unwrap();
+    
+    buffers
+}
+
 // in Rust, mut pointer is not Send or Sync by default, so create this wrapper to workaround it.
 pub struct SendablePtrWrapper<T> {
     ptr: *mut T,
@@ -381,17 +605,28 @@ mod tests {
     use rand::{Rng, SeedableRng};
     use rand::prelude::StdRng;
     
+    use crate::quick_sort::partition::PARTITION_CANCEL_CHECK_INTERVAL;
+    use crate::util::test_util::{
+        assert_stable, check_cancellable_sort, HEAP_SORT_COMPARISONS_AFTER_CANCEL, MERGE_COMPARISONS_AFTER_CANCEL,
+    };
+    
     use super::*;
     
     #[test]
-    fn test_binary_search_leftmost() {
-        let arr = [1, 2, 2, 2, 3, 4, 5, 6, 7, 8, 9];
+    fn test_concurrent_merge_sort_ord() {
+        let mut rng: StdRng = SeedableRng::seed_from_u64(123456);
         
-        assert_eq!(binary_search_leftmost(&arr, &|a, b| a.cmp(b), &2), 1);
-        assert_eq!(binary_search_leftmost(&arr, &|a, b| a.cmp(b), &3), 4);
-        assert_eq!(binary_search_leftmost(&arr, &|a, b| a.cmp(b), &9), 10);
-        assert_eq!(binary_search_leftmost(&arr, &|a, b| a.cmp(b), &0), 0);
-        assert_eq!(binary_search_leftmost(&arr, &|a, b| a.cmp(b), &10), 11);
+        let mut arr: Vec<i32> = (0..10000).map(|_| rng.gen_range(0..1000)).collect();
+        let mut arr_for_ref = arr.clone();
+        concurrent_merge_sort_ord(&mut arr, 4);
+        concurrent_merge_sort(&mut arr_for_ref, &|a, b| a.cmp(b), 4);
+        assert_eq!(arr, arr_for_ref);
+        
+        let mut arr: Vec<String> = (0..1000).map(|_| rng.gen_range(0..100).to_string()).collect();
+        let mut arr_for_ref = arr.clone();
+        concurrent_merge_sort_ord(&mut arr, 4);
+        arr_for_ref.sort();
+        assert_eq!(arr, arr_for_ref);
     }
     
     #[test]
@@ -431,6 +666,286 @@ mod tests {
         };
     }
     
+    // the default mode is stable
+    #[test]
+    fn test_concurrent_merge_sort_stability() {
+        let mut rng: StdRng = SeedableRng::seed_from_u64(123456);
+        
+        for _i in 0..50 {
+            let len = rng.gen_range(0..20000);
+            let max = rng.gen_range(1..100);
+            let parallelism = rng.gen_range(1..16);
+            
+            // (key, tag), only key is compared
+            let input: Vec<(i32, usize)> = (0..len).map(|tag| (rng.gen_range(0..max), tag)).collect();
+            let mut arr = input.clone();
+            
+            concurrent_merge_sort(&mut arr, &|a, b| a.0.cmp(&b.0), parallelism);
+            
+            assert_stable(&input, &arr);
+        }
+    }
+    
+    #[test]
+    fn test_concurrent_merge_sort_unstable() {
+        let mut rng: StdRng = SeedableRng::seed_from_u64(123456);
+        
+        for _i in 0..50 {
+            let len = rng.gen_range(0..20000);
+            let max = rng.gen_range(1..100);
+            let parallelism = rng.gen_range(1..16);
+            
+            // (key, tag), only key is compared
+            let mut arr: Vec<(i32, usize)> = (0..len).map(|tag| (rng.gen_range(0..max), tag)).collect();
+            let mut arr_for_ref = arr.clone();
+            
+            concurrent_merge_sort_with_options(
+                &mut arr, &|a, b| a.0.cmp(&b.0), parallelism, default_sequential_threshold(parallelism),
+                SortStability::Unstable, BaseSort::Merge,
+            );
+            
+            // the keys are sorted, and it's a permutation of the input
+            assert!(arr.windows(2).all(|w| w[0].0 <= w[1].0));
+            arr.sort();
+            arr_for_ref.sort();
+            assert_eq!(arr, arr_for_ref);
+        }
+    }
+    
+    // all the allowed combinations of stability and base sort
+    const STABILITY_AND_BASE_SORTS: [(SortStability, BaseSort); 4] = [
+        (SortStability::Stable, BaseSort::Merge),
+        (SortStability::Unstable, BaseSort::Merge),
+        (SortStability::Unstable, BaseSort::Heap),
+        (SortStability::Unstable, BaseSort::Quick),
+    ];
+    
+    #[test]
+    fn test_concurrent_merge_sort_base_sorts() {
+        let mut rng: StdRng = SeedableRng::seed_from_u64(123456);
+        
+        for (stability, base_sort) in STABILITY_AND_BASE_SORTS {
+            for _i in 0..30 {
+                let len = rng.gen_range(0..20000);
+                let max = rng.gen_range(1..10000);
+                let parallelism = rng.gen_range(1..16);
+                
+                // (key, tag), only key is compared
+                let input: Vec<(i32, usize)> = (0..len).map(|tag| (rng.gen_range(0..max), tag)).collect();
+                let mut arr = input.clone();
+                
+                concurrent_merge_sort_with_options(
+                    &mut arr, &|a, b| a.0.cmp(&b.0), parallelism, default_sequential_threshold(parallelism),
+                    stability, base_sort,
+                );
+                
+                if stability == SortStability::Stable {
+                    assert_stable(&input, &arr);
+                } else {
+                    assert!(arr.windows(2).all(|w| w[0].0 <= w[1].0), "base sort {:?}", base_sort);
+                    let mut sorted_input = input.clone();
+                    sorted_input.sort();
+                    arr.sort();
+                    assert_eq!(arr, sorted_input, "base sort {:?}", base_sort);
+                }
+            }
+        }
+    }
+    
+    #[test]
+    #[should_panic(expected = "stable sort requires a stable base sort")]
+    fn test_concurrent_merge_sort_stable_with_unstable_base_sort() {
+        let mut arr: Vec<i32> = (0..100).rev().collect();
+        concurrent_merge_sort_with_options(&mut arr, &|a, b| a.cmp(b), 4, 0, SortStability::Stable, BaseSort::Heap);
+    }
+    
+    // just above the threshold of sorting sequentially, with few distinct keys,
+    // so many subparts are empty, and some threads have nothing to merge
+    #[test]
+    fn test_concurrent_merge_sort_small_with_high_parallelism() {
+        let mut rng: StdRng = SeedableRng::seed_from_u64(123456);
+        
+        for _i in 0..100 {
+            let parallelism = rng.gen_range(2..40);
+            let len = parallelism * 200 + rng.gen_range(1..50);
+            let max = rng.gen_range(1..4);
+            
+            // (key, tag), only key is compared
+            let input: Vec<(i32, usize)> = (0..len).map(|tag| (rng.gen_range(0..max), tag)).collect();
+            
+            let mut arr = input.clone();
+            concurrent_merge_sort(&mut arr, &|a, b| a.0.cmp(&b.0), parallelism);
+            assert_stable(&input, &arr);
+            
+            let mut arr = input.clone();
+            concurrent_merge_sort_with_options(
+                &mut arr, &|a, b| a.0.cmp(&b.0), parallelism, default_sequential_threshold(parallelism),
+                SortStability::Unstable, BaseSort::Quick,
+            );
+            assert!(arr.windows(2).all(|w| w[0].0 <= w[1].0));
+            arr.sort();
+            let mut sorted_input = input.clone();
+            sorted_input.sort();
+            assert_eq!(arr, sorted_input);
+        }
+    }
+    
+    // with threshold 1, even tiny inputs go through the concurrent phases
+    #[test]
+    fn test_concurrent_merge_sort_threshold_one() {
+        let mut rng: StdRng = SeedableRng::seed_from_u64(123456);
+        
+        for _i in 0..300 {
+            let parallelism = rng.gen_range(1..16);
+            let len = rng.gen_range(parallelism..300);
+            let max = rng.gen_range(1..50);
+            
+            // (key, tag), only key is compared
+            let input: Vec<(i32, usize)> = (0..len).map(|tag| (rng.gen_range(0..max), tag)).collect();
+            
+            let mut arr = input.clone();
+            concurrent_merge_sort_with_options(
+                &mut arr, &|a, b| a.0.cmp(&b.0), parallelism, 1, SortStability::Stable, BaseSort::Merge,
+            );
+            assert_stable(&input, &arr);
+        }
+    }
+    
+    // more threads than elements. the parallelism is capped at the length, so no part is empty
+    #[test]
+    fn test_concurrent_merge_sort_tiny_with_large_parallelism() {
+        let mut rng: StdRng = SeedableRng::seed_from_u64(123456);
+        
+        for len in 3..=10 {
+            for _i in 0..30 {
+                let max = rng.gen_range(1..10);
+                
+                // (key, tag), only key is compared
+                let input: Vec<(i32, usize)> = (0..len).map(|tag| (rng.gen_range(0..max), tag)).collect();
+                let mut sorted_input = input.clone();
+                sorted_input.sort();
+                
+                let mut arr = input.clone();
+                concurrent_merge_sort(&mut arr, &|a, b| a.0.cmp(&b.0), 16);
+                assert_stable(&input, &arr);
+                
+                // threshold 0 never falls back to the sequential sort by length
+                for (stability, base_sort) in STABILITY_AND_BASE_SORTS {
+                    let mut arr = input.clone();
+                    concurrent_merge_sort_with_options(
+                        &mut arr, &|a, b| a.0.cmp(&b.0), 16, 0, stability, base_sort,
+                    );
+                    assert!(arr.windows(2).all(|w| w[0].0 <= w[1].0), "base sort {:?}", base_sort);
+                    if stability == SortStability::Stable {
+                        assert_stable(&input, &arr);
+                    }
+                    arr.sort();
+                    assert_eq!(arr, sorted_input);
+                }
+            }
+        }
+    }
+    
+    #[test]
+    #[cfg(debug_assertions)]
+    #[should_panic(expected = "merges temp element 1 twice")]
+    fn test_debug_merge_tracker_consumed_twice() {
+        let temp = vec![1, 2, 3];
+        let mut tracker = DebugMergeTracker::new(0, &temp, temp.len());
+        tracker.record(0, &temp[1]);
+        tracker.record(1, &temp[1]);
+    }
+    
+    #[test]
+    #[cfg(debug_assertions)]
+    #[should_panic(expected = "writes destination slot 0 twice")]
+    fn test_debug_merge_tracker_written_twice() {
+        let temp = vec![1, 2, 3];
+        let mut tracker = DebugMergeTracker::new(0, &temp, temp.len());
+        tracker.record(0, &temp[0]);
+        tracker.record(0, &temp[1]);
+    }
+    
+    #[test]
+    fn test_default_parallelism() {
+        assert!(default_parallelism() >= 1);
+        assert_eq!(default_sequential_threshold(4), 800);
+    }
+    
+    #[test]
+    fn test_concurrent_merge_sort_cancellable() {
+        // the flag is set while the 4 threads are running the base sorts, each of them finishes its current step
+        check_cancellable_sort("concurrent_merge_sort", 4 * MERGE_COMPARISONS_AFTER_CANCEL, |arr, compare, cancel| {
+            concurrent_merge_sort_cancellable(arr, &compare, 4, cancel)
+        });
+        
+        for (base_sort, base_sort_comparisons_after_cancel) in [
+            (BaseSort::Heap, HEAP_SORT_COMPARISONS_AFTER_CANCEL),
+            (BaseSort::Quick, PARTITION_CANCEL_CHECK_INTERVAL),
+        ] {
+            let name = format!("concurrent_merge_sort with base sort {:?}", base_sort);
+            check_cancellable_sort(&name, 4 * base_sort_comparisons_after_cancel, |arr, compare, cancel| {
+                concurrent_merge_sort_with_options_cancellable(
+                    arr, &compare, 4, default_sequential_threshold(4), SortStability::Unstable, base_sort, cancel,
+                )
+            });
+        }
+    }
+    
+    #[test]
+    fn test_parallel_scatter() {
+        let src: Vec<i32> = (0..100).collect();
+        
+        // two parts, each is separated into 3 subparts
+        let partitions = [
+            RangePartition::from_endpoints(vec![0, 10, 20, 50]),
+            RangePartition::from_endpoints(vec![50, 50, 70, 100]),
+        ];
+        
+        let buffers = parallel_scatter(&src, &partitions, 3);
+        assert_eq!(buffers.len(), 3);
+        
+        let concatenated: Vec<i32> = buffers.iter()
+            .flat_map(|buffer| unsafe { buffer.as_slice(buffer.capacity()) }.to_vec())
+            .collect();
+        let expected: Vec<i32> = (0..10).chain(50..50)
+            .chain(10..20).chain(50..70)
+            .chain(20..50).chain(70..100)
+            .collect();
+        assert_eq!(concatenated, expected);
+    }
+    
+    #[test]
+    fn test_sort_segments() {
+        let mut rng: StdRng = SeedableRng::seed_from_u64(123456);
+        
+        for _i in 0..50 {
+            let len = rng.gen_range(0..2000);
+            let mut vec: Vec<i32> = (0..len).map(|_| rng.gen_range(0..1000)).collect();
+            let original = vec.clone();
+            
+            // random segment endpoints. the partition may not cover the whole array
+            let mut endpoints: Vec<usize> = (0..rng.gen_range(2..20)).map(|_| rng.gen_range(0..=len)).collect();
+            endpoints.sort();
+            let partition = RangePartition::from_endpoints(endpoints);
+            let parallelism = rng.gen_range(1..8);
+            
+            sort_segments(&mut vec, &partition, &|a: &i32, b: &i32| a.cmp(b), parallelism);
+            
+            for segment_index in 0..partition.part_num() {
+                let range = partition.part_at(segment_index);
+                let mut expected = original[range.clone()].to_vec();
+                expected.sort();
+                assert_eq!(vec[range], expected);
+            }
+            
+            // the elements out of the partition are untouched
+            let total_range = partition.total_range();
+            assert_eq!(vec[..total_range.start], original[..total_range.start]);
+            assert_eq!(vec[total_range.end..], original[total_range.end..]);
+        }
+    }
+    
     #[test]
     #[ignore]
     fn test_concurrent_merge_sort_time() {
@@ -457,6 +972,35 @@ mod tests {
         test_time_for(&arr, 32);
     }
     
+    #[test]
+    #[ignore]
+    fn test_concurrent_merge_sort_base_sort_time() {
+        let mut rng: StdRng = SeedableRng::seed_from_u64(123456);
+        
+        let len = 4000000;
+        let max = 100000000;
+        
+        let arr: Vec<i32> = (0..len).map(|_| rng.gen_range(0..max)).collect();
+        
+        // with high thread count, the merge sort's temporary buffer allocation happens concurrently
+        for parallelism in [4, 16, 32] {
+            for base_sort in [BaseSort::Merge, BaseSort::Heap, BaseSort::Quick] {
+                let mut to_sort = arr.clone();
+                let start = Instant::now();
+                concurrent_merge_sort_with_options(
+                    &mut to_sort, &|a, b| a.cmp(b), parallelism, default_sequential_threshold(parallelism),
+                    SortStability::Unstable, base_sort,
+                );
+                let duration = start.elapsed();
+                
+                println!(
+                    "concurrent_merge_sort parallelism {:?} base sort {:?} time: {:?}",
+                    parallelism, base_sort, duration
+                );
+            }
+        }
+    }
+    
     fn test_time_for(mut arr: &Vec<i32>, parallelism: usize) {
         let mut to_sort = arr.clone();
         let start = Instant::now();
@@ -1,10 +1,29 @@
-use std::alloc::Layout;
 use std::cmp::Ordering;
 use std::cmp::Ordering::Less;
 use std::ptr;
-use std::slice::from_raw_parts_mut;
 
 use crate::data_structure::binary_heap::MyMinHeap;
+use crate::data_structure::matrix2d::Matrix2D;
+use crate::data_structure::raw_temp_buffer::RawTempBuffer;
+use crate::search::binary_search::partition_point;
+use crate::search::sorted_slice::first_unsorted_index;
+
+// The merge functions assume that the inputs are sorted. Unsorted input silently produces garbage.
+// In debug build, it checks that and panics with the first offending index.
+fn debug_check_sorted<Element, Comparator>(
+    arr: &[Element], compare: &Comparator, name: &str,
+)
+    where Comparator: Fn(&Element, &Element) -> Ordering
+{
+    if cfg!(debug_assertions) {
+        if let Some(index) = first_unsorted_index(arr, compare) {
+            panic!(
+                "merge input {} is not sorted: {}[{}] > {}[{}]",
+                name, name, index, name, index + 1
+            );
+        }
+    }
+}
 
 pub fn merge_two_sorted_sequences<Element, Comparator, ResultConsumer>(
     arr1: &[Element], arr2: &[Element],
@@ -14,6 +33,23 @@ pub fn merge_two_sorted_sequences<Element, Comparator, ResultConsumer>(
     where Comparator: Fn(&Element, &Element) -> Ordering,
           ResultConsumer: FnMut(usize, &Element)
 {
+    merge_two_sorted_sequences_with_source(
+        arr1, arr2, compare, &mut |index, element, _from_arr2| result_consumer(index, element),
+    );
+}
+
+// Same as merge_two_sorted_sequences, but the consumer also receives whether the element is from arr2.
+fn merge_two_sorted_sequences_with_source<Element, Comparator, ResultConsumer>(
+    arr1: &[Element], arr2: &[Element],
+    compare: &Comparator,
+    result_consumer: &mut ResultConsumer,
+)
+    where Comparator: Fn(&Element, &Element) -> Ordering,
+          ResultConsumer: FnMut(usize, &Element, bool)
+{
+    debug_check_sorted(arr1, compare, "arr1");
+    debug_check_sorted(arr2, compare, "arr2");
+    
     let mut i1 = 0;
     let mut i2 = 0;
     
@@ -21,20 +57,108 @@ pub fn merge_two_sorted_sequences<Element, Comparator, ResultConsumer>(
         let ordering = compare(&arr1[i1], &arr2[i2]);
         match ordering {
             Ordering::Less => {
-                result_consumer(i1 + i2, &arr1[i1]);
+                result_consumer(i1 + i2, &arr1[i1], false);
                 i1 += 1;
             }
             Ordering::Equal => {
                 // output i1 first
-                result_consumer(i1 + i2, &arr1[i1]);
+                result_consumer(i1 + i2, &arr1[i1], false);
                 i1 += 1;
                 // we should not output arr2[i2] here, 
                 // because there may be another element in arr1 that is equal to arr2[i2], but should be output before arr2[i2].
             }
             Ordering::Greater => {
-                result_consumer(i1 + i2, &arr2[i2]);
+                result_consumer(i1 + i2, &arr2[i2], true);
+                i2 += 1;
+            }
+        }
+    }
+    
+    while i1 < arr1.len() {
+        result_consumer(i1 + i2, &arr1[i1], false);
+        i1 += 1;
+    }
+    
+    while i2 < arr2.len() {
+        result_consumer(i1 + i2, &arr2[i2], true);
+        i2 += 1;
+    }
+}
+
+// after taking this many consecutive elements from the same side, the galloping merge starts galloping
+const MIN_GALLOP: usize = 7;
+
+// The number of leading elements that satisfy the predicate, which is true for a prefix.
+// It probes arr[0], arr[1], arr[3], arr[7], ... until the predicate fails, then binary searches in the last gap.
+// It takes O(log k) comparisons where k is the result, cheaper than binary search on the whole when k is small.
+fn gallop<Element>(arr: &[Element], pred: impl Fn(&Element) -> bool) -> usize {
+    let mut bound = 1;
+    while bound <= arr.len() && pred(&arr[bound - 1]) {
+        bound *= 2;
+    }
+    // arr[..(bound / 2)] are all true, and arr[bound - 1] is false if it exists
+    let low = bound / 2;
+    let high = (bound - 1).min(arr.len());
+    low + partition_point(&arr[low..high], pred)
+}
+
+// Same as merge_two_sorted_sequences, but when one side wins MIN_GALLOP times in a row,
+// it finds how many more elements that side wins by galloping, and outputs them without comparing one by one,
+// like the galloping mode of TimSort.
+// When one sequence is mostly smaller than the other, it takes O(log n) comparisons instead of O(n).
+// It's stable: if elements are equal, arr1's goes first.
+pub fn merge_two_sorted_sequences_galloping<Element, Comparator, ResultConsumer>(
+    arr1: &[Element], arr2: &[Element],
+    compare: &Comparator,
+    result_consumer: &mut ResultConsumer,
+)
+    where Comparator: Fn(&Element, &Element) -> Ordering,
+          ResultConsumer: FnMut(usize, &Element)
+{
+    debug_check_sorted(arr1, compare, "arr1");
+    debug_check_sorted(arr2, compare, "arr2");
+    
+    let mut i1 = 0;
+    let mut i2 = 0;
+    
+    // the number of consecutive elements taken from arr1 or arr2
+    let mut arr1_wins = 0;
+    let mut arr2_wins = 0;
+    
+    while i1 < arr1.len() && i2 < arr2.len() {
+        if arr1_wins >= MIN_GALLOP {
+            // all arr1 elements <= arr2[i2] go first, including the equal ones
+            let count = gallop(&arr1[i1..], |e| compare(e, &arr2[i2]) != Ordering::Greater);
+            for e in &arr1[i1..(i1 + count)] {
+                result_consumer(i1 + i2, e);
+                i1 += 1;
+            }
+            arr1_wins = 0;
+            continue;
+        }
+        
+        if arr2_wins >= MIN_GALLOP {
+            // only the arr2 elements < arr1[i1] go first
+            let count = gallop(&arr2[i2..], |e| compare(e, &arr1[i1]) == Ordering::Less);
+            for e in &arr2[i2..(i2 + count)] {
+                result_consumer(i1 + i2, e);
                 i2 += 1;
             }
+            arr2_wins = 0;
+            continue;
+        }
+        
+        // same as merge_two_sorted_sequences, output arr1's first when equal
+        if compare(&arr1[i1], &arr2[i2]) != Ordering::Greater {
+            result_consumer(i1 + i2, &arr1[i1]);
+            i1 += 1;
+            arr1_wins += 1;
+            arr2_wins = 0;
+        } else {
+            result_consumer(i1 + i2, &arr2[i2]);
+            i2 += 1;
+            arr2_wins += 1;
+            arr1_wins = 0;
         }
     }
     
@@ -136,7 +260,7 @@ pub fn merge_multiple_sorted_sequences_smart<Element, Comparator, ResultConsumer
         comparator(&e1.element, &e2.element)
             .then(e1.arr_index.cmp(&e2.arr_index))
     };
-    let mut min_heap: MyMinHeap<MinHeapElement<Element>, _> = MyMinHeap::new(&heap_comparator);
+    let mut min_heap: MyMinHeap<MinHeapElement<Element>, _> = MyMinHeap::with_capacity(arrs.len(), &heap_comparator);
     
     // initialize the min heap with the first element of each array
     for (arr_index, arr) in arrs.iter().enumerate() {
@@ -152,31 +276,111 @@ pub fn merge_multiple_sorted_sequences_smart<Element, Comparator, ResultConsumer
     let mut placing_index = 0;
     
     loop {
-        let min = min_heap.take_min();
-        
-        match min {
+        let (element, arr_index) = match min_heap.peek_min() {
             None => {
                 // no min found means all arrays are exhausted
                 return;
             }
-            Some(MinHeapElement { element, arr_index }) => {
-                // output it
-                result_consumer(placing_index, element);
-                placing_index += 1;
-                
-                let next_index = indices[arr_index];
-                if next_index < arrs[arr_index].len() {
-                    min_heap.insert(MinHeapElement {
-                        element: &arrs[arr_index][next_index],
-                        arr_index,
-                    });
-                    indices[arr_index] = next_index + 1;
-                }
-            }
+            Some(min) => (min.element, min.arr_index),
+        };
+        
+        // output it
+        result_consumer(placing_index, element);
+        placing_index += 1;
+        
+        let next_index = indices[arr_index];
+        if next_index < arrs[arr_index].len() {
+            // the next element of the same array replaces the min, which sifts only once
+            min_heap.replace_min(MinHeapElement {
+                element: &arrs[arr_index][next_index],
+                arr_index,
+            });
+            indices[arr_index] = next_index + 1;
+        } else {
+            min_heap.take_min();
         }
     }
 }
 
+// The k smallest elements across multiple sorted sequences, in order. It's stable.
+// It does the k-way merge using min heap, but stops after k outputs.
+// O(m + k log m) where m is the number of sequences.
+pub fn k_smallest_across<'a, Element, Comparator>(
+    arrs: &[&'a [Element]], k: usize, compare: &Comparator,
+) -> Vec<&'a Element>
+    where Comparator: Fn(&Element, &Element) -> Ordering
+{
+    let heap_comparator = |e1: &MinHeapElement<Element>, e2: &MinHeapElement<Element>| {
+        // if elements are equal, the former array is considered smaller, making it stable
+        compare(e1.element, e2.element).then(e1.arr_index.cmp(&e2.arr_index))
+    };
+    
+    // indices[i] is the index of the next element to check from arrs[i]
+    let mut indices: Vec<usize> = vec![0; arrs.len()];
+    
+    let mut firsts: Vec<MinHeapElement<Element>> = Vec::with_capacity(arrs.len());
+    for (arr_index, arr) in arrs.iter().enumerate() {
+        if let Some(first) = arr.first() {
+            firsts.push(MinHeapElement { element: first, arr_index });
+            indices[arr_index] = 1;
+        }
+    }
+    let mut min_heap: MyMinHeap<MinHeapElement<Element>, _> = MyMinHeap::from_vec(firsts, &heap_comparator);
+    
+    // k may be larger than the total length, like usize::MAX for all of them
+    let mut result: Vec<&'a Element> = Vec::with_capacity(k.min(arrs.iter().map(|a| a.len()).sum()));
+    
+    while result.len() < k {
+        let MinHeapElement { element, arr_index } = match min_heap.take_min() {
+            // all arrays are exhausted. there are less than k elements in total
+            None => break,
+            Some(min) => min,
+        };
+        result.push(element);
+        
+        let arr: &'a [Element] = arrs[arr_index];
+        if let Some(next) = arr.get(indices[arr_index]) {
+            min_heap.insert(MinHeapElement { element: next, arr_index });
+            indices[arr_index] += 1;
+        }
+    }
+    
+    result
+}
+
+// Each row of the matrix is sorted. Merge all rows into one sorted Vec.
+// If elements are equal, the one in the former row goes first.
+pub fn merge_sorted_rows<Element: Clone, Comparator>(
+    matrix: &Matrix2D<Element>, compare: &Comparator,
+) -> Vec<Element>
+    where Comparator: Fn(&Element, &Element) -> Ordering
+{
+    let rows: Vec<&[Element]> = (0..matrix.rows()).map(|row| matrix.borrow_row(row)).collect();
+    k_way_merge(&rows, compare)
+}
+
+// Merge the sorted sequences into a new Vec, cloning the elements.
+// If elements are equal, the one in the former sequence goes first.
+// Unlike merge_multiple_sorted_sequences_smart, it accepts any number of sequences, including 0 and 1.
+pub fn k_way_merge<Element: Clone, Comparator>(
+    arrs: &[&[Element]], compare: &Comparator,
+) -> Vec<Element>
+    where Comparator: Fn(&Element, &Element) -> Ordering
+{
+    // the multi-way merge requires at least 2 sequences
+    if arrs.len() < 2 {
+        return arrs.concat();
+    }
+    
+    let total_len: usize = arrs.iter().map(|arr| arr.len()).sum();
+    let mut result: Vec<Element> = Vec::with_capacity(total_len);
+    merge_multiple_sorted_sequences_smart(arrs, compare, &mut |index, e| {
+        debug_assert_eq!(index, result.len());
+        result.push(e.clone());
+    });
+    result
+}
+
 // It merges two adjacent sorted sequences arr[0..separation_index] and arr[separation_index..], inplace.
 // "Smart" means it uses binary search to reduce the range to merge.
 pub fn smart_merge_two_adjacent_sorted_sequences_inplace<Element, Comparator>(
@@ -185,6 +389,39 @@ pub fn smart_merge_two_adjacent_sorted_sequences_inplace<Element, Comparator>(
     compare: &Comparator,
 )
     where Comparator: Fn(&Element, &Element) -> Ordering
+{
+    smart_merge_two_adjacent_sorted_sequences_inplace_observed(arr, separation_index, compare, &mut |_| {});
+}
+
+// Same as smart_merge_two_adjacent_sorted_sequences_inplace, and it returns the number of cross inversions,
+// the pairs of a left element and a right element where the left one is greater. They are counted while merging.
+// The ranges skipped by the binary searches have no cross inversion:
+// the skipped left prefix is <= the right minimum, and the skipped right suffix is >= the left maximum.
+pub fn smart_merge_two_adjacent_sorted_sequences_inplace_counting_inversions<Element, Comparator>(
+    arr: &mut [Element],
+    separation_index: usize,
+    compare: &Comparator,
+) -> u64
+    where Comparator: Fn(&Element, &Element) -> Ordering
+{
+    let mut count: u64 = 0;
+    smart_merge_two_adjacent_sorted_sequences_inplace_observed(
+        arr, separation_index, compare,
+        &mut |remaining_left| count += remaining_left as u64,
+    );
+    count
+}
+
+// the observer is called when the merge outputs a right element,
+// with the number of left elements not output yet, which are all greater than it.
+fn smart_merge_two_adjacent_sorted_sequences_inplace_observed<Element, Comparator, Observer>(
+    arr: &mut [Element],
+    separation_index: usize,
+    compare: &Comparator,
+    observer: &mut Observer,
+)
+    where Comparator: Fn(&Element, &Element) -> Ordering,
+          Observer: FnMut(usize)
 {
     let len = arr.len();
     if len <= 1 {
@@ -195,6 +432,10 @@ pub fn smart_merge_two_adjacent_sorted_sequences_inplace<Element, Comparator>(
         return;
     }
     
+    // the early returns and binary searches below may hide unsorted input, so check it here
+    debug_check_sorted(&arr[..separation_index], compare, "left");
+    debug_check_sorted(&arr[separation_index..], compare, "right");
+    
     let (left_part, right_part) = arr.split_at_mut(separation_index);
     
     let left_max: &Element = &left_part[separation_index - 1];
@@ -239,10 +480,11 @@ pub fn smart_merge_two_adjacent_sorted_sequences_inplace<Element, Comparator>(
         return;
     }
     
-    merge_two_adjacent_sorted_sequences_inplace(
+    merge_two_adjacent_sorted_sequences_inplace_observed(
         &mut arr[left_delimit_index..right_delimit_index],
         separation_index - left_delimit_index,
         compare,
+        observer,
     );
 }
 
@@ -254,6 +496,19 @@ pub fn merge_two_adjacent_sorted_sequences_inplace<Element, Comparator>(
     compare: &Comparator,
 )
     where Comparator: Fn(&Element, &Element) -> Ordering
+{
+    merge_two_adjacent_sorted_sequences_inplace_observed(arr, separation_index, compare, &mut |_| {});
+}
+
+// the observer is the same as in smart_merge_two_adjacent_sorted_sequences_inplace_observed
+fn merge_two_adjacent_sorted_sequences_inplace_observed<Element, Comparator, Observer>(
+    arr: &mut [Element],
+    separation_index: usize,
+    compare: &Comparator,
+    observer: &mut Observer,
+)
+    where Comparator: Fn(&Element, &Element) -> Ordering,
+          Observer: FnMut(usize)
 {
     let len: usize = arr.len();
     
@@ -263,55 +518,435 @@ pub fn merge_two_adjacent_sorted_sequences_inplace<Element, Comparator>(
         return;
     }
     
+    // it must be checked before moving the left part out.
+    // panicking in the middle of merging would leave arr in an invalid state.
+    debug_check_sorted(&arr[..separation_index], compare, "left");
+    debug_check_sorted(&arr[separation_index..], compare, "right");
+    
     // for performance, we don't want to clone the element. instead we move it.
     // so the element at the original position will be temporarily invalid.
     // this is not allowed in safe Rust, so we use unsafe.
     
-    let alloc_layout: Layout = match Layout::array::<Element>(separation_index) {
-        Ok(alloc_layout) => alloc_layout,
-        Err(_) => {
-            panic!("Unable to allocate memory for merge_two_adjacent_sorted_sequences_inplace");
-        }
-    };
-    
-    // allocate memory for the temporary array.
-    // it will hold the left part temporarily.
-    let temp: *mut Element = unsafe {
-        std::alloc::alloc(alloc_layout) as *mut Element
-    };
+    // the temp buffer holds the left part temporarily.
+    // it frees the memory when dropped, also when a panic in the comparator unwinds through here.
+    // it doesn't drop the elements in it, they are only bitwise copies.
+    let mut temp: RawTempBuffer<Element> = RawTempBuffer::new(separation_index);
     
     // copy the left part to temp
     unsafe {
-        ptr::copy_nonoverlapping(arr.as_ptr(), temp, separation_index);
+        ptr::copy_nonoverlapping(arr.as_ptr(), temp.as_mut_ptr(), separation_index);
         // the left part in arr is temporarily in invalid state now.
     }
     
-    let temp_slice = unsafe { from_raw_parts_mut(temp, separation_index) };
+    let temp_slice = unsafe { temp.as_slice(separation_index) };
     
     let arr_ptr = arr.as_mut_ptr();
     
+    // it's declared after temp, so it's dropped before temp frees the memory
+    let mut hole = MergeHole {
+        temp: temp_slice.as_ptr(),
+        temp_len: separation_index,
+        temp_consumed: 0,
+        dst: arr_ptr,
+        merged: 0,
+    };
+    
     // merge the temp and right part into arr
     // in the merging process, if it selects an element from tmp, the merged region will grow by one,
     // if it selects an element from the right part, the merged region will also grow, and the right region will shrink by one.
     // in the end, the merged region will cover the whole arr.
-    merge_two_sorted_sequences(
+    let mut right_output_count = 0;
+    merge_two_sorted_sequences_with_source(
         &temp_slice,
         &arr[separation_index..],
         compare,
-        &mut |index, element| {
+        &mut |index, element, from_right| {
             unsafe {
                 assert!(index < len);
                 
                 ptr::write(arr_ptr.add(index), ptr::read(element));
             }
+            hole.merged = index + 1;
+            if from_right {
+                // index - right_output_count left elements have been output before it
+                observer(separation_index - (index - right_output_count));
+                right_output_count += 1;
+            } else {
+                hole.temp_consumed += 1;
+            }
         },
     );
+}
+
+// Like MergeHole in std's sort. While merging, arr[..merged] is the merged output,
+// temp[temp_consumed..] are the left elements not output yet, and they exactly fit in the gap arr[merged..],
+// before the right elements not output yet.
+// If the comparator panics, dropping it copies them back into the gap,
+// so arr is a permutation of the input again, and each element will be dropped exactly once.
+// After the merge completes, all of temp is consumed, and it copies nothing.
+struct MergeHole<Element> {
+    temp: *const Element,
+    temp_len: usize,
+    temp_consumed: usize,
+    dst: *mut Element,
+    merged: usize,
+}
+
+impl<Element> Drop for MergeHole<Element> {
+    fn drop(&mut self) {
+        unsafe {
+            ptr::copy_nonoverlapping(
+                self.temp.add(self.temp_consumed),
+                self.dst.add(self.merged),
+                self.temp_len - self.temp_consumed,
+            );
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::cell::Cell;
+    use std::panic::{AssertUnwindSafe, catch_unwind};
+    use std::time::Instant;
+    
+    use rand::{Rng, SeedableRng};
+    use rand::prelude::StdRng;
+    
+    use crate::util::test_util::{assert_stable, count_leaked_allocations};
+    
+    use super::*;
+    
+    fn merge_to_vec<Element: Clone>(
+        merge: impl Fn(&[Element], &[Element], &mut dyn FnMut(usize, &Element)), arr1: &[Element], arr2: &[Element],
+    ) -> Vec<Element> {
+        let mut result: Vec<Element> = Vec::with_capacity(arr1.len() + arr2.len());
+        merge(arr1, arr2, &mut |index, element| {
+            assert_eq!(index, result.len());
+            result.push(element.clone());
+        });
+        result
+    }
     
-    // free the memory. it will not call drop on the elements in temp.
-    unsafe {
-        std::alloc::dealloc(temp as *mut u8, alloc_layout);
+    #[test]
+    fn test_merge_inplace_frees_temp_buffer_on_panic() {
+        // the temp buffer holds the left part. the size of it is not used by other allocations here
+        type Element = [u64; 13];
+        let left_len = 37;
+        let temp_size = std::mem::size_of::<Element>() * left_len;
+        
+        // the left part is even and the right part is odd, so that the debug checks inside one part don't panic,
+        // and it panics in the middle of merging
+        let mut arr: Vec<Element> = (0..left_len as u64).map(|i| [i * 2; 13])
+            .chain((0..50).map(|i| [i * 2 + 1; 13])).collect();
+        let compare = |a: &Element, b: &Element| {
+            if a[0] % 2 != b[0] % 2 && a[0].min(b[0]) > 20 {
+                panic!("comparator panics");
+            }
+            a[0].cmp(&b[0])
+        };
+        
+        let leaked = count_leaked_allocations(temp_size, || {
+            let result = catch_unwind(AssertUnwindSafe(|| {
+                merge_two_adjacent_sorted_sequences_inplace(&mut arr, left_len, &compare);
+            }));
+            assert!(result.is_err());
+        });
+        assert_eq!(leaked, 0);
+        
+        // without panic
+        let mut arr: Vec<Element> = (0..left_len as u64).map(|i| [i * 2; 13])
+            .chain((0..50).map(|i| [i * 2 + 1; 13])).collect();
+        let leaked = count_leaked_allocations(temp_size, || {
+            merge_two_adjacent_sorted_sequences_inplace(&mut arr, left_len, &|a: &Element, b: &Element| a[0].cmp(&b[0]));
+        });
+        assert_eq!(leaked, 0);
+        assert!(arr.windows(2).all(|pair| pair[0][0] <= pair[1][0]));
+    }
+    
+    // drops of the elements are counted by id, so an element moved twice or lost is detected
+    struct DropTracked<'a> {
+        key: u64,
+        id: usize,
+        drop_counts: &'a [Cell<usize>],
+    }
+    
+    impl<'a> Drop for DropTracked<'a> {
+        fn drop(&mut self) {
+            self.drop_counts[self.id].set(self.drop_counts[self.id].get() + 1);
+        }
+    }
+    
+    #[test]
+    fn test_merge_inplace_restores_elements_on_panic() {
+        let mut rng: StdRng = SeedableRng::seed_from_u64(123456);
+        
+        for _i in 0..100 {
+            let left_len = rng.gen_range(1..50);
+            let right_len = rng.gen_range(1..50);
+            let len = left_len + right_len;
+            let drop_counts: Vec<Cell<usize>> = (0..len).map(|_| Cell::new(0)).collect();
+            
+            // the left part is even and the right part is odd, so that the debug checks inside one part don't panic
+            let mut arr: Vec<DropTracked> = (0..left_len).map(|i| (i as u64 * 2, i))
+                .chain((0..right_len).map(|i| (i as u64 * 2 + 1, left_len + i)))
+                .map(|(key, id)| DropTracked { key, id, drop_counts: &drop_counts })
+                .collect();
+            
+            // panics at a random comparison of the merge
+            let comparisons_before_panic = rng.gen_range(0..len);
+            let comparison_count = Cell::new(0);
+            let compare = |a: &DropTracked, b: &DropTracked| {
+                if a.key % 2 != b.key % 2 {
+                    if comparison_count.get() == comparisons_before_panic {
+                        panic!("comparator panics");
+                    }
+                    comparison_count.set(comparison_count.get() + 1);
+                }
+                a.key.cmp(&b.key)
+            };
+            
+            let result = catch_unwind(AssertUnwindSafe(|| {
+                merge_two_adjacent_sorted_sequences_inplace(&mut arr, left_len, &compare);
+            }));
+            
+            // it's still a permutation of the input
+            let mut ids: Vec<usize> = arr.iter().map(|e| e.id).collect();
+            ids.sort();
+            assert_eq!(ids, (0..len).collect::<Vec<usize>>());
+            if result.is_ok() {
+                assert!(arr.windows(2).all(|pair| pair[0].key <= pair[1].key));
+            }
+            
+            assert!(drop_counts.iter().all(|count| count.get() == 0));
+            drop(arr);
+            assert!(drop_counts.iter().all(|count| count.get() == 1));
+        }
     }
     
-    // Note: the temp buffer may memory leak when panic happens inside comparator.
-    // can be fixed by a type that implements Drop.
-}
\ No newline at end of file
+    #[test]
+    fn test_merge_two_sorted_sequences_galloping() {
+        let mut rng: StdRng = SeedableRng::seed_from_u64(123456);
+        let compare = |a: &(i32, usize), b: &(i32, usize)| a.0.cmp(&b.0);
+        
+        for _i in 0..300 {
+            // (key, tag), only key is compared. the tag is used for checking stability
+            let max = rng.gen_range(1..1000);
+            let mut arr1: Vec<(i32, usize)> = (0..rng.gen_range(0..500)).map(|tag| (rng.gen_range(0..max), tag)).collect();
+            // the keys of arr2 are in a narrower range, so that one side dominates for long
+            let (low, high) = (rng.gen_range(0..max), rng.gen_range(0..max));
+            let (low, high) = (low.min(high), low.max(high) + 1);
+            let mut arr2: Vec<(i32, usize)> = (0..rng.gen_range(0..500)).map(|tag| (rng.gen_range(low..high), 1000 + tag)).collect();
+            arr1.sort_by(compare);
+            arr2.sort_by(compare);
+            
+            let result = merge_to_vec(
+                |a, b, consumer| merge_two_sorted_sequences_galloping(a, b, &compare, &mut |i, e| consumer(i, e)), &arr1, &arr2,
+            );
+            let expected = merge_to_vec(
+                |a, b, consumer| merge_two_sorted_sequences(a, b, &compare, &mut |i, e| consumer(i, e)), &arr1, &arr2,
+            );
+            assert_eq!(result, expected);
+        }
+    }
+    
+    #[test]
+    fn test_merge_galloping_comparison_count() {
+        let count: Cell<usize> = Cell::new(0);
+        let counting_compare = |a: &i32, b: &i32| {
+            count.set(count.get() + 1);
+            a.cmp(b)
+        };
+        
+        // arr1 dominates. only a few arr2 elements interleave
+        let arr1: Vec<i32> = (0..100000).collect();
+        let arr2: Vec<i32> = vec![-1, 30000, 30000, 70000];
+        
+        // the comparisons of checking sorted input in debug build are not counted,
+        // by resetting the count at the first output
+        let mut galloping_result: Vec<i32> = Vec::new();
+        merge_two_sorted_sequences_galloping(&arr1, &arr2, &counting_compare, &mut |index, element| {
+            if index == 0 {
+                count.set(0);
+            }
+            galloping_result.push(*element);
+        });
+        let galloping_count = count.get();
+        
+        let mut result: Vec<i32> = Vec::new();
+        merge_two_sorted_sequences(&arr1, &arr2, &counting_compare, &mut |index, element| {
+            if index == 0 {
+                count.set(0);
+            }
+            result.push(*element);
+        });
+        let naive_count = count.get();
+        
+        assert_eq!(galloping_result, result);
+        assert!(naive_count > 70000, "{}", naive_count);
+        assert!(galloping_count < 200, "{}", galloping_count);
+    }
+    
+    #[test]
+    #[ignore]
+    fn test_merge_galloping_time() {
+        let compare = |a: &i32, b: &i32| a.cmp(b);
+        let arr1: Vec<i32> = (0..10000000).collect();
+        let arr2: Vec<i32> = (0..100).map(|i| i * 100000).collect();
+        
+        let mut sum: i64 = 0;
+        let start = Instant::now();
+        merge_two_sorted_sequences(&arr1, &arr2, &compare, &mut |_, e| sum += *e as i64);
+        println!("merge_two_sorted_sequences time: {:?}", start.elapsed());
+        
+        let start = Instant::now();
+        merge_two_sorted_sequences_galloping(&arr1, &arr2, &compare, &mut |_, e| sum -= *e as i64);
+        println!("merge_two_sorted_sequences_galloping time: {:?}", start.elapsed());
+        
+        assert_eq!(sum, 0);
+    }
+    
+    #[test]
+    fn test_k_smallest_across() {
+        let mut rng: StdRng = SeedableRng::seed_from_u64(123456);
+        
+        for _i in 0..300 {
+            // (key, tag), only key is compared. the tag is used for checking stability
+            let arr_num = rng.gen_range(0..10);
+            let mut tag = 0;
+            let arrs: Vec<Vec<(i32, usize)>> = (0..arr_num).map(|_| {
+                let len = rng.gen_range(0..100);
+                let mut arr: Vec<(i32, usize)> = (0..len).map(|_| {
+                    tag += 1;
+                    (rng.gen_range(0..50), tag)
+                }).collect();
+                arr.sort_by(|a, b| a.0.cmp(&b.0));
+                arr
+            }).collect();
+            let arr_refs: Vec<&[(i32, usize)]> = arrs.iter().map(|arr| arr.as_slice()).collect();
+            
+            // the tags increase across arrays, so the stable merge result is the stable sort of the concatenation
+            let mut merged: Vec<(i32, usize)> = arrs.concat();
+            merged.sort_by(|a, b| a.0.cmp(&b.0));
+            
+            let k = rng.gen_range(0..(merged.len() + 10));
+            let result: Vec<(i32, usize)> = k_smallest_across(&arr_refs, k, &|a: &(i32, usize), b: &(i32, usize)| a.0.cmp(&b.0))
+                .into_iter().copied().collect();
+            
+            assert_eq!(result, merged[..k.min(merged.len())]);
+        }
+        
+        // usize::MAX means all of them
+        let arrs: [&[i32]; 2] = [&[1, 3], &[2]];
+        assert_eq!(k_smallest_across(&arrs, usize::MAX, &|a: &i32, b: &i32| a.cmp(b)), vec![&1, &2, &3]);
+    }
+    
+    #[test]
+    fn test_k_way_merge() {
+        let mut rng: StdRng = SeedableRng::seed_from_u64(123456);
+        let compare = |a: &(i32, usize), b: &(i32, usize)| a.0.cmp(&b.0);
+        
+        for arr_num in 0..10 {
+            for _i in 0..30 {
+                // (key, tag), only key is compared. the tag increases across arrays
+                let mut tag = 0;
+                let arrs: Vec<Vec<(i32, usize)>> = (0..arr_num).map(|_| {
+                    let len = rng.gen_range(0..100);
+                    let mut arr: Vec<(i32, usize)> = (0..len).map(|_| {
+                        tag += 1;
+                        (rng.gen_range(0..50), tag)
+                    }).collect();
+                    arr.sort_by(compare);
+                    arr
+                }).collect();
+                let arr_refs: Vec<&[(i32, usize)]> = arrs.iter().map(|arr| arr.as_slice()).collect();
+                
+                // the tags increase across arrays, so the stable merge result is the stable sort of the concatenation
+                assert_stable(&arrs.concat(), &k_way_merge(&arr_refs, &compare));
+            }
+        }
+        
+        assert_eq!(k_way_merge::<i32, _>(&[], &|a: &i32, b: &i32| a.cmp(b)), Vec::<i32>::new());
+        assert_eq!(k_way_merge(&[&[1, 2, 3][..]], &|a: &i32, b: &i32| a.cmp(b)), vec![1, 2, 3]);
+        assert_eq!(k_way_merge(&[&[][..], &[2][..], &[][..]], &|a: &i32, b: &i32| a.cmp(b)), vec![2]);
+    }
+    
+    #[test]
+    fn test_merge_sorted_rows() {
+        let mut rng: StdRng = SeedableRng::seed_from_u64(123456);
+        
+        for _i in 0..100 {
+            let rows = rng.gen_range(0..10);
+            let cols = rng.gen_range(0..50);
+            
+            // (key, tag), only key is compared. the tag increases in row-major order
+            let mut matrix: Matrix2D<(i32, usize)> = Matrix2D::new(rows, cols, (0, 0));
+            let mut flattened: Vec<(i32, usize)> = Vec::new();
+            for row in 0..rows {
+                let mut row_data: Vec<(i32, usize)> = (0..cols).map(|col| (rng.gen_range(0..20), row * cols + col)).collect();
+                row_data.sort_by(|a, b| a.0.cmp(&b.0));
+                for (col, e) in row_data.iter().enumerate() {
+                    matrix.set(row, col, *e);
+                }
+                flattened.extend(row_data);
+            }
+            
+            let merged = merge_sorted_rows(&matrix, &|a: &(i32, usize), b: &(i32, usize)| a.0.cmp(&b.0));
+            
+            // the merge result is the stable sort of the rows concatenated in order
+            assert_stable(&flattened, &merged);
+        }
+    }
+    
+    #[test]
+    fn test_smart_merge_counting_inversions() {
+        let mut rng: StdRng = SeedableRng::seed_from_u64(123456);
+        
+        for _i in 0..1000 {
+            let len = rng.gen_range(0..100);
+            let separation_index = rng.gen_range(0..=len);
+            let max = rng.gen_range(1..50);
+            let mut arr: Vec<i32> = (0..len).map(|_| rng.gen_range(0..max)).collect();
+            arr[..separation_index].sort();
+            arr[separation_index..].sort();
+            
+            let expected_inversions = arr[..separation_index].iter()
+                .map(|a| arr[separation_index..].iter().filter(|b| a > b).count() as u64)
+                .sum::<u64>();
+            let mut expected = arr.clone();
+            expected.sort();
+            
+            let inversions = smart_merge_two_adjacent_sorted_sequences_inplace_counting_inversions(
+                &mut arr, separation_index, &|a: &i32, b: &i32| a.cmp(b),
+            );
+            assert_eq!(inversions, expected_inversions);
+            assert_eq!(arr, expected);
+        }
+    }
+    
+    #[test]
+    #[cfg(debug_assertions)]
+    #[should_panic(expected = "merge input arr2 is not sorted: arr2[1] > arr2[2]")]
+    fn test_merge_two_sorted_sequences_unsorted_input() {
+        let arr1 = [1, 3, 5];
+        let arr2 = [2, 6, 4, 8];
+        merge_two_sorted_sequences(&arr1, &arr2, &|a: &i32, b: &i32| a.cmp(b), &mut |_, _| {});
+    }
+    
+    #[test]
+    #[cfg(debug_assertions)]
+    #[should_panic(expected = "merge input left is not sorted: left[0] > left[1]")]
+    fn test_merge_inplace_unsorted_input() {
+        let mut arr = vec![3, 1, 5, 2, 4, 6];
+        merge_two_adjacent_sorted_sequences_inplace(&mut arr, 3, &|a: &i32, b: &i32| a.cmp(b));
+    }
+    
+    #[test]
+    #[cfg(debug_assertions)]
+    #[should_panic(expected = "merge input right is not sorted: right[1] > right[2]")]
+    fn test_smart_merge_inplace_unsorted_input() {
+        // left_max <= right_min, so without the check it would return early and leave it unsorted
+        let mut arr = vec![1, 2, 3, 4, 6, 5];
+        smart_merge_two_adjacent_sorted_sequences_inplace(&mut arr, 3, &|a: &i32, b: &i32| a.cmp(b));
+    }
+}
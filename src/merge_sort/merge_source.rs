@@ -0,0 +1,312 @@
+use std::cell::RefCell;
+use std::cmp::Ordering;
+use std::ptr;
+
+use crate::data_structure::binary_heap::MyMinHeap;
+use crate::data_structure::raw_temp_buffer::RawTempBuffer;
+
+// A sorted source of elements to merge. It doesn't need to be materialized as a slice.
+pub trait MergeSource<Element> {
+    // the current element. None means the source is exhausted.
+    fn peek(&self) -> Option<&Element>;
+    
+    // move to the next element
+    fn advance(&mut self);
+}
+
+pub struct SliceMergeSource<'a, Element> {
+    arr: &'a [Element],
+    index: usize,
+}
+
+impl<'a, Element> SliceMergeSource<'a, Element> {
+    pub fn new(arr: &'a [Element]) -> Self {
+        SliceMergeSource { arr, index: 0 }
+    }
+}
+
+impl<'a, Element> MergeSource<Element> for SliceMergeSource<'a, Element> {
+    fn peek(&self) -> Option<&Element> {
+        self.arr.get(self.index)
+    }
+    
+    fn advance(&mut self) {
+        self.index += 1;
+    }
+}
+
+// the elements are produced on the fly by the iterator.
+// it holds the current element, so that it can be peeked.
+pub struct IterMergeSource<Iter: Iterator> {
+    iter: Iter,
+    current: Option<Iter::Item>,
+}
+
+impl<Iter: Iterator> IterMergeSource<Iter> {
+    pub fn new(mut iter: Iter) -> Self {
+        let current = iter.next();
+        IterMergeSource { iter, current }
+    }
+}
+
+impl<Iter: Iterator> MergeSource<Iter::Item> for IterMergeSource<Iter> {
+    fn peek(&self) -> Option<&Iter::Item> {
+        self.current.as_ref()
+    }
+    
+    fn advance(&mut self) {
+        self.current = self.iter.next();
+    }
+}
+
+// It holds at most a window of elements taken from the iterator, and refills the window when it's drained.
+// buffer[start..end] are the initialized elements that are owned by this.
+struct WindowedMergeSource<Element, Iter: Iterator<Item=Element>> {
+    iter: Iter,
+    buffer: RawTempBuffer<Element>,
+    start: usize,
+    end: usize,
+}
+
+impl<Element, Iter: Iterator<Item=Element>> WindowedMergeSource<Element, Iter> {
+    fn new(iter: Iter, window_size: usize) -> Self {
+        let mut source = WindowedMergeSource { iter, buffer: RawTempBuffer::new(window_size), start: 0, end: 0 };
+        source.refill();
+        source
+    }
+    
+    // the window must be empty
+    fn refill(&mut self) {
+        self.start = 0;
+        self.end = 0;
+        while self.end < self.buffer.capacity() {
+            match self.iter.next() {
+                None => break,
+                Some(element) => {
+                    unsafe { ptr::write(self.buffer.as_mut_ptr().add(self.end), element); }
+                    // increase end after writing, so that if the iterator panics, only initialized ones are dropped
+                    self.end += 1;
+                }
+            }
+        }
+    }
+}
+
+impl<Element, Iter: Iterator<Item=Element>> MergeSource<Element> for WindowedMergeSource<Element, Iter> {
+    fn peek(&self) -> Option<&Element> {
+        if self.start < self.end {
+            Some(unsafe { &*self.buffer.as_ptr().add(self.start) })
+        } else {
+            None
+        }
+    }
+    
+    fn advance(&mut self) {
+        assert!(self.start < self.end, "advancing exhausted source");
+        // move start before dropping, so that if the drop panics, it will not be dropped again
+        self.start += 1;
+        unsafe { ptr::drop_in_place(self.buffer.as_mut_ptr().add(self.start - 1)); }
+        
+        if self.start == self.end {
+            self.refill();
+        }
+    }
+}
+
+impl<Element, Iter: Iterator<Item=Element>> Drop for WindowedMergeSource<Element, Iter> {
+    fn drop(&mut self) {
+        // RawTempBuffer doesn't drop the elements. the remaining ones are owned by this.
+        unsafe {
+            let remaining = ptr::slice_from_raw_parts_mut(
+                self.buffer.as_mut_ptr().add(self.start), self.end - self.start,
+            );
+            ptr::drop_in_place(remaining);
+        }
+    }
+}
+
+// Merge the sorted sources that are produced on the fly, such as reading from large files.
+// It holds at most window_size elements from each source at a time,
+// so the memory is bounded by the number of sources times window_size, regardless of the source lengths.
+// Larger window means fewer refills. It's stable, the same as merge_sources.
+pub fn windowed_merge<'a, Element: 'a, Iter, Comparator, ResultConsumer>(
+    sources: Vec<Iter>,
+    window_size: usize,
+    compare: &Comparator,
+    
+    // it takes an output index and an element reference
+    result_consumer: &mut ResultConsumer,
+)
+    where Iter: Iterator<Item=Element> + 'a,
+          Comparator: Fn(&Element, &Element) -> Ordering,
+          ResultConsumer: FnMut(usize, &Element)
+{
+    assert!(window_size > 0, "window size should be positive");
+    
+    let windowed_sources: Vec<Box<dyn MergeSource<Element> + 'a>> = sources.into_iter()
+        .map(|iter| Box::new(WindowedMergeSource::new(iter, window_size)) as Box<dyn MergeSource<Element> + 'a>)
+        .collect();
+    
+    merge_sources(windowed_sources, compare, result_consumer);
+}
+
+// It merges multiple sorted sources using a min heap. It's stable.
+// Same as merge_multiple_sorted_sequences_smart, but it doesn't require the sources to be slices.
+pub fn merge_sources<'a, Element, Comparator, ResultConsumer>(
+    sources: Vec<Box<dyn MergeSource<Element> + 'a>>,
+    compare: &Comparator,
+    
+    // it takes an output index and an element reference
+    result_consumer: &mut ResultConsumer,
+)
+    where Comparator: Fn(&Element, &Element) -> Ordering,
+          ResultConsumer: FnMut(usize, &Element)
+{
+    // the heap contains the indices of non-exhausted sources.
+    // the comparator peeks the sources, and the merging advances the sources, so it needs RefCell.
+    // a source is not advanced when its index is in the heap, so the heap property holds.
+    let sources = RefCell::new(sources);
+    
+    let heap_comparator = |a: &usize, b: &usize| {
+        let sources = sources.borrow();
+        // if elements are equal, the former source is considered smaller, making it stable
+        compare(sources[*a].peek().unwrap(), sources[*b].peek().unwrap())
+            .then(a.cmp(b))
+    };
+    let source_num = sources.borrow().len();
+    let mut min_heap: MyMinHeap<usize, _> = MyMinHeap::with_capacity(source_num, &heap_comparator);
+    
+    for source_index in 0..source_num {
+        let is_exhausted = sources.borrow()[source_index].peek().is_none();
+        if !is_exhausted {
+            min_heap.insert(source_index);
+        }
+    }
+    
+    let mut placing_index = 0;
+    
+    while let Some(source_index) = min_heap.take_min() {
+        let is_exhausted = {
+            let mut sources = sources.borrow_mut();
+            let source = &mut sources[source_index];
+            result_consumer(placing_index, source.peek().unwrap());
+            placing_index += 1;
+            
+            source.advance();
+            source.peek().is_none()
+        };
+        
+        if !is_exhausted {
+            min_heap.insert(source_index);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::panic::{AssertUnwindSafe, catch_unwind};
+    use std::rc::Rc;
+    
+    use rand::{Rng, SeedableRng};
+    use rand::prelude::StdRng;
+    
+    use super::*;
+    
+    #[test]
+    fn test_merge_sources() {
+        let mut rng: StdRng = SeedableRng::seed_from_u64(123456);
+        
+        for _i in 0..100 {
+            // (key, tag), only key is compared. the tag is used for checking stability
+            let len = rng.gen_range(0..1000);
+            let mut slice_part: Vec<(i32, usize)> = (0..len).map(|tag| (rng.gen_range(0..100), tag)).collect();
+            slice_part.sort_by(|a, b| a.0.cmp(&b.0));
+            
+            // the generated part has keys 0, 0, 1, 1, 2, 2, ...
+            let generated_len = rng.gen_range(0..200);
+            let generator = (0..generated_len).map(|i| (i as i32 / 2, len + i));
+            let generated_part: Vec<(i32, usize)> = generator.clone().collect();
+            
+            let sources: Vec<Box<dyn MergeSource<(i32, usize)>>> = vec![
+                Box::new(SliceMergeSource::new(&slice_part)),
+                Box::new(IterMergeSource::new(generator)),
+            ];
+            
+            let mut result: Vec<(i32, usize)> = Vec::new();
+            merge_sources(sources, &|a: &(i32, usize), b: &(i32, usize)| a.0.cmp(&b.0), &mut |index, element| {
+                assert_eq!(index, result.len());
+                result.push(*element);
+            });
+            
+            // the concatenation sorted by std's stable sort is the expected stable merge result
+            let mut expected: Vec<(i32, usize)> = slice_part.iter().chain(generated_part.iter()).copied().collect();
+            expected.sort_by(|a, b| a.0.cmp(&b.0));
+            assert_eq!(result, expected);
+        }
+    }
+    
+    #[test]
+    fn test_windowed_merge() {
+        let mut rng: StdRng = SeedableRng::seed_from_u64(123456);
+        
+        for _i in 0..100 {
+            // (key, tag), only key is compared. the tag is used for checking stability
+            let source_num = rng.gen_range(0..5);
+            let mut tag = 0;
+            let parts: Vec<Vec<(i32, usize)>> = (0..source_num).map(|_| {
+                let len = rng.gen_range(0..300);
+                let mut part: Vec<(i32, usize)> = (0..len).map(|_| {
+                    tag += 1;
+                    (rng.gen_range(0..50), tag)
+                }).collect();
+                part.sort_by(|a, b| a.0.cmp(&b.0));
+                part
+            }).collect();
+            
+            // much smaller than the sources
+            let window_size = rng.gen_range(1..10);
+            
+            let mut result: Vec<(i32, usize)> = Vec::new();
+            windowed_merge(
+                parts.iter().map(|part| part.iter().copied()).collect(),
+                window_size,
+                &|a: &(i32, usize), b: &(i32, usize)| a.0.cmp(&b.0),
+                &mut |index, element| {
+                    assert_eq!(index, result.len());
+                    result.push(*element);
+                },
+            );
+            
+            let mut expected: Vec<(i32, usize)> = parts.concat();
+            expected.sort_by(|a, b| a.0.cmp(&b.0));
+            assert_eq!(result, expected);
+        }
+    }
+    
+    #[test]
+    fn test_windowed_merge_drops_elements() {
+        // all elements are moved into the window and dropped exactly once,
+        // including the ones left in the windows when the merging panics
+        let counter = Rc::new(());
+        let sources: Vec<Vec<(i32, Rc<()>)>> = vec![
+            (0..20).map(|i| (i, counter.clone())).collect(),
+            (0..30).map(|i| (i, counter.clone())).collect(),
+        ];
+        
+        let result = catch_unwind(AssertUnwindSafe(|| {
+            windowed_merge(
+                sources.into_iter().map(|source| source.into_iter()).collect(),
+                4,
+                &|a: &(i32, Rc<()>), b: &(i32, Rc<()>)| a.0.cmp(&b.0),
+                &mut |index, _element| {
+                    if index == 25 {
+                        panic!("stop merging");
+                    }
+                },
+            );
+        }));
+        
+        assert!(result.is_err());
+        assert_eq!(Rc::strong_count(&counter), 1);
+    }
+}
@@ -1,3 +1,6 @@
 mod merge;
-mod simple_merge_sort;
-mod concurrent_merge_sort;
\ No newline at end of file
+pub mod simple_merge_sort;
+pub mod concurrent_merge_sort;
+mod tournament_merge;
+pub mod tim_sort;
+mod merge_source;
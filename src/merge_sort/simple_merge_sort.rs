@@ -1,10 +1,17 @@
 use std::cmp::Ordering;
+use std::sync::atomic::AtomicBool;
 
-use crate::merge_sort::merge::{merge_two_sorted_sequences, smart_merge_two_adjacent_sorted_sequences_inplace};
+use crate::algo::midpoint::midpoint;
+use crate::insertion_sort::simple_insertion_sort::insertion_sort;
+use crate::merge_sort::merge::{merge_two_sorted_sequences, smart_merge_two_adjacent_sorted_sequences_inplace, smart_merge_two_adjacent_sorted_sequences_inplace_counting_inversions};
+use crate::util::cancellation::{Cancelled, check_cancelled, without_cancellation};
+
+// below this size, insertion sort is faster than recursing further
+pub const DEFAULT_INSERTION_SORT_CUTOFF: usize = 24;
 
 // It does not modify the input array, it creates a new Vec.
 // This requires Clone.
-fn simple_merge_sort_requires_clone<Element: Clone, Comparator>(
+pub fn simple_merge_sort_requires_clone<Element: Clone, Comparator>(
     arr: &[Element], compare: &Comparator,
 ) -> Vec<Element>
     where
@@ -14,7 +21,7 @@ fn simple_merge_sort_requires_clone<Element: Clone, Comparator>(
         return arr.to_vec();
     }
     
-    let mid = arr.len() / 2;
+    let mid = midpoint(0, arr.len());
     
     let left = simple_merge_sort_requires_clone(&arr[..mid], compare);
     let right = simple_merge_sort_requires_clone(&arr[mid..], compare);
@@ -28,6 +35,36 @@ fn simple_merge_sort_requires_clone<Element: Clone, Comparator>(
     result
 }
 
+// Same as simple_merge_sort_requires_clone, but the final merge is done lazily by the returned iterator.
+// The two halves are still sorted eagerly. If only a prefix is consumed, the final merge doesn't complete,
+// and no Vec of the whole result is allocated.
+pub fn simple_merge_sort_iter<'a, Element: Clone + 'a, Comparator>(
+    arr: &[Element], compare: &'a Comparator,
+) -> impl Iterator<Item=Element> + 'a
+    where
+        Comparator: Fn(&Element, &Element) -> Ordering,
+{
+    let mid = midpoint(0, arr.len());
+    
+    let mut left = simple_merge_sort_requires_clone(&arr[..mid], compare).into_iter().peekable();
+    let mut right = simple_merge_sort_requires_clone(&arr[mid..], compare).into_iter().peekable();
+    
+    std::iter::from_fn(move || {
+        let take_left = match (left.peek(), right.peek()) {
+            (None, None) => return None,
+            (Some(_), None) => true,
+            (None, Some(_)) => false,
+            // if equal, output left first, making it stable
+            (Some(l), Some(r)) => compare(l, r).is_le(),
+        };
+        if take_left {
+            left.next()
+        } else {
+            right.next()
+        }
+    })
+}
+
 // this modifies the slice in-place.
 // this does not require Clone.
 pub fn simple_merge_sort_inplace<Element, Comparator>(
@@ -35,27 +72,187 @@ pub fn simple_merge_sort_inplace<Element, Comparator>(
 )
     where
         Comparator: Fn(&Element, &Element) -> Ordering
+{
+    simple_merge_sort_inplace_with_cutoff(arr, compare, DEFAULT_INSERTION_SORT_CUTOFF);
+}
+
+// Same as simple_merge_sort_inplace, but it checks the cancel flag before each recursion,
+// and returns Err(Cancelled) if it's set. The halves sorted so far are not merged then.
+pub fn simple_merge_sort_inplace_cancellable<Element, Comparator>(
+    arr: &mut [Element], compare: &Comparator, cancel: &AtomicBool,
+) -> Result<(), Cancelled>
+    where
+        Comparator: Fn(&Element, &Element) -> Ordering
+{
+    merge_sort_inplace_recursive(arr, compare, DEFAULT_INSERTION_SORT_CUTOFF, cancel)
+}
+
+// Shortcut for Ord types, without passing a comparator.
+pub fn simple_merge_sort_inplace_ord<Element: Ord>(arr: &mut [Element]) {
+    simple_merge_sort_inplace(arr, &Element::cmp);
+}
+
+// Sort in descending order, and it's still stable: the equal elements keep their original order.
+// Swapping the comparator's arguments keeps it stable, because equal elements still compare Equal,
+// and the merge still takes the left one first on ties.
+// What breaks stability is sorting ascending and then reversing, which reverses the equal elements too.
+pub fn simple_merge_sort_inplace_descending<Element, Comparator>(
+    arr: &mut [Element], compare: &Comparator,
+)
+    where
+        Comparator: Fn(&Element, &Element) -> Ordering
+{
+    simple_merge_sort_inplace(arr, &|a: &Element, b: &Element| compare(b, a));
+}
+
+// the sub-slices not longer than cutoff are sorted by insertion sort, instead of recursing to single elements.
+// both are stable, so the result is the same regardless of the cutoff.
+pub fn simple_merge_sort_inplace_with_cutoff<Element, Comparator>(
+    arr: &mut [Element], compare: &Comparator, cutoff: usize,
+)
+    where
+        Comparator: Fn(&Element, &Element) -> Ordering
+{
+    without_cancellation(|cancel| merge_sort_inplace_recursive(arr, compare, cutoff, cancel));
+}
+
+fn merge_sort_inplace_recursive<Element, Comparator>(
+    arr: &mut [Element], compare: &Comparator, cutoff: usize, cancel: &AtomicBool,
+) -> Result<(), Cancelled>
+    where
+        Comparator: Fn(&Element, &Element) -> Ordering
 {
     if arr.len() <= 1 {
-        return;
+        return Ok(());
     }
     
-    let mid = arr.len() / 2;
+    if arr.len() <= cutoff {
+        insertion_sort(arr, compare);
+        return Ok(());
+    }
     
-    simple_merge_sort_inplace(&mut arr[..mid], compare);
+    check_cancelled(cancel)?;
     
-    simple_merge_sort_inplace(&mut arr[mid..], compare);
+    let mid = midpoint(0, arr.len());
+    
+    merge_sort_inplace_recursive(&mut arr[..mid], compare, cutoff, cancel)?;
+    
+    merge_sort_inplace_recursive(&mut arr[mid..], compare, cutoff, cancel)?;
     
     smart_merge_two_adjacent_sorted_sequences_inplace(
         arr, mid, compare,
     );
+    
+    Ok(())
+}
+
+// The non-recursive merge sort. It merges the adjacent runs of width 1, 2, 4, ... in passes.
+// The last run of a pass may be shorter, or have no pair, then it's left as is for the pass.
+// It's stable, as each merge keeps the left run's elements first on ties.
+pub fn bottom_up_merge_sort_inplace<Element, Comparator>(
+    arr: &mut [Element], compare: &Comparator,
+)
+    where
+        Comparator: Fn(&Element, &Element) -> Ordering
+{
+    let len = arr.len();
+    let mut width = 1;
+    
+    while width < len {
+        // merge arr[start..(start + width)] and arr[(start + width)..end]
+        let mut start = 0;
+        while start + width < len {
+            let end = (start + 2 * width).min(len);
+            smart_merge_two_adjacent_sorted_sequences_inplace(&mut arr[start..end], width, compare);
+            start = end;
+        }
+        
+        width *= 2;
+    }
+}
+
+// Natural merge sort. It scans the natural runs (maximal non-descending sequences) first,
+// then merges the adjacent runs pairwise in passes, like the bottom-up merge sort but starting from the runs.
+// Sorted input is one run, taking n - 1 comparisons and no merging. Each pass halves the number of runs,
+// so it takes O(n log r) for r runs.
+// Descending runs are not reversed (unlike tim_sort), so a descending input gives n runs of length 1.
+// It's stable.
+pub fn natural_merge_sort_inplace<Element, Comparator>(
+    arr: &mut [Element], compare: &Comparator,
+)
+    where
+        Comparator: Fn(&Element, &Element) -> Ordering
+{
+    let len = arr.len();
+    if len <= 1 {
+        return;
+    }
+    
+    // run i is arr[run_starts[i]..run_starts[i + 1]]. the last one is len.
+    let mut run_starts: Vec<usize> = vec![0];
+    for i in 1..len {
+        if compare(&arr[i - 1], &arr[i]) == Ordering::Greater {
+            run_starts.push(i);
+        }
+    }
+    run_starts.push(len);
+    
+    while run_starts.len() > 2 {
+        // merge run 2k and run 2k+1. if the number of runs is odd, the last run is kept as is.
+        let mut merged_run_starts: Vec<usize> = Vec::with_capacity(run_starts.len() / 2 + 2);
+        for pair in run_starts.chunks(2) {
+            merged_run_starts.push(pair[0]);
+        }
+        
+        for run_index in (0..(run_starts.len() - 2)).step_by(2) {
+            let start = run_starts[run_index];
+            let mid = run_starts[run_index + 1];
+            let end = run_starts[run_index + 2];
+            smart_merge_two_adjacent_sorted_sequences_inplace(&mut arr[start..end], mid - start, compare);
+        }
+        
+        if *merged_run_starts.last().unwrap() != len {
+            merged_run_starts.push(len);
+        }
+        run_starts = merged_run_starts;
+    }
+}
+
+// Sort it and return the number of inversions (pairs i < j where arr[i] > arr[j]) of the original array.
+// The inversions are either inside one half, counted by recursion, or across the two halves.
+// An across inversion is a right element that the merge outputs before some left elements,
+// and it's inverted with all the left elements remaining at that time. They are counted by the merge itself.
+// Equal elements are not inversions, and the merge outputs the left one first on ties, so it's stable.
+pub fn merge_sort_count_inversions<Element, Comparator>(
+    arr: &mut [Element], compare: &Comparator,
+) -> u64
+    where
+        Comparator: Fn(&Element, &Element) -> Ordering
+{
+    if arr.len() <= 1 {
+        return 0;
+    }
+    
+    let mid = midpoint(0, arr.len());
+    
+    let left_inversions = merge_sort_count_inversions(&mut arr[..mid], compare);
+    let right_inversions = merge_sort_count_inversions(&mut arr[mid..], compare);
+    let cross_inversions = smart_merge_two_adjacent_sorted_sequences_inplace_counting_inversions(arr, mid, compare);
+    
+    left_inversions + right_inversions + cross_inversions
 }
 
 #[cfg(test)]
 mod tests {
+    use std::cell::Cell;
+    use std::cmp::Reverse;
+    use std::time::Instant;
+    
     use rand::{Rng, SeedableRng};
     use rand::prelude::StdRng;
     
+    use crate::util::test_util::{assert_same_order, assert_stable, check_cancellable_sort, MERGE_COMPARISONS_AFTER_CANCEL, NotCloneable, to_not_cloneable};
+    
     use super::*;
     
     fn random_int_vec(rng: &mut StdRng) -> Vec<i32> {
@@ -102,20 +299,83 @@ mod tests {
         }
     }
     
-    // #[test]
-    // fn test_stability() {
-    //     let mut v: Vec<String> = vec!["add", "what", "o", "c", "nn", "d", "ff"].iter()
-    //         .map(|s|s.to_string()).collect();
-    //     let mut v_ref = v.clone();
-    //     
-    //     let sorted = simple_merge_sort_inplace(
-    //         v.as_mut_slice(), &|a, b| a.len().cmp(&b.len())
-    //     );
-    //     
-    //     v_ref.sort_by(&|a: &String, b: &String| a.len().cmp(&b.len()));
-    //     
-    //     assert_eq!(v, v_ref);
-    // }
+    #[test]
+    fn test_simple_merge_sort_iter() {
+        let mut rng: StdRng = SeedableRng::seed_from_u64(123456);
+        
+        for _i in 0..300 {
+            let len = rng.gen_range(0..1000);
+            let max = rng.gen_range(1..100);
+            
+            // (key, tag), only key is compared
+            let vec: Vec<(i32, usize)> = (0..len).map(|tag| (rng.gen_range(0..max), tag)).collect();
+            let mut vec_for_ref = vec.clone();
+            vec_for_ref.sort_by(|a, b| a.0.cmp(&b.0));
+            
+            let k = rng.gen_range(0..=len);
+            let compare = |a: &(i32, usize), b: &(i32, usize)| a.0.cmp(&b.0);
+            let taken: Vec<(i32, usize)> = simple_merge_sort_iter(&vec, &compare).take(k).collect();
+            
+            assert_eq!(taken, vec_for_ref[..k]);
+        }
+    }
+    
+    #[test]
+    fn test_simple_merge_sort_inplace_not_cloneable() {
+        let mut rng = SeedableRng::seed_from_u64(123456);
+        
+        for _i in 0..100 {
+            let mut vec = random_int_vec(&mut rng);
+            let mut boxed: Vec<NotCloneable> = to_not_cloneable(&vec);
+            
+            simple_merge_sort_inplace(&mut boxed, &|a, b| a.cmp(b));
+            
+            vec.sort();
+            assert_eq!(boxed, to_not_cloneable(&vec));
+        }
+    }
+    
+    #[test]
+    fn test_simple_merge_sort_inplace_stability() {
+        let mut rng: StdRng = SeedableRng::seed_from_u64(123456);
+        
+        for _i in 0..300 {
+            let len = rng.gen_range(0..1000);
+            let max = rng.gen_range(1..50);
+            // (key, tag), only key is compared
+            let input: Vec<(i32, usize)> = (0..len).map(|tag| (rng.gen_range(0..max), tag)).collect();
+            let mut vec = input.clone();
+            
+            simple_merge_sort_inplace(&mut vec, &|a: &(i32, usize), b: &(i32, usize)| a.0.cmp(&b.0));
+            
+            assert_stable(&input, &vec);
+        }
+    }
+    
+    #[test]
+    fn test_simple_merge_sort_inplace_ord() {
+        let mut rng = SeedableRng::seed_from_u64(123456);
+        
+        let mut vec = random_int_vec(&mut rng);
+        let mut vec_ref = vec.clone();
+        simple_merge_sort_inplace_ord(&mut vec);
+        simple_merge_sort_inplace(&mut vec_ref, &|a, b| a.cmp(b));
+        assert_same_order(&vec, &vec_ref);
+        
+        let mut vec = random_string_vec(&mut rng);
+        let mut vec_ref = vec.clone();
+        simple_merge_sort_inplace_ord(&mut vec);
+        simple_merge_sort_inplace(&mut vec_ref, &|a: &String, b: &String| a.cmp(b));
+        assert_same_order(&vec, &vec_ref);
+    }
+    
+    #[test]
+    fn test_simple_merge_sort_inplace_cancellable() {
+        check_cancellable_sort("simple_merge_sort_inplace", MERGE_COMPARISONS_AFTER_CANCEL, |arr, compare, cancel| {
+            simple_merge_sort_inplace_cancellable(arr, &compare, cancel)
+        });
+    }
+    
     #[test]
     fn test_simple_merge_sort_inplace() {
         let mut rng = SeedableRng::seed_from_u64(123456);
@@ -128,7 +388,7 @@ mod tests {
             
             vec_ref.sort();
             
-            assert_eq!(vec, vec_ref);
+            assert_same_order(&vec, &vec_ref);
         }
         
         for _i in 0..1000 {
@@ -141,7 +401,203 @@ mod tests {
             
             vec_ref.sort_by(&|a: &String, b: &String| a.len().cmp(&b.len()));
             
-            assert_eq!(vec, vec_ref);
+            assert_same_order(&vec, &vec_ref);
+        }
+    }
+    
+    #[test]
+    fn test_bottom_up_merge_sort_inplace() {
+        let mut rng = SeedableRng::seed_from_u64(123456);
+        
+        for _i in 0..300 {
+            let mut vec = random_int_vec(&mut rng);
+            let mut vec_ref = vec.clone();
+            
+            bottom_up_merge_sort_inplace(vec.as_mut_slice(), &|a, b| a.cmp(b));
+            vec_ref.sort();
+            
+            assert_same_order(&vec, &vec_ref);
+        }
+        
+        // stable, same as the recursive version
+        for _i in 0..300 {
+            let mut vec = random_string_vec(&mut rng);
+            let mut vec_ref = vec.clone();
+            let mut vec_recursive = vec.clone();
+            
+            bottom_up_merge_sort_inplace(vec.as_mut_slice(), &|a, b| a.len().cmp(&b.len()));
+            vec_ref.sort_by(&|a: &String, b: &String| a.len().cmp(&b.len()));
+            simple_merge_sort_inplace(vec_recursive.as_mut_slice(), &|a, b| a.len().cmp(&b.len()));
+            
+            assert_same_order(&vec, &vec_ref);
+            assert_eq!(vec, vec_recursive);
+        }
+        
+        let mut vec = to_not_cloneable(&[3, 1, 2, 5, 4]);
+        bottom_up_merge_sort_inplace(&mut vec, &|a: &NotCloneable, b: &NotCloneable| a.0.cmp(&b.0));
+        assert_eq!(vec.iter().map(|e| *e.0).collect::<Vec<i32>>(), vec![1, 2, 3, 4, 5]);
+    }
+    
+    #[test]
+    fn test_simple_merge_sort_inplace_descending() {
+        let mut rng: StdRng = SeedableRng::seed_from_u64(123456);
+        
+        for _i in 0..300 {
+            // (key, tag), only key is compared
+            let len = rng.gen_range(0..1000);
+            let max = rng.gen_range(1..50);
+            let input: Vec<(i32, usize)> = (0..len).map(|tag| (rng.gen_range(0..max), tag)).collect();
+            let mut vec = input.clone();
+            
+            simple_merge_sort_inplace_descending(&mut vec, &|a: &(i32, usize), b: &(i32, usize)| a.0.cmp(&b.0));
+            
+            // descending keys is ascending reversed keys, and the tags among equal keys keep the input order
+            let reversed_keys = |v: &[(i32, usize)]| -> Vec<(Reverse<i32>, usize)> {
+                v.iter().map(|(key, tag)| (Reverse(*key), *tag)).collect()
+            };
+            assert_stable(&reversed_keys(&input), &reversed_keys(&vec));
+        }
+        
+        // sorting ascending then reversing is not stable
+        let mut vec = vec![(1, 'a'), (2, 'b'), (1, 'c'), (2, 'd')];
+        let mut reversed = vec.clone();
+        simple_merge_sort_inplace_descending(&mut vec, &|a: &(i32, char), b: &(i32, char)| a.0.cmp(&b.0));
+        assert_eq!(vec, vec![(2, 'b'), (2, 'd'), (1, 'a'), (1, 'c')]);
+        simple_merge_sort_inplace(&mut reversed, &|a: &(i32, char), b: &(i32, char)| a.0.cmp(&b.0));
+        reversed.reverse();
+        assert_eq!(reversed, vec![(2, 'd'), (2, 'b'), (1, 'c'), (1, 'a')]);
+    }
+    
+    #[test]
+    fn test_natural_merge_sort_inplace() {
+        let mut rng: StdRng = SeedableRng::seed_from_u64(123456);
+        
+        for _i in 0..300 {
+            // (key, tag), only key is compared
+            let len = rng.gen_range(0..1000);
+            let max = rng.gen_range(1..50);
+            let mut vec: Vec<(i32, usize)> = (0..len).map(|tag| (rng.gen_range(0..max), tag)).collect();
+            
+            // some inputs have long runs
+            if rng.gen_bool(0.5) {
+                let run_len = rng.gen_range(1..100);
+                for chunk in vec.chunks_mut(run_len) {
+                    chunk.sort_by_key(|e| e.0);
+                }
+            }
+            
+            let input = vec.clone();
+            natural_merge_sort_inplace(&mut vec, &|a: &(i32, usize), b: &(i32, usize)| a.0.cmp(&b.0));
+            assert_stable(&input, &vec);
+        }
+    }
+    
+    #[test]
+    fn test_natural_merge_sort_comparison_count() {
+        let count: Cell<usize> = Cell::new(0);
+        let counting_compare = |a: &i32, b: &i32| {
+            count.set(count.get() + 1);
+            a.cmp(b)
+        };
+        let len = 10000;
+        
+        // one run. only the scan
+        let mut vec: Vec<i32> = (0..len).collect();
+        natural_merge_sort_inplace(&mut vec, &counting_compare);
+        assert_eq!(count.get(), len as usize - 1);
+        assert_eq!(vec, (0..len).collect::<Vec<i32>>());
+        
+        // all equal is also one run
+        count.set(0);
+        let mut vec: Vec<i32> = vec![7; len as usize];
+        natural_merge_sort_inplace(&mut vec, &counting_compare);
+        assert_eq!(count.get(), len as usize - 1);
+        
+        // descending gives single-element runs, still sorted correctly
+        let mut vec: Vec<i32> = (0..len).rev().collect();
+        natural_merge_sort_inplace(&mut vec, &counting_compare);
+        assert_eq!(vec, (0..len).collect::<Vec<i32>>());
+        
+        // two runs
+        let mut vec: Vec<i32> = (0..len).map(|i| (i * 2) % len).collect();
+        natural_merge_sort_inplace(&mut vec, &counting_compare);
+        let mut vec_ref = vec.clone();
+        vec_ref.sort();
+        assert_same_order(&vec, &vec_ref);
+    }
+    
+    fn count_inversions_brute_force<T>(arr: &[T], compare: impl Fn(&T, &T) -> Ordering) -> u64 {
+        let mut count = 0;
+        for i in 0..arr.len() {
+            for j in (i + 1)..arr.len() {
+                if compare(&arr[i], &arr[j]) == Ordering::Greater {
+                    count += 1;
+                }
+            }
+        }
+        count
+    }
+    
+    #[test]
+    fn test_merge_sort_count_inversions() {
+        let mut rng: StdRng = SeedableRng::seed_from_u64(123456);
+        let compare = |a: &(i32, usize), b: &(i32, usize)| a.0.cmp(&b.0);
+        
+        for _i in 0..1000 {
+            // (key, tag), only key is compared
+            let len = rng.gen_range(0..100);
+            let max = rng.gen_range(1..50);
+            let mut vec: Vec<(i32, usize)> = (0..len).map(|tag| (rng.gen_range(0..max), tag)).collect();
+            
+            let input = vec.clone();
+            let inversions = merge_sort_count_inversions(&mut vec, &compare);
+            
+            assert_eq!(inversions, count_inversions_brute_force(&input, compare));
+            assert_stable(&input, &vec);
+        }
+        
+        let mut vec: Vec<i32> = (0..100).rev().collect();
+        assert_eq!(merge_sort_count_inversions(&mut vec, &|a: &i32, b: &i32| a.cmp(b)), 100 * 99 / 2);
+        assert_eq!(merge_sort_count_inversions(&mut vec, &|a: &i32, b: &i32| a.cmp(b)), 0);
+        
+        let mut vec = vec![5; 100];
+        assert_eq!(merge_sort_count_inversions(&mut vec, &|a: &i32, b: &i32| a.cmp(b)), 0);
+    }
+    
+    #[test]
+    fn test_simple_merge_sort_inplace_with_cutoff() {
+        let mut rng = SeedableRng::seed_from_u64(123456);
+        
+        for cutoff in [0, 1, 2, 5, 24, 100, 2000] {
+            for _i in 0..100 {
+                let mut vec = random_string_vec(&mut rng);
+                let mut vec_ref = vec.clone();
+                
+                simple_merge_sort_inplace_with_cutoff(
+                    vec.as_mut_slice(), &|a, b| a.len().cmp(&b.len()), cutoff,
+                );
+                
+                vec_ref.sort_by(&|a: &String, b: &String| a.len().cmp(&b.len()));
+                
+                assert_same_order(&vec, &vec_ref);
+            }
+        }
+    }
+    
+    #[test]
+    #[ignore]
+    fn test_simple_merge_sort_cutoff_time() {
+        let mut rng: StdRng = SeedableRng::seed_from_u64(123456);
+        
+        let arr: Vec<i32> = (0..4000000).map(|_| rng.gen_range(0..100000000)).collect();
+        
+        for cutoff in [1, 8, 16, 24, 32, 64] {
+            let mut to_sort = arr.clone();
+            let start = Instant::now();
+            simple_merge_sort_inplace_with_cutoff(&mut to_sort, &|a, b| a.cmp(b), cutoff);
+            let duration = start.elapsed();
+            
+            println!("simple_merge_sort_inplace_with_cutoff cutoff {:?} time: {:?}", cutoff, duration);
         }
     }
 }
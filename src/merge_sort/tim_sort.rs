@@ -0,0 +1,247 @@
+use std::cmp::Ordering;
+use std::ops::Range;
+
+use crate::insertion_sort::bounded_displacement_sort::bounded_displacement_sort;
+use crate::merge_sort::merge::smart_merge_two_adjacent_sorted_sequences_inplace;
+
+// TimSort. Reference: https://en.wikipedia.org/wiki/Timsort
+// It scans the array from left to right, finding the natural runs (already sorted sequences).
+// Strictly descending runs are reversed. Short runs are extended to min_run length using insertion sort.
+// The runs are pushed into a stack, and merged to keep the run lengths balanced.
+// The stack invariants (from top to bottom, denote the lengths as A, B, C, D):
+// * B > A
+// * C > B + A
+// * D > C + B
+// The original implementation only checked the top 3 runs, which could break the invariant deeper in the stack
+// (found in 2015 by formal verification). This implementation also checks the 4th run.
+// It's stable.
+pub fn tim_sort<Element, Comparator>(
+    arr: &mut [Element], compare: &Comparator,
+)
+    where
+        Comparator: Fn(&Element, &Element) -> Ordering
+{
+    tim_sort_observing_merges(arr, compare, &mut |_merged_len| {});
+}
+
+// on_merge is called with the length of each merged range.
+fn tim_sort_observing_merges<Element, Comparator, MergeObserver>(
+    arr: &mut [Element], compare: &Comparator, on_merge: &mut MergeObserver,
+)
+    where
+        Comparator: Fn(&Element, &Element) -> Ordering,
+        MergeObserver: FnMut(usize)
+{
+    let len = arr.len();
+    if len <= 1 {
+        return;
+    }
+    
+    let min_run = compute_min_run(len);
+    
+    let mut run_stack: Vec<Range<usize>> = Vec::new();
+    
+    let mut run_start = 0;
+    while run_start < len {
+        let mut run_end = find_run_end(arr, compare, run_start);
+        
+        // extend short run
+        if run_end - run_start < min_run {
+            let forced_end = (run_start + min_run).min(len);
+            // it's binary insertion sort when the displacement is not bounded
+            bounded_displacement_sort(&mut arr[run_start..forced_end], compare, forced_end - run_start);
+            run_end = forced_end;
+        }
+        
+        run_stack.push(run_start..run_end);
+        merge_collapse(arr, compare, &mut run_stack, on_merge);
+        
+        run_start = run_end;
+    }
+    
+    // merge all remaining runs
+    while run_stack.len() > 1 {
+        let n = run_stack.len() - 2;
+        // merge the smaller neighbor first, same as merge_collapse
+        let n = if n > 0 && run_stack[n - 1].len() < run_stack[n + 1].len() { n - 1 } else { n };
+        merge_at(arr, compare, &mut run_stack, n, on_merge);
+    }
+}
+
+// it takes 32..=64, so that len / min_run is equal to or slightly less than a power of 2,
+// which makes the final merges balanced
+fn compute_min_run(mut len: usize) -> usize {
+    // becomes 1 if any 1 bits are shifted off
+    let mut r = 0;
+    while len >= 64 {
+        r |= len & 1;
+        len >>= 1;
+    }
+    len + r
+}
+
+// find the end of the natural run starting at run_start.
+// if the run is strictly descending, reverse it.
+// strictly descending is required for stability, as reversing equal elements breaks the order.
+fn find_run_end<Element, Comparator>(
+    arr: &mut [Element], compare: &Comparator, run_start: usize,
+) -> usize
+    where
+        Comparator: Fn(&Element, &Element) -> Ordering
+{
+    let len = arr.len();
+    let mut run_end = run_start + 1;
+    if run_end == len {
+        return run_end;
+    }
+    
+    if compare(&arr[run_end], &arr[run_start]) == Ordering::Less {
+        // strictly descending
+        while run_end < len && compare(&arr[run_end], &arr[run_end - 1]) == Ordering::Less {
+            run_end += 1;
+        }
+        arr[run_start..run_end].reverse();
+    } else {
+        // non-descending
+        while run_end < len && compare(&arr[run_end], &arr[run_end - 1]) != Ordering::Less {
+            run_end += 1;
+        }
+    }
+    
+    run_end
+}
+
+// merge runs until the stack invariants hold
+fn merge_collapse<Element, Comparator, MergeObserver>(
+    arr: &mut [Element], compare: &Comparator,
+    run_stack: &mut Vec<Range<usize>>, on_merge: &mut MergeObserver,
+)
+    where
+        Comparator: Fn(&Element, &Element) -> Ordering,
+        MergeObserver: FnMut(usize)
+{
+    while run_stack.len() > 1 {
+        // run_stack[n + 1] is the top
+        let mut n = run_stack.len() - 2;
+        let len_of = |run_stack: &Vec<Range<usize>>, i: usize| run_stack[i].len();
+        
+        if (n > 0 && len_of(run_stack, n - 1) <= len_of(run_stack, n) + len_of(run_stack, n + 1))
+            || (n > 1 && len_of(run_stack, n - 2) <= len_of(run_stack, n - 1) + len_of(run_stack, n))
+        {
+            // merge the middle run with the smaller neighbor
+            if len_of(run_stack, n - 1) < len_of(run_stack, n + 1) {
+                n -= 1;
+            }
+        } else if len_of(run_stack, n) > len_of(run_stack, n + 1) {
+            // invariants hold
+            return;
+        }
+        
+        merge_at(arr, compare, run_stack, n, on_merge);
+    }
+}
+
+// merge run_stack[n] and run_stack[n + 1]
+fn merge_at<Element, Comparator, MergeObserver>(
+    arr: &mut [Element], compare: &Comparator,
+    run_stack: &mut Vec<Range<usize>>, n: usize, on_merge: &mut MergeObserver,
+)
+    where
+        Comparator: Fn(&Element, &Element) -> Ordering,
+        MergeObserver: FnMut(usize)
+{
+    let left = run_stack[n].clone();
+    let right = run_stack.remove(n + 1);
+    assert_eq!(left.end, right.start);
+    
+    smart_merge_two_adjacent_sorted_sequences_inplace(
+        &mut arr[left.start..right.end], left.len(), compare,
+    );
+    on_merge(right.end - left.start);
+    
+    run_stack[n] = left.start..right.end;
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::{Rng, SeedableRng};
+    use rand::prelude::StdRng;
+    
+    use crate::util::test_util::assert_stable;
+    
+    use super::*;
+    
+    // (key, tag) with many runs of varying lengths, some ascending, some descending.
+    fn random_runs_vec(rng: &mut StdRng) -> Vec<(i32, usize)> {
+        let run_num = rng.gen_range(1..100);
+        let max = rng.gen_range(1..1000);
+        let mut vec: Vec<(i32, usize)> = Vec::new();
+        for _ in 0..run_num {
+            let run_len = if rng.gen_bool(0.2) { rng.gen_range(0..2000) } else { rng.gen_range(0..50) };
+            let mut run: Vec<i32> = (0..run_len).map(|_| rng.gen_range(0..max)).collect();
+            run.sort();
+            if rng.gen_bool(0.5) {
+                run.reverse();
+            }
+            vec.extend(run.into_iter().map(|key| (key, 0)));
+        }
+        // tag is the original index
+        vec.iter_mut().enumerate().for_each(|(i, e)| e.1 = i);
+        vec
+    }
+    
+    #[test]
+    fn test_tim_sort() {
+        let mut rng: StdRng = SeedableRng::seed_from_u64(123456);
+        
+        for _i in 0..100 {
+            let mut vec = if rng.gen_bool(0.2) {
+                // random
+                let len = rng.gen_range(0..2000);
+                (0..len).map(|i| (rng.gen_range(0..100), i)).collect()
+            } else {
+                random_runs_vec(&mut rng)
+            };
+            let input = vec.clone();
+            
+            tim_sort(&mut vec, &|a: &(i32, usize), b: &(i32, usize)| a.0.cmp(&b.0));
+            
+            assert_stable(&input, &vec);
+        }
+    }
+    
+    #[test]
+    fn test_tim_sort_merge_work() {
+        let mut rng: StdRng = SeedableRng::seed_from_u64(123456);
+        
+        for _i in 0..100 {
+            let mut vec = random_runs_vec(&mut rng);
+            let len = vec.len();
+            
+            let mut total_merged: usize = 0;
+            tim_sort_observing_merges(
+                &mut vec, &|a: &(i32, usize), b: &(i32, usize)| a.0.cmp(&b.0),
+                &mut |merged_len| total_merged += merged_len,
+            );
+            
+            // each merge level touches at most n elements, and the balanced merging makes O(log n) levels.
+            let log_len = (len.max(2) as f64).log2();
+            assert!(
+                (total_merged as f64) <= 2.0 * (len as f64) * log_len,
+                "merged {} elements in total for length {}", total_merged, len
+            );
+        }
+    }
+    
+    #[test]
+    fn test_compute_min_run() {
+        assert_eq!(compute_min_run(10), 10);
+        assert_eq!(compute_min_run(64), 32);
+        assert_eq!(compute_min_run(65), 33);
+        
+        for len in 64..100000 {
+            let min_run = compute_min_run(len);
+            assert!((32..=64).contains(&min_run));
+        }
+    }
+}
@@ -0,0 +1,190 @@
+use std::cmp::Ordering;
+
+use crate::algo::midpoint::midpoint;
+
+// It merges multiple sorted sequences into one sorted sequence, using a loser tree (tournament tree).
+// The leaves are the heads of the sequences. Each internal node records the loser of the match in it,
+// and the overall winner is recorded separately.
+// After outputting the winner, only the path from the winner's leaf to the root needs to be replayed,
+// and each node on that path takes one comparison with the stored loser.
+// Compared with the binary heap, which compares with both children when sifting down,
+// it does about half of the comparisons, which matters when merging a large number of sequences.
+// It's stable: if elements are equal, the one from the former array is output first.
+pub fn merge_tournament<Element, Comparator, ResultConsumer>(
+    arrs: &[&[Element]],
+    compare: &Comparator,
+    
+    // it takes an output index and an element reference
+    result_consumer: &mut ResultConsumer,
+)
+    where Comparator: Fn(&Element, &Element) -> Ordering,
+          ResultConsumer: FnMut(usize, &Element)
+{
+    let k = arrs.len();
+    if k == 0 {
+        return;
+    }
+    
+    // indices[i] is the index of the next element to output from arrs[i]
+    let mut indices: Vec<usize> = vec![0; k];
+    
+    // whether arr a's head should be output before arr b's head.
+    // an exhausted array is considered larger than everything.
+    // if the heads are equal, the former array wins, to make it stable.
+    let beats = |indices: &Vec<usize>, a: usize, b: usize| -> bool {
+        let a_exhausted = indices[a] >= arrs[a].len();
+        let b_exhausted = indices[b] >= arrs[b].len();
+        match (a_exhausted, b_exhausted) {
+            (true, _) => false,
+            (false, true) => true,
+            (false, false) => {
+                compare(&arrs[a][indices[a]], &arrs[b][indices[b]])
+                    .then(a.cmp(&b)) == Ordering::Less
+            }
+        }
+    };
+    
+    // the tree is stored like a binary heap, node n has children 2n and 2n+1.
+    // internal nodes are 1..k, the leaf of arr i is node k+i.
+    // losers[n] is the arr index that lost the match in internal node n.
+    let mut losers: Vec<usize> = vec![0; k];
+    
+    // build the tree bottom-up. winners[n] is the winner of the subtree rooted at node n.
+    let mut winners: Vec<usize> = vec![0; 2 * k];
+    for arr_index in 0..k {
+        winners[k + arr_index] = arr_index;
+    }
+    for node in (1..k).rev() {
+        let left = winners[2 * node];
+        let right = winners[2 * node + 1];
+        if beats(&indices, right, left) {
+            winners[node] = right;
+            losers[node] = left;
+        } else {
+            winners[node] = left;
+            losers[node] = right;
+        }
+    }
+    let mut winner: usize = winners[1];
+    
+    let mut placing_index = 0;
+    
+    loop {
+        if indices[winner] >= arrs[winner].len() {
+            // the winner is exhausted means all arrays are exhausted
+            return;
+        }
+        
+        // output it
+        result_consumer(placing_index, &arrs[winner][indices[winner]]);
+        placing_index += 1;
+        indices[winner] += 1;
+        
+        // replay the matches from the winner's leaf to the root.
+        // the new head of the winner's arr competes with the stored losers on the path.
+        let mut node = midpoint(k, winner);
+        while node >= 1 {
+            if beats(&indices, losers[node], winner) {
+                std::mem::swap(&mut losers[node], &mut winner);
+            }
+            node /= 2;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::cell::Cell;
+    
+    use rand::{Rng, SeedableRng};
+    use rand::prelude::StdRng;
+    
+    use crate::merge_sort::merge::merge_multiple_sorted_sequences_smart;
+    
+    use super::*;
+    
+    #[test]
+    fn test_merge_tournament() {
+        let mut rng: StdRng = SeedableRng::seed_from_u64(123456);
+        
+        // the heap merge replaces the min by the next element, sifting once per output.
+        // on some inputs it happens to use fewer comparisons, so compare the total.
+        let mut tournament_total_count = 0;
+        let mut heap_total_count = 0;
+        
+        for _i in 0..100 {
+            let arr_num = rng.gen_range(2..1000);
+            let max = rng.gen_range(1..100);
+            
+            // (key, tag), only key is compared. the tag is used for checking stability
+            let mut tag = 0;
+            let arrs: Vec<Vec<(i32, usize)>> = (0..arr_num).map(|_| {
+                let len = rng.gen_range(0..20);
+                let mut arr: Vec<(i32, usize)> = (0..len).map(|_| {
+                    tag += 1;
+                    (rng.gen_range(0..max), tag)
+                }).collect();
+                arr.sort_by(|a, b| a.0.cmp(&b.0));
+                arr
+            }).collect();
+            let arr_refs: Vec<&[(i32, usize)]> = arrs.iter().map(|arr| arr.as_slice()).collect();
+            
+            let tournament_count = Cell::new(0);
+            let mut tournament_result: Vec<(i32, usize)> = Vec::new();
+            merge_tournament(
+                &arr_refs,
+                &|a: &(i32, usize), b: &(i32, usize)| {
+                    tournament_count.set(tournament_count.get() + 1);
+                    a.0.cmp(&b.0)
+                },
+                &mut |index, element| {
+                    assert_eq!(index, tournament_result.len());
+                    tournament_result.push(*element);
+                },
+            );
+            
+            let heap_count = Cell::new(0);
+            let mut heap_result: Vec<(i32, usize)> = Vec::new();
+            merge_multiple_sorted_sequences_smart(
+                &arr_refs,
+                &|a: &(i32, usize), b: &(i32, usize)| {
+                    heap_count.set(heap_count.get() + 1);
+                    a.0.cmp(&b.0)
+                },
+                &mut |_index, element| {
+                    heap_result.push(*element);
+                },
+            );
+            
+            // both are stable, so the result should be exactly the same
+            assert_eq!(tournament_result, heap_result);
+            
+            tournament_total_count += tournament_count.get();
+            heap_total_count += heap_count.get();
+        }
+        
+        assert!(
+            tournament_total_count <= heap_total_count,
+            "tournament merge used {} comparisons, heap merge used {}",
+            tournament_total_count, heap_total_count
+        );
+    }
+    
+    #[test]
+    fn test_merge_tournament_few_arrays() {
+        let arr1 = [1, 3, 5];
+        let arr2: [i32; 0] = [];
+        
+        let mut result: Vec<i32> = Vec::new();
+        merge_tournament(&[&arr1[..]], &|a: &i32, b: &i32| a.cmp(b), &mut |_index, e| result.push(*e));
+        assert_eq!(result, vec![1, 3, 5]);
+        
+        let mut result: Vec<i32> = Vec::new();
+        merge_tournament(&[&arr2[..], &arr1[..]], &|a: &i32, b: &i32| a.cmp(b), &mut |_index, e| result.push(*e));
+        assert_eq!(result, vec![1, 3, 5]);
+        
+        let mut result: Vec<i32> = Vec::new();
+        merge_tournament::<i32, _, _>(&[], &|a: &i32, b: &i32| a.cmp(b), &mut |_index, e| result.push(*e));
+        assert!(result.is_empty());
+    }
+}
@@ -81,6 +81,13 @@ pub fn container_agnostic_fat_partition<
     // convert to left_part_and_eq_part_size + 1 + right_part_size <= range_size
     
     while left_part_and_eq_part_size + 1 + right_part_size <= range_size {
+        // if range_size is wrong, the size-based termination doesn't match the index-based termination.
+        // for linked list, it will then walk out of the range, or corrupt the processed regions.
+        debug_assert!(
+            eq_index != container.next_index(right_index.clone()),
+            "range_size {} is larger than the actual size of the range", range_size
+        );
+        
         if curr_pivot_index == eq_index {
             // no need to compare arr[eq_index] with arr[pivot_index] now, treat it as equal
             eq_index = container.next_index(eq_index);
@@ -128,7 +135,15 @@ pub fn container_agnostic_fat_partition<
         }
     }
     
+    debug_assert!(
+        eq_index == container.next_index(right_index.clone()),
+        "range_size {} is smaller than the actual size of the range", range_size
+    );
     assert!(eq_index == container.next_index(right_index.clone()));
+    debug_assert_eq!(
+        left_part_and_eq_part_size + right_part_size, range_size,
+        "the tracked sizes are inconsistent with range_size"
+    );
     
     let r_left = left_index;
     let r_right = container.next_index(right_index);
@@ -300,6 +315,100 @@ impl<Element> QuickSortableContainer<Element> for MyLinkedList<Element> {
     }
 }
 
+impl<Element> MyLinkedList<Element> {
+    // quick sort the whole linked list.
+    // it's a shortcut of calling container_agnostic_quick_sort with the whole range.
+    pub fn sort_by<Comparator>(&mut self, comparator: &Comparator)
+        where
+            Comparator: Fn(&Element, &Element) -> Ordering
+    {
+        let begin_cursor = match self.begin() {
+            None => return,
+            Some(begin_cursor) => begin_cursor,
+        };
+        let len = self.size();
+        container_agnostic_quick_sort(
+            self, comparator,
+            LinkedListIndex::Cursor(begin_cursor), LinkedListIndex::AfterLast, len,
+        );
+    }
+    
+    pub fn sort(&mut self)
+        where
+            Element: Ord
+    {
+        self.sort_by(&|a: &Element, b: &Element| a.cmp(b));
+    }
+}
+
+// Fixed-size records stored back to back in a flat byte buffer, like a memory-mapped file of records.
+// The index is the byte offset of the record, and the slot after the last record is the buffer length.
+// Swapping moves the bytes of two records, so it doesn't need to know the record layout.
+pub struct FixedSizeRecords<const RECORD_SIZE: usize> {
+    bytes: Vec<u8>,
+}
+
+impl<const RECORD_SIZE: usize> FixedSizeRecords<RECORD_SIZE> {
+    pub fn from_bytes(bytes: Vec<u8>) -> Self {
+        assert!(RECORD_SIZE > 0);
+        assert_eq!(bytes.len() % RECORD_SIZE, 0, "the buffer is not a whole number of records");
+        FixedSizeRecords { bytes }
+    }
+    
+    pub fn into_bytes(self) -> Vec<u8> {
+        self.bytes
+    }
+    
+    pub fn len(&self) -> usize {
+        self.bytes.len() / RECORD_SIZE
+    }
+    
+    pub fn sort_by<Comparator>(&mut self, comparator: &Comparator)
+        where
+            Comparator: Fn(&[u8; RECORD_SIZE], &[u8; RECORD_SIZE]) -> Ordering
+    {
+        let len = self.len();
+        let end = self.bytes.len();
+        container_agnostic_quick_sort(self, comparator, 0, end, len);
+    }
+}
+
+impl<const RECORD_SIZE: usize> QuickSortableContainer<[u8; RECORD_SIZE]> for FixedSizeRecords<RECORD_SIZE> {
+    type Index = usize;
+    
+    fn swap(&mut self, a: usize, b: usize) {
+        if a == b {
+            return;
+        }
+        let (low, high) = (a.min(b), a.max(b));
+        let (left, right) = self.bytes.split_at_mut(high);
+        left[low..(low + RECORD_SIZE)].swap_with_slice(&mut right[..RECORD_SIZE]);
+    }
+    
+    fn get(&self, index: usize) -> &[u8; RECORD_SIZE] {
+        self.bytes[index..(index + RECORD_SIZE)].try_into().unwrap()
+    }
+    
+    fn next_index(&self, index: usize) -> usize {
+        index + RECORD_SIZE
+    }
+    
+    fn prev_index(&self, index: usize) -> usize {
+        index - RECORD_SIZE
+    }
+    
+    fn select_pivot_index<
+        Comparator: Fn(&[u8; RECORD_SIZE], &[u8; RECORD_SIZE]) -> Ordering
+    >(
+        &self, range_begin: usize, range_end_exclusive: usize,
+        _comparator: &Comparator,
+    ) -> usize {
+        // the middle record. the offset must be at a record boundary
+        let record_count = (range_end_exclusive - range_begin) / RECORD_SIZE;
+        range_begin + (record_count / 2) * RECORD_SIZE
+    }
+}
+
 //noinspection DuplicatedCode
 #[cfg(test)]
 mod tests {
@@ -347,6 +456,100 @@ mod tests {
         }
     }
     
+    #[test]
+    fn test_linked_list_sort() {
+        let mut rng = create_rng();
+        
+        for _i in 0..50 {
+            let mut vec_ref = random_vec(&mut rng);
+            let mut list = to_linked_list(&vec_ref);
+            
+            list.sort();
+            vec_ref.sort();
+            
+            let list_converted_to_vec: Vec<i32> = list.iter().copied().collect();
+            assert_eq!(list_converted_to_vec, vec_ref);
+        }
+        
+        for _i in 0..50 {
+            let mut vec_ref = random_vec(&mut rng);
+            let mut list = to_linked_list(&vec_ref);
+            
+            list.sort_by(&|a: &i32, b: &i32| b.cmp(a));
+            vec_ref.sort_by(|a, b| b.cmp(a));
+            
+            let list_converted_to_vec: Vec<i32> = list.iter().copied().collect();
+            assert_eq!(list_converted_to_vec, vec_ref);
+        }
+        
+        let mut empty: MyLinkedList<i32> = MyLinkedList::new();
+        empty.sort();
+        assert_eq!(empty.size(), 0);
+    }
+    
+    #[test]
+    fn test_fixed_size_records_sort() {
+        let mut rng = create_rng();
+        
+        // a record is a 4-byte little-endian key followed by 8 bytes of payload
+        let compare_key = |a: &[u8; 12], b: &[u8; 12]| {
+            u32::from_le_bytes(a[..4].try_into().unwrap()).cmp(&u32::from_le_bytes(b[..4].try_into().unwrap()))
+        };
+        
+        for _i in 0..100 {
+            let records: Vec<[u8; 12]> = random_vec(&mut rng).into_iter().map(|key| {
+                let mut record = [0u8; 12];
+                record[..4].copy_from_slice(&(key as u32).to_le_bytes());
+                rng.fill(&mut record[4..]);
+                record
+            }).collect();
+            
+            let mut sorted = FixedSizeRecords::<12>::from_bytes(records.concat());
+            assert_eq!(sorted.len(), records.len());
+            sorted.sort_by(&compare_key);
+            let sorted_bytes = sorted.into_bytes();
+            let sorted_records: Vec<[u8; 12]> = sorted_bytes.chunks(12).map(|chunk| chunk.try_into().unwrap()).collect();
+            
+            // the payloads move together with the keys, so it's a permutation of the records
+            assert!(sorted_records.windows(2).all(|pair| compare_key(&pair[0], &pair[1]).is_le()));
+            let mut sorted_all = sorted_records.clone();
+            sorted_all.sort();
+            let mut records_ref = records.clone();
+            records_ref.sort();
+            assert_eq!(sorted_all, records_ref);
+        }
+        
+        let mut empty = FixedSizeRecords::<12>::from_bytes(Vec::new());
+        empty.sort_by(&compare_key);
+        assert_eq!(empty.len(), 0);
+    }
+    
+    #[test]
+    #[cfg(debug_assertions)]
+    #[should_panic(expected = "is larger than the actual size of the range")]
+    fn test_container_agnostic_fat_partition_range_size_too_large() {
+        let mut list = to_linked_list(&vec![3, 1, 4, 1, 5, 9, 2, 6]);
+        let begin = LinkedListIndex::Cursor(list.begin().unwrap());
+        
+        container_agnostic_fat_partition(
+            &mut list, &|a: &i32, b: &i32| a.cmp(b),
+            begin, LinkedListIndex::AfterLast, begin, 10,
+        );
+    }
+    
+    #[test]
+    #[cfg(debug_assertions)]
+    #[should_panic(expected = "is smaller than the actual size of the range")]
+    fn test_container_agnostic_fat_partition_range_size_too_small() {
+        let mut list = to_linked_list(&vec![3, 1, 4, 1, 5, 9, 2, 6]);
+        let begin = LinkedListIndex::Cursor(list.begin().unwrap());
+        
+        container_agnostic_fat_partition(
+            &mut list, &|a: &i32, b: &i32| a.cmp(b),
+            begin, LinkedListIndex::AfterLast, begin, 5,
+        );
+    }
+    
     fn create_rng() -> StdRng {
         let seed: [u8; 32] = [
             1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11, 12, 13, 14, 15, 16, 17, 18, 19, 20, 21, 22, 23, 24,
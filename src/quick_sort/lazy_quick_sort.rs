@@ -65,6 +65,7 @@ impl<'a, Element, Comparator> LazyQuickSorter<'a, Element, Comparator>
     }
     
     // Get the index + 1 th smallest element in the array.
+    // "Smallest" is by the comparator. Passing a reversed comparator makes it the index + 1 th largest.
     // Average time complexity is O(n) where n is list size.
     pub fn at(&mut self, index: usize) -> &Element {
         LazyQuickSorter::ensure_sorted(
@@ -78,6 +79,114 @@ impl<'a, Element, Comparator> LazyQuickSorter<'a, Element, Comparator>
         return &self.arr[index];
     }
     
+    // Sort the whole array, completing the remaining partitions.
+    // The partitions that are already done will not be redone.
+    pub fn sort_all(&mut self) {
+        LazyQuickSorter::ensure_fully_sorted(
+            &mut self.root_node,
+            0,
+            self.arr.len(),
+            self.arr,
+            self.comparator,
+        );
+    }
+    
+    // The depth of the partition tree. Each partitioned layer adds one.
+    // It's 0 if nothing is partitioned yet, or the whole array is sorted (the tree is released).
+    pub fn partition_tree_depth(&self) -> usize {
+        LazyQuickSorter::<Element, Comparator>::node_depth(&self.root_node)
+    }
+    
+    // The fraction of elements that are already at their sorted position,
+    // including the fully sorted ranges and the "equal" regions of the partitions.
+    pub fn sorted_fraction(&self) -> f64 {
+        let len = self.arr.len();
+        if len == 0 {
+            return 1.0;
+        }
+        
+        let sorted_count = LazyQuickSorter::<Element, Comparator>::node_sorted_count(
+            &self.root_node, 0, len,
+        );
+        sorted_count as f64 / len as f64
+    }
+    
+    fn node_depth(node: &NodeState) -> usize {
+        match node {
+            NodeState::PartiallySorted(partial_sort_data) => {
+                1 + LazyQuickSorter::<Element, Comparator>::node_depth(&partial_sort_data.left_child)
+                    .max(LazyQuickSorter::<Element, Comparator>::node_depth(&partial_sort_data.right_child))
+            }
+            NodeState::Unsorted | NodeState::FullySorted => 0,
+        }
+    }
+    
+    fn node_sorted_count(node: &NodeState, range_left: usize, range_right_exclusive: usize) -> usize {
+        let len = range_right_exclusive - range_left;
+        match node {
+            // a range with at most one element is sorted, even if not visited
+            NodeState::Unsorted => if len <= 1 { len } else { 0 },
+            NodeState::FullySorted => len,
+            NodeState::PartiallySorted(partial_sort_data) => {
+                let equal_region_len = partial_sort_data.partition_right - partial_sort_data.partition_left;
+                let left_sorted_count = LazyQuickSorter::<Element, Comparator>::node_sorted_count(
+                    &partial_sort_data.left_child, range_left, partial_sort_data.partition_left,
+                );
+                let right_sorted_count = LazyQuickSorter::<Element, Comparator>::node_sorted_count(
+                    &partial_sort_data.right_child, partial_sort_data.partition_right, range_right_exclusive,
+                );
+                equal_region_len + left_sorted_count + right_sorted_count
+            }
+        }
+    }
+    
+    // Ensure that the whole range is sorted, and mark the node as fully sorted.
+    fn ensure_fully_sorted(
+        node: &mut NodeState,
+        range_left: usize,
+        range_right_exclusive: usize,
+        arr: &mut [Element],
+        comparator: &Comparator,
+    ) {
+        // the child range of a partition could be empty
+        if range_right_exclusive - range_left <= 1 {
+            *node = NodeState::FullySorted;
+            return;
+        }
+        
+        if let NodeState::Unsorted = node {
+            // partition it first. it will also sort the first element of the range,
+            // which is part of sorting the whole range anyway.
+            LazyQuickSorter::ensure_sorted(
+                node,
+                range_left,
+                range_left,
+                range_right_exclusive,
+                arr,
+                comparator,
+            );
+        }
+        
+        if let NodeState::PartiallySorted(ref mut partial_sort_data) = node {
+            LazyQuickSorter::ensure_fully_sorted(
+                &mut partial_sort_data.left_child,
+                range_left,
+                partial_sort_data.partition_left,
+                arr,
+                comparator,
+            );
+            LazyQuickSorter::ensure_fully_sorted(
+                &mut partial_sort_data.right_child,
+                partial_sort_data.partition_right,
+                range_right_exclusive,
+                arr,
+                comparator,
+            );
+        }
+        
+        *node = NodeState::FullySorted;
+    }
+    
     // Ensure that the element at the target_index is sorted, in the context of a range.
     // Each range correspond to a node in the tree.
     fn ensure_sorted(
@@ -241,6 +350,108 @@ mod tests {
         test_lazy_quick_sort_for(&vec, &mut rng);
     }
     
+    #[test]
+    fn test_lazy_quick_sort_sort_all() {
+        let mut rng = create_rng();
+        
+        for _i in 0..100 {
+            let size = rng.gen_range(0..1000);
+            let max = rng.gen_range(1..2000);
+            let mut vec: Vec<i32> = (0..size).map(|_| rng.gen_range(0..max)).collect();
+            let mut vec_for_ref = vec.clone();
+            vec_for_ref.sort();
+            
+            let query_num = rng.gen_range(0..10);
+            
+            let mut s = LazyQuickSorter::new(vec.as_mut_slice(), &|x: &i32, y: &i32| x.cmp(y));
+            
+            // partially sort it first
+            if size > 0 {
+                for _j in 0..query_num {
+                    let index = rng.gen_range(0..size);
+                    assert_eq!(*s.at(index), vec_for_ref[index]);
+                }
+            }
+            
+            s.sort_all();
+            
+            // it's still usable after sorting all
+            if size > 0 {
+                assert_eq!(*s.at(size - 1), vec_for_ref[size - 1]);
+            }
+            
+            assert_eq!(vec, vec_for_ref);
+        }
+    }
+    
+    #[test]
+    fn test_lazy_quick_sort_both_orderings() {
+        let mut rng = create_rng();
+        
+        for _i in 0..100 {
+            // duplicate-heavy
+            let size = rng.gen_range(1..1000);
+            let max = rng.gen_range(1..5);
+            let vec: Vec<i32> = (0..size).map(|_| rng.gen_range(0..max)).collect();
+            
+            let mut ascending_ref = vec.clone();
+            ascending_ref.sort();
+            let mut descending_ref = vec.clone();
+            descending_ref.sort_by(|a, b| b.cmp(a));
+            
+            let mut ascending = vec.clone();
+            let mut s = LazyQuickSorter::new(ascending.as_mut_slice(), &|x: &i32, y: &i32| x.cmp(y));
+            assert_eq!(*s.at(0), *vec.iter().min().unwrap());
+            for _j in 0..10 {
+                let index = rng.gen_range(0..size);
+                assert_eq!(*s.at(index), ascending_ref[index]);
+            }
+            
+            let mut descending = vec.clone();
+            let mut s = LazyQuickSorter::new(descending.as_mut_slice(), &|x: &i32, y: &i32| y.cmp(x));
+            assert_eq!(*s.at(0), *vec.iter().max().unwrap());
+            for _j in 0..10 {
+                let index = rng.gen_range(0..size);
+                assert_eq!(*s.at(index), descending_ref[index]);
+            }
+            s.sort_all();
+            assert_eq!(descending, descending_ref);
+        }
+    }
+    
+    #[test]
+    fn test_lazy_quick_sort_statistics() {
+        let mut rng = create_rng();
+        
+        let size = 100000;
+        let mut vec: Vec<i32> = (0..size).map(|_| rng.gen_range(0..1000000)).collect();
+        
+        let mut s = LazyQuickSorter::new(vec.as_mut_slice(), &|x: &i32, y: &i32| x.cmp(y));
+        assert_eq!(s.partition_tree_depth(), 0);
+        assert_eq!(s.sorted_fraction(), 0.0);
+        
+        s.at(0);
+        s.at(size - 1);
+        
+        let depth_after_two_queries = s.partition_tree_depth();
+        let fraction_after_two_queries = s.sorted_fraction();
+        assert!(depth_after_two_queries > 0);
+        assert!(fraction_after_two_queries > 0.0);
+        assert!(fraction_after_two_queries < 0.01);
+        
+        for _i in 0..1000 {
+            s.at(rng.gen_range(0..size));
+        }
+        
+        let fraction_after_more_queries = s.sorted_fraction();
+        assert!(fraction_after_more_queries > fraction_after_two_queries);
+        assert!(fraction_after_more_queries < 1.0);
+        
+        s.sort_all();
+        assert_eq!(s.sorted_fraction(), 1.0);
+        assert_eq!(s.partition_tree_depth(), 0);
+    }
+    
     fn create_rng() -> StdRng {
         let seed: [u8; 32] = [
             1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11, 12, 13, 14, 15, 16, 17, 18, 19, 20, 21, 22, 23, 24,
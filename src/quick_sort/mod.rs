@@ -1,6 +1,9 @@
 pub mod simple_quick_sort;
 pub mod lazy_quick_sort;
-mod partition;
-mod pivot_select;
+pub mod partition;
+pub mod pivot_select;
 mod container_agnostic_quick_sort;
 mod stable_quick_sort;
+mod parallel_partition;
+pub mod parallel_bucket_sort;
+pub mod parallel_quick_sort;
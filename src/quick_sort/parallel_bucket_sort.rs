@@ -0,0 +1,128 @@
+use std::cmp::Ordering;
+use std::sync::atomic::AtomicBool;
+
+use crate::merge_sort::concurrent_merge_sort::RangePartition;
+use crate::quick_sort::simple_quick_sort::normal_quick_sort_cancellable;
+use crate::select::quick_select::quantile_buckets_cancellable;
+use crate::util::cancellation::{Cancelled, without_cancellation};
+
+// A parallel sort without merging.
+// It first splits the array into parallelism quantile buckets by quick select, sequentially,
+// then each bucket is sorted by a thread. Every element in a bucket is <= every element in the next bucket,
+// so the sorted buckets together are sorted.
+// The buckets are split by position (quantile), not by value ranges,
+// so their sizes differ by at most 1 even if the data is skewed, and the threads have balanced work.
+// The quick select and quick sort are not stable, so it's not stable.
+pub fn parallel_bucket_sort<Element, Comparator>(
+    arr: &mut [Element], compare: &Comparator, parallelism: usize,
+)
+    where
+        Element: Send,
+        Comparator: Fn(&Element, &Element) -> Ordering + Sync
+{
+    without_cancellation(|cancel| parallel_bucket_sort_cancellable(arr, compare, parallelism, cancel));
+}
+
+// Same as parallel_bucket_sort, but the selections and the bucket sorts check the cancel flag,
+// so it stops before spawning the threads if it's already set. It returns Err(Cancelled) if it's set.
+pub fn parallel_bucket_sort_cancellable<Element, Comparator>(
+    arr: &mut [Element], compare: &Comparator, parallelism: usize, cancel: &AtomicBool,
+) -> Result<(), Cancelled>
+    where
+        Element: Send,
+        Comparator: Fn(&Element, &Element) -> Ordering + Sync
+{
+    assert!(parallelism > 0);
+    
+    if parallelism == 1 || arr.len() <= 1 {
+        return normal_quick_sort_cancellable(arr, compare, cancel);
+    }
+    
+    let buckets = quantile_buckets_cancellable(arr, parallelism, compare, cancel)?;
+    
+    let mut endpoints: Vec<usize> = buckets.iter().map(|bucket| bucket.start).collect();
+    endpoints.push(arr.len());
+    let partition = RangePartition::from_endpoints(endpoints);
+    
+    let results: Vec<Result<(), Cancelled>> = crossbeam::thread::scope(|s| {
+        let handles: Vec<_> = partition.split_borrow(arr).into_iter().map(|bucket| {
+            s.spawn(move |_| normal_quick_sort_cancellable(bucket, compare, cancel))
+        }).collect();
+        
+        handles.into_iter().map(|handle| handle.join().unwrap()).collect()
+    }).unwrap();
+    
+    results.into_iter().collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::{Rng, SeedableRng};
+    use rand::prelude::StdRng;
+    
+    use crate::quick_sort::partition::PARTITION_CANCEL_CHECK_INTERVAL;
+    use crate::util::test_util::{assert_same_order, check_cancellable_sort};
+    
+    use super::*;
+    
+    #[test]
+    fn test_parallel_bucket_sort() {
+        let mut rng: StdRng = SeedableRng::seed_from_u64(123456);
+        
+        for parallelism in [1, 2, 3, 4, 8, 16] {
+            for _i in 0..30 {
+                let len = rng.gen_range(0..10000);
+                let max = rng.gen_range(1..10000);
+                let mut vec: Vec<i32> = (0..len).map(|_| rng.gen_range(0..max)).collect();
+                let mut vec_ref = vec.clone();
+                
+                parallel_bucket_sort(&mut vec, &|a: &i32, b: &i32| a.cmp(b), parallelism);
+                vec_ref.sort();
+                
+                assert_same_order(&vec, &vec_ref);
+            }
+        }
+        
+        // fewer elements than threads, some buckets are empty
+        for len in 0..20 {
+            let mut vec: Vec<i32> = (0..len).rev().collect();
+            parallel_bucket_sort(&mut vec, &|a: &i32, b: &i32| a.cmp(b), 8);
+            assert_same_order(&vec, &(0..len).collect::<Vec<i32>>());
+        }
+    }
+    
+    // splitting by value range would put most elements in one bucket,
+    // but quantile buckets still split them evenly
+    #[test]
+    fn test_parallel_bucket_sort_skewed() {
+        let mut rng: StdRng = SeedableRng::seed_from_u64(123456);
+        
+        for _i in 0..30 {
+            let len = rng.gen_range(0..20000);
+            let parallelism = rng.gen_range(2..16);
+            
+            // mostly a few small values, with a long tail of large values
+            let mut vec: Vec<u64> = (0..len).map(|_| {
+                if rng.gen_bool(0.9) {
+                    rng.gen_range(0..3)
+                } else {
+                    1u64 << rng.gen_range(0..60)
+                }
+            }).collect();
+            let mut vec_ref = vec.clone();
+            
+            parallel_bucket_sort(&mut vec, &|a: &u64, b: &u64| a.cmp(b), parallelism);
+            vec_ref.sort();
+            
+            assert_same_order(&vec, &vec_ref);
+        }
+    }
+    
+    #[test]
+    fn test_parallel_bucket_sort_cancellable() {
+        // the flag is set during the quick select, before spawning
+        check_cancellable_sort("parallel_bucket_sort", PARTITION_CANCEL_CHECK_INTERVAL, |arr, compare, cancel| {
+            parallel_bucket_sort_cancellable(arr, &compare, 4, cancel)
+        });
+    }
+}
@@ -0,0 +1,253 @@
+use std::cmp::Ordering;
+use std::ops::Range;
+use std::ptr;
+
+use crate::merge_sort::concurrent_merge_sort::{RangePartition, SendablePtrWrapper};
+
+// Parallel partition.
+// for return value p, it ensures arr[0..p] <= pivot, arr[p - 1] == pivot (the pivot itself) and arr[p..] > pivot
+// so 0 < p <= arr.len()
+// steps:
+// - move the pivot to the front, the remaining elements are to be partitioned.
+// - separate the remaining elements into M blocks, each thread partitions its own block concurrently.
+//   after that, each block is "<= pivot" elements followed by "> pivot" elements.
+// - the total number of "<= pivot" elements is known, so the partition point is known.
+//   the "> pivot" elements on the left of partition point are misplaced,
+//   the "<= pivot" elements on the right of partition point are also misplaced,
+//   and their numbers are equal. swap them pairwise concurrently.
+// - move the pivot to the partition point.
+// Note: the pivot will get moved
+pub fn parallel_partition<Element, Comparator>(
+    arr: &mut [Element],
+    compare: &Comparator,
+    pivot_index: usize,
+    parallelism: usize,
+) -> usize
+    where
+        Element: Send + Sync,
+        Comparator: Fn(&Element, &Element) -> Ordering + Sync
+{
+    assert!(parallelism > 0);
+    assert!(pivot_index < arr.len());
+    
+    arr.swap(0, pivot_index);
+    
+    let (pivot_slot, rest) = arr.split_at_mut(1);
+    let pivot: &Element = &pivot_slot[0];
+    let rest_len = rest.len();
+    
+    // don't create empty blocks
+    let parallelism = parallelism.min(rest_len).max(1);
+    
+    let blocks = RangePartition::evenly_partition(0..rest_len, parallelism);
+    
+    // partition each block concurrently.
+    // small_counts[i] is the number of "<= pivot" elements in block i
+    let small_counts: Vec<usize> = crossbeam::thread::scope(|s| {
+        let handles: Vec<_> = blocks.split_borrow(rest).into_iter().map(|block| {
+            s.spawn(move |_| partition_block(block, compare, pivot))
+        }).collect();
+        
+        handles.into_iter().map(|handle| handle.join().unwrap()).collect()
+    }).unwrap();
+    
+    let split_point: usize = small_counts.iter().sum();
+    
+    // find the misplaced ranges
+    let mut misplaced_large: Vec<Range<usize>> = Vec::new();
+    let mut misplaced_small: Vec<Range<usize>> = Vec::new();
+    for (block_index, small_count) in small_counts.iter().enumerate() {
+        let block = blocks.part_at(block_index);
+        let block_mid = block.start + small_count;
+        
+        // "> pivot" elements on the left of split point
+        let large_start = block_mid;
+        let large_end = block.end.min(split_point);
+        if large_start < large_end {
+            misplaced_large.push(large_start..large_end);
+        }
+        
+        // "<= pivot" elements on the right of split point
+        let small_start = block.start.max(split_point);
+        let small_end = block_mid;
+        if small_start < small_end {
+            misplaced_small.push(small_start..small_end);
+        }
+    }
+    
+    let misplaced_num: usize = misplaced_large.iter().map(|r| r.len()).sum();
+    assert_eq!(misplaced_num, misplaced_small.iter().map(|r| r.len()).sum());
+    
+    // swap the misplaced elements pairwise concurrently.
+    // thread k handles the k-th part of the misplaced element pairs.
+    // the misplaced large elements are all on the left of split point,
+    // and the misplaced small elements are all on the right of split point,
+    // so different threads never touch the same element.
+    let swap_partition = RangePartition::evenly_partition(0..misplaced_num, parallelism);
+    let rest_ptr = SendablePtrWrapper::new(rest.as_mut_ptr());
+    let misplaced_large_ref = &misplaced_large; // this should not be inlined
+    let misplaced_small_ref = &misplaced_small; // this should not be inlined
+    crossbeam::thread::scope(|s| {
+        for thread_index in 0..parallelism {
+            let pair_range = swap_partition.part_at(thread_index);
+            
+            s.spawn(move |_| {
+                let large_indexes = indexes_in_concatenated_ranges(misplaced_large_ref, pair_range.clone());
+                let small_indexes = indexes_in_concatenated_ranges(misplaced_small_ref, pair_range);
+                
+                for (large_index, small_index) in large_indexes.zip(small_indexes) {
+                    unsafe {
+                        ptr::swap(
+                            rest_ptr.as_mut_ptr().add(large_index),
+                            rest_ptr.as_mut_ptr().add(small_index),
+                        );
+                    }
+                }
+            });
+        }
+    }).unwrap();
+    
+    // now arr[1..(split_point + 1)] <= pivot, arr[(split_point + 1)..] > pivot
+    // move the pivot to the end of the left part
+    arr.swap(0, split_point);
+    
+    split_point + 1
+}
+
+// Treat the ranges as concatenated into one sequence of indexes, and take the part in sub_range of that sequence.
+// Each range is clipped directly, so it doesn't walk through the indexes before sub_range.
+fn indexes_in_concatenated_ranges(
+    ranges: &[Range<usize>], sub_range: Range<usize>,
+) -> impl Iterator<Item=usize> + '_ {
+    ranges.iter()
+        .scan(0, |offset, range| {
+            // the position of range.start in the concatenated sequence
+            let range_offset = *offset;
+            *offset += range.len();
+            Some((range_offset, range))
+        })
+        .flat_map(move |(range_offset, range)| {
+            let from = sub_range.start.clamp(range_offset, range_offset + range.len());
+            let to = sub_range.end.clamp(range_offset, range_offset + range.len());
+            (range.start + from - range_offset)..(range.start + to - range_offset)
+        })
+}
+
+// the sequential partition used for each block.
+// for return value r, it ensures block[0..r] <= pivot, block[r..] > pivot
+fn partition_block<Element, Comparator>(
+    block: &mut [Element],
+    compare: &Comparator,
+    pivot: &Element,
+) -> usize
+    where
+        Comparator: Fn(&Element, &Element) -> Ordering
+{
+    // block[0..left_index] <= pivot, block[left_index..j] > pivot
+    let mut left_index = 0;
+    for j in 0..block.len() {
+        if compare(&block[j], pivot) != Ordering::Greater {
+            block.swap(left_index, j);
+            left_index += 1;
+        }
+    }
+    left_index
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::{Rng, SeedableRng};
+    use rand::prelude::StdRng;
+    
+    use crate::quick_sort::partition::fat_partition_no_clone_required;
+    use crate::util::test_util::{assert_same_elements, verify_partition_fat, verify_partition_hoare};
+    
+    use super::*;
+    
+    fn random_vec(rng: &mut StdRng) -> Vec<i32> {
+        let size = rng.gen_range(1..10000);
+        let max = rng.gen_range(1..500);
+        (0..size).map(|_| rng.gen_range(0..max)).collect()
+    }
+    
+    fn validate_partition_result(original: &[i32], vec: &[i32], pivot: i32, p: usize) {
+        assert!(p > 0, "the left part is empty");
+        assert!(p <= vec.len());
+        assert_eq!(vec[p - 1], pivot, "the pivot is not at the end of left part");
+        
+        assert!(vec[0..p].iter().all(|x| *x <= pivot));
+        assert!(vec[p..].iter().all(|x| *x > pivot));
+        
+        assert_same_elements(original, vec);
+    }
+    
+    #[test]
+    fn test_parallel_partition() {
+        let mut rng: StdRng = SeedableRng::seed_from_u64(123456);
+        
+        for i in 0..300 {
+            let mut vec = random_vec(&mut rng);
+            let original = vec.clone();
+            let parallelism = rng.gen_range(1..16);
+            
+            let pivot_index: usize =
+                if (0..10).contains(&i) {
+                    // chose the minimum element as pivot
+                    vec.iter().enumerate().min_by_key(|(_idx, ele)| *ele).unwrap().0
+                } else if (10..20).contains(&i) {
+                    // chose the maximum element as pivot
+                    vec.iter().enumerate().max_by_key(|(_idx, ele)| *ele).unwrap().0
+                } else {
+                    // choose random element as pivot
+                    rng.gen_range(0..vec.len())
+                };
+            let pivot = vec[pivot_index];
+            
+            let p = parallel_partition(vec.as_mut_slice(), &|x: &i32, y: &i32| x.cmp(y), pivot_index, parallelism);
+            
+            validate_partition_result(&original, &vec, pivot, p);
+        }
+    }
+    
+    #[test]
+    fn test_parallel_partition_single_thread() {
+        let mut rng: StdRng = SeedableRng::seed_from_u64(123456);
+        
+        for _i in 0..300 {
+            let mut vec = random_vec(&mut rng);
+            let original = vec.clone();
+            let pivot_index = rng.gen_range(0..vec.len());
+            let pivot = vec[pivot_index];
+            
+            let p = parallel_partition(vec.as_mut_slice(), &|x: &i32, y: &i32| x.cmp(y), pivot_index, 1);
+            
+            validate_partition_result(&original, &vec, pivot, p);
+            
+            // the sequential fat partition with the same pivot puts the same elements on the left
+            let compare = |x: &i32, y: &i32| x.cmp(y);
+            let mut expected = original.clone();
+            let (l, r) = fat_partition_no_clone_required(&mut expected, &compare, pivot_index);
+            verify_partition_fat(&expected, &compare, l, r);
+            
+            assert_eq!(p, r);
+            if p < vec.len() {
+                verify_partition_hoare(&vec, &compare, p);
+            }
+            assert_same_elements(&vec[..p], &expected[..r]);
+        }
+    }
+    
+    #[test]
+    fn test_indexes_in_concatenated_ranges() {
+        let ranges = vec![2..5, 7..7, 10..14];
+        let all: Vec<usize> = indexes_in_concatenated_ranges(&ranges, 0..7).collect();
+        assert_eq!(all, vec![2, 3, 4, 10, 11, 12, 13]);
+        
+        for start in 0..=7 {
+            for end in start..=7 {
+                let part: Vec<usize> = indexes_in_concatenated_ranges(&ranges, start..end).collect();
+                assert_eq!(part, all[start..end]);
+            }
+        }
+    }
+}
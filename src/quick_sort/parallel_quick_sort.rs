@@ -0,0 +1,229 @@
+use std::cmp::Ordering;
+use std::sync::atomic::AtomicBool;
+
+use crate::merge_sort::concurrent_merge_sort::default_parallelism;
+use crate::quick_sort::partition::fat_partition_cancellable;
+use crate::quick_sort::pivot_select::median_of_three_pivot;
+use crate::quick_sort::simple_quick_sort::intro_sort_cancellable;
+use crate::util::cancellation::{Cancelled, check_cancelled, without_cancellation};
+
+// below this size, spawning a thread costs more than sorting it in the current thread
+pub const PARALLEL_QUICK_SORT_CUTOFF: usize = 10000;
+
+// the spawning depth beyond log2 of the parallelism. a few more levels let the threads of the larger parts
+// still spawn when the partitions are not exactly even.
+const EXTRA_SPAWN_DEPTH: usize = 3;
+
+// when the smaller side of a partition is shorter than 1/UNBALANCED_RATIO of the range,
+// the pivot is bad (possibly by adversarial input), and it stops spawning for the range.
+const UNBALANCED_RATIO: usize = 16;
+
+// Parallel quick sort. After the fat partition, the left and right parts are independent,
+// so the left part is sorted by a new thread, while the current thread sorts the right part.
+// The equal region is already in place, so many duplicates make both parts smaller.
+// Unlike concurrent_merge_sort, it doesn't need temporary buffers, but it's not stable.
+// The partition of the whole array is done by one thread, so the parallelism ramps up gradually.
+// Each level of spawning doubles the threads, so the spawning depth is limited to about log2 of the parallelism.
+// Below the limit, or after an unbalanced partition, the range is sorted by intro_sort in the current thread,
+// so the number of threads is bounded, and a killer input is still O(n log n).
+pub fn parallel_quick_sort<Element, Comparator>(
+    arr: &mut [Element], compare: &Comparator,
+)
+    where
+        Element: Send,
+        Comparator: Fn(&Element, &Element) -> Ordering + Sync
+{
+    parallel_quick_sort_with_cutoff(arr, compare, PARALLEL_QUICK_SORT_CUTOFF);
+}
+
+// the sub-slices not longer than cutoff are sorted sequentially by intro_sort.
+pub fn parallel_quick_sort_with_cutoff<Element, Comparator>(
+    arr: &mut [Element], compare: &Comparator, cutoff: usize,
+)
+    where
+        Element: Send,
+        Comparator: Fn(&Element, &Element) -> Ordering + Sync
+{
+    without_cancellation(|cancel| {
+        parallel_quick_sort_with_spawn_depth(arr, compare, cutoff, default_spawn_depth(), cancel)
+    });
+}
+
+// Same as parallel_quick_sort, but it checks the cancel flag before each partition,
+// so it stops partitioning and spawning once the flag is set, and returns Err(Cancelled).
+// Then the array is a permutation of the input, possibly unsorted.
+pub fn parallel_quick_sort_cancellable<Element, Comparator>(
+    arr: &mut [Element], compare: &Comparator, cancel: &AtomicBool,
+) -> Result<(), Cancelled>
+    where
+        Element: Send,
+        Comparator: Fn(&Element, &Element) -> Ordering + Sync
+{
+    parallel_quick_sort_with_spawn_depth(arr, compare, PARALLEL_QUICK_SORT_CUTOFF, default_spawn_depth(), cancel)
+}
+
+fn default_spawn_depth() -> usize {
+    // the ceiling of log2
+    let parallelism = default_parallelism();
+    (usize::BITS - (parallelism - 1).leading_zeros()) as usize + EXTRA_SPAWN_DEPTH
+}
+
+// spawn_depth is the remaining levels that can spawn. With it, at most 2^spawn_depth threads sort at the same time.
+fn parallel_quick_sort_with_spawn_depth<Element, Comparator>(
+    arr: &mut [Element], compare: &Comparator, cutoff: usize, spawn_depth: usize, cancel: &AtomicBool,
+) -> Result<(), Cancelled>
+    where
+        Element: Send,
+        Comparator: Fn(&Element, &Element) -> Ordering + Sync
+{
+    if arr.len() <= cutoff.max(2) || spawn_depth == 0 {
+        return intro_sort_cancellable(arr, compare, cancel);
+    }
+    
+    check_cancelled(cancel)?;
+    
+    let len = arr.len();
+    let pivot_index = median_of_three_pivot(arr, compare);
+    let (l, r) = fat_partition_cancellable(arr, compare, pivot_index, cancel)?;
+    
+    let (left_and_equal, right) = arr.split_at_mut(r);
+    let left = &mut left_and_equal[..l];
+    
+    if left.len().min(right.len()) * UNBALANCED_RATIO < len - (r - l) {
+        intro_sort_cancellable(left, compare, cancel)?;
+        return intro_sort_cancellable(right, compare, cancel);
+    }
+    
+    crossbeam::thread::scope(|s| {
+        let left_handle = s.spawn(|_| {
+            parallel_quick_sort_with_spawn_depth(left, compare, cutoff, spawn_depth - 1, cancel)
+        });
+        let right_result = parallel_quick_sort_with_spawn_depth(right, compare, cutoff, spawn_depth - 1, cancel);
+        left_handle.join().unwrap().and(right_result)
+    }).unwrap()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashSet;
+    use std::sync::atomic::{AtomicUsize, Ordering as AtomicOrdering};
+    use std::sync::Mutex;
+    use std::thread::ThreadId;
+    
+    use rand::{Rng, SeedableRng};
+    use rand::prelude::StdRng;
+    
+    use crate::quick_sort::partition::PARTITION_CANCEL_CHECK_INTERVAL;
+    use crate::quick_sort::simple_quick_sort::normal_quick_sort;
+    use crate::util::test_util::{assert_same_order, check_cancellable_sort, generate_killer_input};
+    
+    use super::*;
+    
+    #[test]
+    fn test_parallel_quick_sort() {
+        let mut rng: StdRng = SeedableRng::seed_from_u64(123456);
+        
+        // each part above the cutoff spawns a thread, so a small cutoff spawns many threads
+        for cutoff in [100, 1000, PARALLEL_QUICK_SORT_CUTOFF] {
+            for _i in 0..20 {
+                let len = rng.gen_range(0..30000);
+                let max = rng.gen_range(1..100000);
+                let mut vec: Vec<i32> = (0..len).map(|_| rng.gen_range(0..max)).collect();
+                let mut vec_ref = vec.clone();
+                
+                parallel_quick_sort_with_cutoff(&mut vec, &|a: &i32, b: &i32| a.cmp(b), cutoff);
+                vec_ref.sort();
+                
+                assert_same_order(&vec, &vec_ref);
+            }
+        }
+        
+        let mut vec: Vec<i32> = (0..100000).rev().collect();
+        parallel_quick_sort(&mut vec, &|a: &i32, b: &i32| a.cmp(b));
+        assert_same_order(&vec, &(0..100000).collect::<Vec<i32>>());
+    }
+    
+    #[test]
+    fn test_parallel_quick_sort_many_equal() {
+        let mut rng: StdRng = SeedableRng::seed_from_u64(123456);
+        
+        for _i in 0..30 {
+            // a few distinct values
+            let len = rng.gen_range(0..50000);
+            let max = rng.gen_range(1..5);
+            let mut vec: Vec<i32> = (0..len).map(|_| rng.gen_range(0..max)).collect();
+            let mut vec_ref = vec.clone();
+            
+            parallel_quick_sort_with_cutoff(&mut vec, &|a: &i32, b: &i32| a.cmp(b), 100);
+            vec_ref.sort();
+            
+            assert_same_order(&vec, &vec_ref);
+        }
+        
+        // all equal. the first partition puts all of them in the equal region, and both sides are empty
+        let count = AtomicUsize::new(0);
+        let counting_compare = |a: &i32, b: &i32| {
+            count.fetch_add(1, AtomicOrdering::Relaxed);
+            a.cmp(b)
+        };
+        let len = 100000;
+        let mut vec: Vec<i32> = vec![7; len];
+        parallel_quick_sort_with_cutoff(&mut vec, &counting_compare, 0);
+        assert!(count.load(AtomicOrdering::Relaxed) < 2 * len, "{}", count.load(AtomicOrdering::Relaxed));
+    }
+    
+    #[test]
+    fn test_parallel_quick_sort_spawn_depth() {
+        let mut rng: StdRng = SeedableRng::seed_from_u64(123456);
+        
+        for spawn_depth in 0..5 {
+            let len = 100000;
+            let mut vec: Vec<i32> = (0..len).map(|_| rng.gen_range(0..1000000)).collect();
+            let mut vec_ref = vec.clone();
+            
+            // the threads that have compared elements
+            let threads: Mutex<HashSet<ThreadId>> = Mutex::new(HashSet::new());
+            let recording_compare = |a: &i32, b: &i32| {
+                threads.lock().unwrap().insert(std::thread::current().id());
+                a.cmp(b)
+            };
+            without_cancellation(|cancel| {
+                parallel_quick_sort_with_spawn_depth(&mut vec, &recording_compare, 100, spawn_depth, cancel)
+            });
+            vec_ref.sort();
+            
+            assert_same_order(&vec, &vec_ref);
+            let thread_count = threads.lock().unwrap().len();
+            assert!(thread_count <= 1 << spawn_depth, "{} threads with spawn depth {}", thread_count, spawn_depth);
+        }
+    }
+    
+    // the top levels use the same pivot selection and partition as normal_quick_sort,
+    // so its killer input makes them unbalanced. it falls back to intro_sort instead of going quadratic.
+    #[test]
+    fn test_parallel_quick_sort_killer_input() {
+        let len: usize = 5000;
+        let n_log_n = len * (usize::BITS - len.leading_zeros()) as usize;
+        let killer = generate_killer_input(len, |arr, compare| normal_quick_sort(arr, &compare));
+        
+        let count = AtomicUsize::new(0);
+        let counting_compare = |a: &usize, b: &usize| {
+            count.fetch_add(1, AtomicOrdering::Relaxed);
+            a.cmp(b)
+        };
+        let mut vec = killer.clone();
+        parallel_quick_sort_with_cutoff(&mut vec, &counting_compare, 0);
+        
+        assert_eq!(vec, (0..len).collect::<Vec<usize>>());
+        let comparisons = count.load(AtomicOrdering::Relaxed);
+        assert!(comparisons < 5 * n_log_n, "{} comparisons on the killer input", comparisons);
+    }
+    
+    #[test]
+    fn test_parallel_quick_sort_cancellable() {
+        // the flag is set during the partition of the whole array, before spawning
+        check_cancellable_sort("parallel_quick_sort", PARTITION_CANCEL_CHECK_INTERVAL, |arr, compare, cancel| {
+            parallel_quick_sort_cancellable(arr, &compare, cancel)
+        });
+    }
+}
@@ -1,5 +1,8 @@
 use std::cmp::Ordering;
 use std::cmp::Ordering::{Greater, Less};
+use std::sync::atomic::AtomicBool;
+
+use crate::util::cancellation::{Cancelled, check_cancelled, without_cancellation};
 
 // Reference: https://en.wikipedia.org/wiki/Quicksort
 // This file contains:
@@ -33,7 +36,7 @@ pub fn lomuto_partition<Element: Clone, Comparator>(
         Element: Clone,
 {
     let len = arr.len();
-    assert!(len > 2);
+    assert!(pivot_index < len);
     
     // move the pivot to the end
     arr.swap(pivot_index, len - 1);
@@ -92,7 +95,9 @@ pub fn hoare_partition<Element: Clone, Comparator>(
         Element: Clone,
 {
     let len = arr.len();
-    assert!(len > 2, "the array should have at least 3 elements");
+    // both parts should not be empty, so it needs at least 2 elements
+    assert!(len >= 2, "the array should have at least 2 elements");
+    assert!(pivot_index < len);
     
     // it clones the pivot element to stack
     let pivot = arr[pivot_index].clone();
@@ -195,7 +200,7 @@ pub fn fat_partition<Element: Clone, Comparator>(
         Element: Clone,
 {
     let len = arr.len();
-    assert!(len > 2);
+    assert!(pivot_index < len);
     
     // it clones the pivot element to stack
     let pivot = arr[pivot_index].clone();
@@ -269,9 +274,27 @@ pub fn fat_partition_no_clone_required<Element, Comparator>(
 ) -> (usize, usize)
     where
         Comparator: Fn(&Element, &Element) -> Ordering
+{
+    without_cancellation(|cancel| fat_partition_cancellable(arr, comparator, initial_pivot_index, cancel))
+}
+
+// the cancellable partition checks the flag once in this many steps, so that the check costs little
+pub const PARTITION_CANCEL_CHECK_INTERVAL: usize = 4096;
+
+// Same as fat_partition_no_clone_required, but it checks the cancel flag periodically,
+// so that a long partition of a large array can also be cancelled.
+// It only swaps, so the array is a permutation of the input after it's cancelled.
+pub fn fat_partition_cancellable<Element, Comparator>(
+    arr: &mut [Element],
+    comparator: &Comparator,
+    initial_pivot_index: usize,
+    cancel: &AtomicBool,
+) -> Result<(usize, usize), Cancelled>
+    where
+        Comparator: Fn(&Element, &Element) -> Ordering
 {
     let len = arr.len();
-    assert!(len > 2);
+    assert!(initial_pivot_index < len);
     
     let mut curr_pivot_index = initial_pivot_index;
     
@@ -279,7 +302,15 @@ pub fn fat_partition_no_clone_required<Element, Comparator>(
     let mut right_index = len - 1;
     let mut eq_index = 0;
     
+    let mut steps_until_check = PARTITION_CANCEL_CHECK_INTERVAL;
+    
     while eq_index <= right_index {
+        steps_until_check -= 1;
+        if steps_until_check == 0 {
+            check_cancelled(cancel)?;
+            steps_until_check = PARTITION_CANCEL_CHECK_INTERVAL;
+        }
+        
         if curr_pivot_index == eq_index {
             // no need to compare arr[eq_index] with arr[pivot_index] now, treat it as equal
             eq_index += 1;
@@ -322,7 +353,88 @@ pub fn fat_partition_no_clone_required<Element, Comparator>(
     
     assert_eq!(eq_index, right_index + 1);
     
-    (left_index, eq_index)
+    Ok((left_index, eq_index))
+}
+
+// Same as fat_partition_no_clone_required, but in debug build,
+// it scans the result to validate the comparator, panicking if the comparator is inconsistent.
+// The partitioning only compares the elements with the pivot, so it checks more than the regions:
+// * anti-symmetry: comparing pivot with an element gives the reversed result of comparing the element with pivot
+// * transitivity: every element in left region is less than an element in right region, and vice versa
+// It takes O(n) extra comparisons. In release build, it doesn't check.
+pub fn fat_partition_checked<Element, Comparator>(
+    arr: &mut [Element],
+    comparator: &Comparator,
+    initial_pivot_index: usize,
+) -> (usize, usize)
+    where
+        Comparator: Fn(&Element, &Element) -> Ordering
+{
+    let (l, r) = fat_partition_no_clone_required(arr, comparator, initial_pivot_index);
+    
+    if cfg!(debug_assertions) {
+        validate_fat_partition(arr, comparator, l, r);
+    }
+    
+    (l, r)
+}
+
+fn validate_fat_partition<Element, Comparator>(
+    arr: &[Element],
+    comparator: &Comparator,
+    l: usize,
+    r: usize,
+)
+    where
+        Comparator: Fn(&Element, &Element) -> Ordering
+{
+    let pivot = &arr[l];
+    
+    let expected_ordering = |i: usize| -> Ordering {
+        if i < l {
+            Ordering::Less
+        } else if i < r {
+            Ordering::Equal
+        } else {
+            Ordering::Greater
+        }
+    };
+    
+    for (i, element) in arr.iter().enumerate() {
+        let expected = expected_ordering(i);
+        
+        assert_eq!(
+            comparator(element, pivot), expected,
+            "inconsistent comparator: element at {} compared with pivot is not {:?}", i, expected
+        );
+        assert_eq!(
+            comparator(pivot, element), expected.reverse(),
+            "inconsistent comparator: it's not anti-symmetric. pivot compared with element at {} is not {:?}",
+            i, expected.reverse()
+        );
+    }
+    
+    // by transitivity, left < pivot < right
+    if l > 0 && r < arr.len() {
+        let left_witness = &arr[0];
+        let right_witness = &arr[r];
+        
+        for (i, element) in arr[..l].iter().enumerate() {
+            assert_eq!(
+                comparator(element, right_witness), Ordering::Less,
+                "inconsistent comparator: it's not transitive. element at {} (left region) is not less than element at {} (right region)",
+                i, r
+            );
+        }
+        
+        for (i, element) in arr.iter().enumerate().skip(r) {
+            assert_eq!(
+                comparator(left_witness, element), Ordering::Less,
+                "inconsistent comparator: it's not transitive. element at 0 (left region) is not less than element at {} (right region)",
+                i
+            );
+        }
+    }
 }
 
 //noinspection SpellCheckingInspection
@@ -332,6 +444,8 @@ mod tests {
     use rand::{Rng, SeedableRng};
     use rand::prelude::StdRng;
     
+    use crate::util::test_util::{assert_same_elements, verify_partition_fat, verify_partition_hoare, verify_partition_lomuto};
+    
     use super::*;
     
     fn create_rng() -> StdRng {
@@ -368,20 +482,11 @@ mod tests {
                     // choose random element as pivot
                     rng.gen_range(0..vec.len())
                 };
+            let original = vec.clone();
             let p = lomuto_partition(vec.as_mut_slice(), &|x: &i32, y: &i32| x.cmp(y), pivot_index);
-            assert!(p < vec.len());
-            
-            let left_max = vec[0..p].iter().max();
-            let pivot = vec[p];
-            let right_min = vec[(p + 1)..].iter().min();
-            
-            if let Some(left_max) = left_max {
-                assert!(*left_max < pivot);
-            }
             
-            if let Some(right_min) = right_min {
-                assert!(*right_min >= pivot);
-            }
+            verify_partition_lomuto(&vec, &|x: &i32, y: &i32| x.cmp(y), p);
+            assert_same_elements(&original, &vec);
         }
     }
     
@@ -422,16 +527,11 @@ mod tests {
                     // choose random element as pivot
                     rng.gen_range(0..vec.len())
                 };
+            let original = vec.clone();
             let p = hoare_partition(vec.as_mut_slice(), &|x: &i32, y: &i32| x.cmp(y), pivot_index);
-            // arr[0..p] <= pivot and arr[p..] > pivot
-            
-            assert_ne!(p, 0, "the left part is empty");
-            assert_ne!(p, vec.len(), "the right part is empty");
             
-            let left_max = vec[0..p].iter().max().unwrap();
-            let right_min = vec[p..].iter().min().unwrap();
-            
-            assert!(left_max <= right_min);
+            verify_partition_hoare(&vec, &|x: &i32, y: &i32| x.cmp(y), p);
+            assert_same_elements(&original, &vec);
         }
     }
     
@@ -456,7 +556,7 @@ mod tests {
             
             let (l, r) = fat_partition(vec.as_mut_slice(), &|x: &i32, y: &i32| x.cmp(y), pivot_index);
             
-            validate_fat_partition_result(&mut vec, l, r);
+            verify_partition_fat(&vec, &|x: &i32, y: &i32| x.cmp(y), l, r);
         }
         
         for i in 0..1000 {
@@ -478,27 +578,41 @@ mod tests {
                 vec.as_mut_slice(), &|x: &i32, y: &i32| x.cmp(y), pivot_index,
             );
             
-            validate_fat_partition_result(&mut vec, l, r);
+            verify_partition_fat(&vec, &|x: &i32, y: &i32| x.cmp(y), l, r);
         }
     }
     
-    fn validate_fat_partition_result(vec: &mut Vec<i32>, l: usize, r: usize) {
-        assert!(l < r, "equal region is empty");
-        
-        let left_max = vec[0..l].iter().max();
-        let pivot = vec[l];
-        let right_min = vec[r..].iter().min();
-        
-        assert!(vec[l..r].iter().all(|x| *x == pivot), "equal region is not equal");
+    #[test]
+    fn test_fat_partition_checked() {
+        let mut rng = create_rng();
         
-        if let Some(left_max) = left_max {
-            assert!(*left_max < pivot);
+        for _i in 0..1000 {
+            let mut vec = random_vec(&mut rng);
+            let pivot_index = rng.gen_range(0..vec.len());
+            
+            let (l, r) = fat_partition_checked(vec.as_mut_slice(), &|x: &i32, y: &i32| x.cmp(y), pivot_index);
+            
+            verify_partition_fat(&vec, &|x: &i32, y: &i32| x.cmp(y), l, r);
         }
+    }
+    
+    #[test]
+    #[cfg(debug_assertions)]
+    #[should_panic(expected = "inconsistent comparator")]
+    fn test_fat_partition_checked_broken_comparator() {
+        // rock-paper-scissors: 0 < 1 < 2 < 0, it's not transitive
+        let broken_comparator = |x: &i32, y: &i32| -> Ordering {
+            match (x - y).rem_euclid(3) {
+                0 => Ordering::Equal,
+                1 => Ordering::Greater,
+                _ => Ordering::Less,
+            }
+        };
         
-        if let Some(right_min) = right_min {
-            assert!(*right_min >= pivot);
-        }
+        let mut vec: Vec<i32> = (0..30).map(|i| i % 3).collect();
+        fat_partition_checked(vec.as_mut_slice(), &broken_comparator, 0);
     }
+    
 }
 
 fn _hoare_wikipedia<Element: Clone, Comparator>(
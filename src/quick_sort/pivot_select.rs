@@ -1,5 +1,9 @@
 use std::cmp::Ordering;
 
+use crate::algo::midpoint::midpoint;
+use crate::insertion_sort::simple_insertion_sort::insertion_sort;
+use crate::select::quick_select::introselect;
+
 // select the first element as pivot
 pub fn first_element_as_pivot<Element>(_arr: &[Element]) -> usize {
     0
@@ -7,7 +11,7 @@ pub fn first_element_as_pivot<Element>(_arr: &[Element]) -> usize {
 
 // select the middle element as pivot
 pub fn middle_element_as_pivot<Element>(arr: &[Element]) -> usize {
-    arr.len() / 2
+    midpoint(0, arr.len())
 }
 
 // select the last element as pivot
@@ -24,9 +28,18 @@ pub fn median_of_three_pivot<Element, Comparator>(
         Comparator: Fn(&Element, &Element) -> Ordering,
 {
     let len = arr.len();
-    let i1 = 0;
-    let i2 = len / 2;
-    let i3 = len - 1;
+    median_index_of_three(arr, compare, 0, midpoint(0, len), len - 1)
+}
+
+// returns the index of the median of arr[i1], arr[i2], arr[i3]
+fn median_index_of_three<Element, Comparator>(
+    arr: &[Element],
+    compare: &Comparator,
+    i1: usize, i2: usize, i3: usize,
+) -> usize
+    where
+        Comparator: Fn(&Element, &Element) -> Ordering,
+{
     let e1 = &arr[i1];
     let e2 = &arr[i2];
     let e3 = &arr[i3];
@@ -51,15 +64,102 @@ pub fn median_of_three_pivot<Element, Comparator>(
         return i1;
     }
     // e3 <= e1 <= e2
-    if cmp13.is_ge() && cmp23.is_le() {
+    if cmp13.is_ge() && cmp12.is_le() {
         return i1;
     }
     
     return i3;
 }
 
+// Tukey's ninther. Take 9 evenly spaced elements, separate them into 3 groups of 3,
+// and select the median of the 3 medians. It's a better pivot than median of three for larger arrays.
+pub fn ninther_pivot<Element, Comparator>(
+    arr: &[Element],
+    compare: &Comparator,
+) -> usize
+    where
+        Comparator: Fn(&Element, &Element) -> Ordering,
+{
+    let len = arr.len();
+    assert!(len >= 9);
+    
+    let step = len / 8;
+    let mid = midpoint(0, len);
+    let last = len - 1;
+    
+    let m1 = median_index_of_three(arr, compare, 0, step, 2 * step);
+    let m2 = median_index_of_three(arr, compare, mid - step, mid, mid + step);
+    let m3 = median_index_of_three(arr, compare, last - 2 * step, last - step, last);
+    
+    median_index_of_three(arr, compare, m1, m2, m3)
+}
+
+pub const NINTHER_THRESHOLD: usize = 64;
+pub const MEDIAN_OF_MEDIANS_THRESHOLD: usize = 1 << 16;
+
+// Select the pivot strategy by size:
+// - small: median of three. it's cheap, and a bad pivot on small array doesn't cost much.
+// - medium: ninther.
+// - very large: median of medians. its O(n) cost is small compared to the partition,
+//   and it guarantees a good split where a bad split is the most costly.
+// It may rearrange the array (for median of medians).
+pub fn adaptive_pivot<Element, Comparator>(
+    arr: &mut [Element],
+    compare: &Comparator,
+) -> usize
+    where
+        Comparator: Fn(&Element, &Element) -> Ordering,
+{
+    let len = arr.len();
+    if len < NINTHER_THRESHOLD {
+        median_of_three_pivot(arr, compare)
+    } else if len < MEDIAN_OF_MEDIANS_THRESHOLD {
+        ninther_pivot(arr, compare)
+    } else {
+        median_of_medians_pivot(arr, compare)
+    }
+}
+
+// Median of medians. It guarantees that at least 30% of elements are <= pivot and at least 30% are >= pivot,
+// so selecting with it is O(n) in the worst case. But it's slower than median of three on average.
+// - separate the array into groups of 5, find the median of each group by sorting the group.
+// - move the medians to the front of the array.
+// - recursively select the median of the medians.
+// It rearranges the array, so it requires mutable access, unlike the other pivot selections.
+pub fn median_of_medians_pivot<Element, Comparator>(
+    arr: &mut [Element],
+    compare: &Comparator,
+) -> usize
+    where
+        Comparator: Fn(&Element, &Element) -> Ordering,
+{
+    let len = arr.len();
+    assert!(len > 0);
+    
+    if len <= 5 {
+        insertion_sort(arr, compare);
+        return len / 2;
+    }
+    
+    // the last group may have less than 5 elements, it's ignored
+    let group_num = len / 5;
+    for group_index in 0..group_num {
+        let group_start = group_index * 5;
+        insertion_sort(&mut arr[group_start..(group_start + 5)], compare);
+        // arr[group_index] is in a group that is already handled (or the current group), so it can be overwritten
+        arr.swap(group_index, group_start + 2);
+    }
+    
+    let medians = &mut arr[..group_num];
+    introselect(medians, group_num / 2, compare);
+    group_num / 2
+}
+
 #[cfg(test)]
 mod tests {
+    use rand::SeedableRng;
+    use rand::prelude::{SliceRandom, StdRng};
+    
     use super::*;
     
     #[test]
@@ -68,6 +168,47 @@ mod tests {
         test_median_for(&[1, 2, 5, 4, 3], 4);
         test_median_for(&[1, 2, 3, 4, 5], 2);
         test_median_for(&[5, 4, 3, 2, 1], 2);
+        test_median_for(&[2, 4, 3, 5, 1], 0);
+        test_median_for(&[3, 4, 1, 5, 2], 4);
+    }
+    
+    #[test]
+    fn test_median_of_medians_pivot() {
+        let compare = |a: &i32, b: &i32| a.cmp(b);
+        
+        for len in 1..200 {
+            let mut vec: Vec<i32> = (0..len).map(|x| (x * 37) % len).collect();
+            let pivot_index = median_of_medians_pivot(&mut vec, &compare);
+            let pivot = vec[pivot_index];
+            
+            // for large enough arrays, at least 30% of elements are on each side
+            let less_or_equal = vec.iter().filter(|x| **x <= pivot).count() as i32;
+            let greater_or_equal = vec.iter().filter(|x| **x >= pivot).count() as i32;
+            assert!(less_or_equal * 10 >= len * 3 - 50);
+            assert!(greater_or_equal * 10 >= len * 3 - 50);
+        }
+    }
+    
+    #[test]
+    fn test_ninther_pivot() {
+        let compare = |a: &i32, b: &i32| a.cmp(b);
+        
+        // sorted and reversed, the ninther is the middle
+        let vec: Vec<i32> = (0..100).collect();
+        assert_eq!(ninther_pivot(&vec, &compare), 50);
+        let vec: Vec<i32> = (0..100).rev().collect();
+        assert_eq!(ninther_pivot(&vec, &compare), 50);
+        
+        // for distinct elements, the ninther is larger than 4 samples and smaller than 4 samples,
+        // so it's neither the min nor the max
+        let mut rng: StdRng = SeedableRng::seed_from_u64(123456);
+        for len in 9..300 {
+            let mut vec: Vec<i32> = (0..len).collect();
+            vec.shuffle(&mut rng);
+            let pivot = vec[ninther_pivot(&vec, &compare)];
+            assert!(pivot > 0);
+            assert!(pivot < len - 1);
+        }
     }
     
     fn test_median_for(arr: &[i32], result: usize) {
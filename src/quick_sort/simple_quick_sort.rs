@@ -1,23 +1,211 @@
 use std::cmp::Ordering;
+use std::sync::atomic::AtomicBool;
 
-use crate::quick_sort::partition::fat_partition_no_clone_required;
-use crate::quick_sort::pivot_select::median_of_three_pivot;
+use crate::data_structure::binary_heap::heap_sort_cancellable;
+use crate::insertion_sort::simple_insertion_sort::insertion_sort;
+use crate::quick_sort::partition::{fat_partition_cancellable, fat_partition_no_clone_required};
+use crate::quick_sort::pivot_select::{adaptive_pivot, median_of_three_pivot};
+use crate::search::sorted_slice::{first_unsorted_index, is_sorted};
+use crate::util::cancellation::{Cancelled, check_cancelled, without_cancellation};
 
+// It only moves elements by swapping, so the element doesn't need to be Clone.
+// It recurses into the smaller part and loops on the larger part,
+// so the recursion depth is O(log n) even when the partitions are unbalanced.
 pub fn normal_quick_sort<Element, Comparator>(
     arr: &mut [Element], compare: &Comparator,
 ) where
     Comparator: Fn(&Element, &Element) -> Ordering,
 {
-    let len = arr.len();
+    without_cancellation(|cancel| normal_quick_sort_cancellable(arr, compare, cancel));
+}
+
+// Same as normal_quick_sort, but it checks the cancel flag before each partition,
+// and returns Err(Cancelled) if it's set. Then the array is a permutation of the input, possibly unsorted.
+pub fn normal_quick_sort_cancellable<Element, Comparator>(
+    arr: &mut [Element], compare: &Comparator, cancel: &AtomicBool,
+) -> Result<(), Cancelled>
+    where
+        Comparator: Fn(&Element, &Element) -> Ordering,
+{
+    normal_quick_sort_observed(arr, compare, 0, 0, &mut |_depth, _step| {}, cancel)
+}
+
+// Same as normal_quick_sort, but it records each partition step as
+// (range_start, equal_left, equal_right), in the order they are done. The indices are in the whole slice.
+// After the step, arr[equal_left..equal_right] are equal to the pivot, and they are in the final position.
+// These steps form the partition tree that produced the final order.
+// The ranges of length 2 or less are sorted directly, without a partition step.
+pub fn normal_quick_sort_recording<Element, Comparator>(
+    arr: &mut [Element], compare: &Comparator,
+) -> Vec<(usize, usize, usize)>
+    where
+        Comparator: Fn(&Element, &Element) -> Ordering,
+{
+    let mut steps: Vec<(usize, usize, usize)> = Vec::new();
+    without_cancellation(|cancel| {
+        normal_quick_sort_observed(arr, compare, 0, 0, &mut |_depth, step| steps.push(step), cancel)
+    });
+    steps
+}
+
+// the observer receives the recursion depth and the partition step, see normal_quick_sort_recording.
+// offset is the index of arr[0] in the whole slice.
+fn normal_quick_sort_observed<Element, Comparator, Observer>(
+    arr: &mut [Element], compare: &Comparator,
+    offset: usize, depth: usize, observer: &mut Observer, cancel: &AtomicBool,
+) -> Result<(), Cancelled>
+    where
+        Comparator: Fn(&Element, &Element) -> Ordering,
+        Observer: FnMut(usize, (usize, usize, usize)),
+{
+    let mut arr = arr;
+    let mut offset = offset;
     
-    if len <= 1 {
-        return;
+    loop {
+        let len = arr.len();
+        
+        if len <= 1 {
+            return Ok(());
+        }
+        
+        if len == 2 {
+            if compare(&arr[0], &arr[1]) == Ordering::Greater {
+                arr.swap(0, 1);
+            }
+            return Ok(());
+        }
+        
+        check_cancelled(cancel)?;
+        
+        let initial_pivot_index = median_of_three_pivot(arr, compare);
+        
+        let (l, r) = fat_partition_cancellable(arr, compare, initial_pivot_index, cancel)?;
+        
+        observer(depth, (offset, offset + l, offset + r));
+        
+        let (left_part, remaining) = arr.split_at_mut(l);
+        let right_part = &mut remaining[(r - l)..];
+        
+        // the smaller part is at most half, so each level of recursion halves the length
+        if left_part.len() <= right_part.len() {
+            normal_quick_sort_observed(left_part, compare, offset, depth + 1, observer, cancel)?;
+            arr = right_part;
+            offset += r;
+        } else {
+            normal_quick_sort_observed(right_part, compare, offset + r, depth + 1, observer, cancel)?;
+            arr = left_part;
+        }
     }
+}
+
+// below this size, intro_sort uses insertion sort
+const INTRO_SORT_INSERTION_THRESHOLD: usize = 16;
+
+// Introsort. Same as normal_quick_sort, but when the partitions go too deep
+// (which means the median of three pivots are bad, possibly by adversarial input),
+// it switches the current range to heap sort, to guarantee O(n log n) in the worst case.
+// The small ranges are sorted by insertion sort, which is faster than partitioning them.
+// It's not stable.
+pub fn intro_sort<Element, Comparator>(
+    arr: &mut [Element], compare: &Comparator,
+) where
+    Comparator: Fn(&Element, &Element) -> Ordering,
+{
+    without_cancellation(|cancel| intro_sort_cancellable(arr, compare, cancel));
+}
+
+// Same as intro_sort, but it checks the cancel flag before each partition, and in the heap sort fallback.
+pub fn intro_sort_cancellable<Element, Comparator>(
+    arr: &mut [Element], compare: &Comparator, cancel: &AtomicBool,
+) -> Result<(), Cancelled>
+    where
+        Comparator: Fn(&Element, &Element) -> Ordering,
+{
+    // for good pivots, the length halves in each level, so it goes O(log n) levels deep
+    let depth_limit = 2 * (usize::BITS - arr.len().leading_zeros()) as usize;
+    intro_sort_with_depth_limit(arr, compare, depth_limit, cancel)
+}
+
+fn intro_sort_with_depth_limit<Element, Comparator>(
+    arr: &mut [Element], compare: &Comparator, depth_limit: usize, cancel: &AtomicBool,
+) -> Result<(), Cancelled>
+    where
+        Comparator: Fn(&Element, &Element) -> Ordering,
+{
+    let mut arr = arr;
+    // every partition counts, including the ones of the larger part done in the loop,
+    // otherwise a killer input can make the loop go O(n) times
+    let mut depth_limit = depth_limit;
     
-    if len == 2 {
-        if compare(&arr[0], &arr[1]) == Ordering::Greater {
-            arr.swap(0, 1);
+    loop {
+        if arr.len() <= INTRO_SORT_INSERTION_THRESHOLD {
+            insertion_sort(arr, compare);
+            return Ok(());
+        }
+        
+        if depth_limit == 0 {
+            return heap_sort_cancellable(arr, compare, cancel);
         }
+        depth_limit -= 1;
+        
+        check_cancelled(cancel)?;
+        
+        let initial_pivot_index = median_of_three_pivot(arr, compare);
+        
+        let (l, r) = fat_partition_cancellable(arr, compare, initial_pivot_index, cancel)?;
+        
+        let (left_part, remaining) = arr.split_at_mut(l);
+        let right_part = &mut remaining[(r - l)..];
+        
+        // same as normal_quick_sort, recurse into the smaller part and loop on the larger part
+        if left_part.len() <= right_part.len() {
+            intro_sort_with_depth_limit(left_part, compare, depth_limit, cancel)?;
+            arr = right_part;
+        } else {
+            intro_sort_with_depth_limit(right_part, compare, depth_limit, cancel)?;
+            arr = left_part;
+        }
+    }
+}
+
+// Shortcut for Ord types, without passing a comparator.
+// The shortcuts are named after their comparator forms with an _ord suffix, instead of a plain `sort` in each module,
+// because the sort modules are often imported together (like in fuzz_tests), where several `sort`s would clash.
+pub fn normal_quick_sort_ord<Element: Ord>(arr: &mut [Element]) {
+    normal_quick_sort(arr, &Element::cmp);
+}
+
+// Sort and give the result in run-length encoding: (value, count) of each distinct value, in sorted order.
+// The fat partition puts all elements equal to the pivot together, and they are never partitioned again,
+// so each distinct value is a pivot once. With d distinct values, it's expected O(n log d).
+// Same as intro_sort, when the partitions go too deep, the range is sorted by intro_sort
+// and encoded in one pass, so the recursion depth is O(log n) even for adversarial input.
+// The slice is reordered (but not necessarily sorted) in the process.
+pub fn sort_rle<Element: Clone, Comparator>(
+    arr: &mut [Element], compare: &Comparator,
+) -> Vec<(Element, usize)>
+    where
+        Comparator: Fn(&Element, &Element) -> Ordering,
+{
+    let mut result: Vec<(Element, usize)> = Vec::new();
+    let depth_limit = 2 * (usize::BITS - arr.len().leading_zeros()) as usize;
+    sort_rle_into(arr, compare, depth_limit, &mut result);
+    result
+}
+
+fn sort_rle_into<Element: Clone, Comparator>(
+    arr: &mut [Element], compare: &Comparator, depth_limit: usize, result: &mut Vec<(Element, usize)>,
+)
+    where
+        Comparator: Fn(&Element, &Element) -> Ordering,
+{
+    if arr.is_empty() {
+        return;
+    }
+    
+    if depth_limit == 0 {
+        intro_sort(arr, compare);
+        push_runs(arr, compare, result);
         return;
     }
     
@@ -25,18 +213,114 @@ pub fn normal_quick_sort<Element, Comparator>(
     
     let (l, r) = fat_partition_no_clone_required(arr, compare, initial_pivot_index);
     
-    let left_part = &mut arr[0..l];
-    normal_quick_sort(left_part, compare);
+    sort_rle_into(&mut arr[0..l], compare, depth_limit - 1, result);
+    result.push((arr[l].clone(), r - l));
+    sort_rle_into(&mut arr[r..], compare, depth_limit - 1, result);
+}
+
+// push (value, count) of each run of equal elements in the sorted arr
+fn push_runs<Element: Clone, Comparator>(
+    arr: &[Element], compare: &Comparator, result: &mut Vec<(Element, usize)>,
+)
+    where
+        Comparator: Fn(&Element, &Element) -> Ordering,
+{
+    let mut run_start = 0;
+    for index in 1..=arr.len() {
+        if index == arr.len() || compare(&arr[run_start], &arr[index]) != Ordering::Equal {
+            result.push((arr[run_start].clone(), index - run_start));
+            run_start = index;
+        }
+    }
+}
+
+// Same as normal_quick_sort, but it selects the pivot strategy by size. See adaptive_pivot.
+pub fn adaptive_quick_sort<Element, Comparator>(
+    arr: &mut [Element], compare: &Comparator,
+) where
+    Comparator: Fn(&Element, &Element) -> Ordering,
+{
+    let mut arr = arr;
+    
+    loop {
+        let len = arr.len();
+        
+        if len <= 1 {
+            return;
+        }
+        
+        if len == 2 {
+            if compare(&arr[0], &arr[1]) == Ordering::Greater {
+                arr.swap(0, 1);
+            }
+            return;
+        }
+        
+        let initial_pivot_index = adaptive_pivot(arr, compare);
+        
+        let (l, r) = fat_partition_no_clone_required(arr, compare, initial_pivot_index);
+        
+        let (left_part, remaining) = arr.split_at_mut(l);
+        let right_part = &mut remaining[(r - l)..];
+        
+        // same as normal_quick_sort, recurse into the smaller part and loop on the larger part,
+        // as the ninther pivot of the smaller sizes can still be made bad by adversarial input
+        if left_part.len() <= right_part.len() {
+            adaptive_quick_sort(left_part, compare);
+            arr = right_part;
+        } else {
+            adaptive_quick_sort(right_part, compare);
+            arr = left_part;
+        }
+    }
+}
+
+// Sort, and return whether the order changed.
+// If it's already sorted, it only takes n - 1 comparisons and doesn't move anything.
+// Otherwise the sorted result must differ from the input, so it doesn't need to compare the elements
+// before and after sorting, thus doesn't require PartialEq or Clone.
+pub fn sort_detect_change<Element, Comparator>(
+    arr: &mut [Element], compare: &Comparator,
+) -> bool
+    where
+        Comparator: Fn(&Element, &Element) -> Ordering,
+{
+    if is_sorted(arr, compare) {
+        return false;
+    }
     
-    let right_part = &mut arr[r..];
-    normal_quick_sort(right_part, compare);
+    normal_quick_sort(arr, compare);
+    true
 }
 
+// Same as normal_quick_sort, but in debug build, it checks that the result is sorted.
+// An inconsistent comparator may silently leave the array unsorted, this catches it early.
+pub fn quick_sort_debug<Element, Comparator>(
+    arr: &mut [Element], compare: &Comparator,
+) where
+    Comparator: Fn(&Element, &Element) -> Ordering,
+{
+    normal_quick_sort(arr, compare);
+    
+    if cfg!(debug_assertions) {
+        if let Some(index) = first_unsorted_index(arr, compare) {
+            panic!(
+                "not sorted after quick sort: arr[{}] > arr[{}]. the comparator may be inconsistent",
+                index, index + 1
+            );
+        }
+    }
+}
 
 #[cfg(test)]
 mod tests {
+    use std::cell::Cell;
+    
     use rand::{Rng, rngs::StdRng, SeedableRng};
     
+    use crate::quick_sort::partition::PARTITION_CANCEL_CHECK_INTERVAL;
+    use crate::util::test_util::{assert_same_order, check_cancellable_sort, count_comparisons, generate_killer_input, NotCloneable, to_not_cloneable};
+    
     use super::*;
     
     #[test]
@@ -53,8 +337,314 @@ mod tests {
             
             vec_ref.sort();
             
-            assert_eq!(vec, vec_ref);
+            assert_same_order(&vec, &vec_ref);
+        }
+    }
+    
+    #[test]
+    fn test_normal_quick_sort_not_cloneable() {
+        let mut rng = create_rng();
+        
+        for _i in 0..100 {
+            let vec = random_vec(&mut rng);
+            let mut boxed: Vec<NotCloneable> = to_not_cloneable(&vec);
+            
+            normal_quick_sort(&mut boxed, &|a, b| a.cmp(b));
+            
+            let mut vec_ref = vec.clone();
+            vec_ref.sort();
+            assert_eq!(boxed, to_not_cloneable(&vec_ref));
+        }
+    }
+    
+    #[test]
+    fn test_normal_quick_sort_recursion_depth() {
+        let len: usize = 100000;
+        let max_allowed_depth = (usize::BITS - len.leading_zeros()) as usize;
+        
+        let sorted: Vec<i32> = (0..len as i32).collect();
+        let reversed: Vec<i32> = (0..len as i32).rev().collect();
+        // organ pipe: ascending then descending
+        let organ_pipe: Vec<i32> = (0..len as i32).map(|i| i.min(len as i32 - i)).collect();
+        let all_equal: Vec<i32> = vec![1; len];
+        // interleaving the ascending and descending sequences
+        let sawtooth: Vec<i32> = (0..len as i32).map(|i| if i % 2 == 0 { i } else { len as i32 - i }).collect();
+        
+        for input in [sorted, reversed, organ_pipe, all_equal, sawtooth] {
+            let mut vec = input.clone();
+            let mut max_depth = 0;
+            without_cancellation(|cancel| normal_quick_sort_observed(
+                &mut vec, &|a: &i32, b: &i32| a.cmp(b), 0, 0,
+                &mut |depth, _step| max_depth = max_depth.max(depth), cancel,
+            ));
+            
+            assert!(max_depth <= max_allowed_depth, "recursion too deep: {}", max_depth);
+            let mut vec_ref = input;
+            vec_ref.sort();
+            assert_same_order(&vec, &vec_ref);
+        }
+        
+        // the killer input makes every partition maximally unbalanced, so the comparisons are quadratic,
+        // but the recursion still only goes into the smaller part.
+        // it's quadratic to generate, so it's shorter.
+        let killer_len: usize = 5000;
+        let max_allowed_depth = (usize::BITS - killer_len.leading_zeros()) as usize;
+        let killer = generate_killer_input(killer_len, |arr, compare| normal_quick_sort(arr, &compare));
+        let mut vec = killer.clone();
+        let mut max_depth = 0;
+        let mut partition_count = 0;
+        without_cancellation(|cancel| normal_quick_sort_observed(
+            &mut vec, &|a: &usize, b: &usize| a.cmp(b), 0, 0,
+            &mut |depth, _step| {
+                max_depth = max_depth.max(depth);
+                partition_count += 1;
+            },
+            cancel,
+        ));
+        
+        // it's really the bad case, nearly every partition only removes a few elements
+        assert!(partition_count > killer_len / 4, "only {} partitions", partition_count);
+        assert!(max_depth <= max_allowed_depth, "recursion too deep on the killer input: {}", max_depth);
+        assert_eq!(vec, (0..killer_len).collect::<Vec<usize>>());
+    }
+    
+    #[test]
+    fn test_normal_quick_sort_recording() {
+        let mut rng = create_rng();
+        
+        for _i in 0..100 {
+            let input = random_vec(&mut rng);
+            let len = input.len();
+            let mut vec = input.clone();
+            
+            let steps = normal_quick_sort_recording(&mut vec, &|a: &i32, b: &i32| a.cmp(b));
+            
+            let mut vec_ref = input.clone();
+            vec_ref.sort();
+            assert_same_order(&vec, &vec_ref);
+            
+            // the equal regions don't overlap
+            let mut covered = vec![false; len];
+            for (range_start, equal_left, equal_right) in steps.iter().copied() {
+                assert!(range_start <= equal_left && equal_left < equal_right && equal_right <= len);
+                for is_covered in &mut covered[equal_left..equal_right] {
+                    assert!(!*is_covered);
+                    *is_covered = true;
+                }
+            }
+            
+            // the remaining ranges are sorted directly, and they are separated by the equal regions
+            let mut uncovered_run = 0;
+            for is_covered in covered {
+                uncovered_run = if is_covered { 0 } else { uncovered_run + 1 };
+                assert!(uncovered_run <= 2);
+            }
+            
+            // replay: each pivot is placed by its rank in the input, which is where the sorted result has it
+            for (_range_start, equal_left, equal_right) in steps.iter().copied() {
+                let pivot = vec[equal_left];
+                assert_eq!(input.iter().filter(|e| **e < pivot).count(), equal_left);
+                assert_eq!(input.iter().filter(|e| **e == pivot).count(), equal_right - equal_left);
+            }
+        }
+    }
+    
+    #[test]
+    fn test_intro_sort() {
+        let mut rng = create_rng();
+        
+        for _i in 0..1000 {
+            let mut vec = random_vec(&mut rng);
+            let mut vec_ref = vec.clone();
+            
+            intro_sort(&mut vec, &|a: &i32, b: &i32| a.cmp(b));
+            vec_ref.sort();
+            
+            assert_same_order(&vec, &vec_ref);
+        }
+        
+        let mut vec = to_not_cloneable(&[3, 1, 2, 5, 4]);
+        intro_sort(&mut vec, &|a: &NotCloneable, b: &NotCloneable| a.0.cmp(&b.0));
+        assert_eq!(vec.iter().map(|e| *e.0).collect::<Vec<i32>>(), vec![1, 2, 3, 4, 5]);
+    }
+    
+    #[test]
+    fn test_intro_sort_killer_input() {
+        let len: usize = 5000;
+        let n_log_n = len * (usize::BITS - len.leading_zeros()) as usize;
+        
+        let quick_sort_killer = generate_killer_input(len, |arr, compare| normal_quick_sort(arr, &compare));
+        let quick_sort_comparisons = count_comparisons(&quick_sort_killer, |arr, compare| normal_quick_sort(arr, &compare));
+        let intro_sort_comparisons_on_quick_sort_killer =
+            count_comparisons(&quick_sort_killer, |arr, compare| intro_sort(arr, &compare));
+        
+        let intro_sort_killer = generate_killer_input(len, |arr, compare| intro_sort(arr, &compare));
+        let intro_sort_comparisons = count_comparisons(&intro_sort_killer, |arr, compare| intro_sort(arr, &compare));
+        
+        // quadratic
+        assert!(
+            quick_sort_comparisons > len * len / 10,
+            "quick sort on its killer input: {} comparisons", quick_sort_comparisons
+        );
+        
+        // n log n
+        assert!(
+            intro_sort_comparisons_on_quick_sort_killer < 5 * n_log_n,
+            "intro sort on quick sort killer input: {} comparisons", intro_sort_comparisons_on_quick_sort_killer
+        );
+        assert!(
+            intro_sort_comparisons < 5 * n_log_n,
+            "intro sort on its killer input: {} comparisons", intro_sort_comparisons
+        );
+        
+        let mut vec = intro_sort_killer.clone();
+        intro_sort(&mut vec, &|a: &usize, b: &usize| a.cmp(b));
+        assert_eq!(vec, (0..len).collect::<Vec<usize>>());
+    }
+    
+    #[test]
+    fn test_quick_sorts_cancellable() {
+        check_cancellable_sort("normal_quick_sort", PARTITION_CANCEL_CHECK_INTERVAL, |arr, compare, cancel| {
+            normal_quick_sort_cancellable(arr, &compare, cancel)
+        });
+        check_cancellable_sort("intro_sort", PARTITION_CANCEL_CHECK_INTERVAL, |arr, compare, cancel| {
+            intro_sort_cancellable(arr, &compare, cancel)
+        });
+    }
+    
+    #[test]
+    fn test_normal_quick_sort_ord() {
+        let mut rng = create_rng();
+        
+        let mut vec = random_vec(&mut rng);
+        let mut vec_ref = vec.clone();
+        normal_quick_sort_ord(&mut vec);
+        normal_quick_sort(&mut vec_ref, &|a, b| a.cmp(b));
+        assert_same_order(&vec, &vec_ref);
+        
+        let mut vec: Vec<String> = vec!["pear".to_string(), "apple".to_string(), "fig".to_string(), "apple".to_string()];
+        normal_quick_sort_ord(&mut vec);
+        assert_eq!(vec, vec!["apple", "apple", "fig", "pear"]);
+    }
+    
+    #[test]
+    fn test_sort_rle() {
+        let mut rng = create_rng();
+        
+        let mut vec: Vec<i32> = (0..1000).map(|_| [7, 3, 5][rng.gen_range(0..3)]).collect();
+        let count_of = |value: i32| vec.iter().filter(|e| **e == value).count();
+        let expected = vec![(3, count_of(3)), (5, count_of(5)), (7, count_of(7))];
+        
+        assert_eq!(sort_rle(&mut vec, &|a, b| a.cmp(b)), expected);
+        
+        for _i in 0..100 {
+            let mut vec = random_vec(&mut rng);
+            let mut vec_ref = vec.clone();
+            vec_ref.sort();
+            
+            let rle = sort_rle(&mut vec, &|a, b| a.cmp(b));
+            let expanded: Vec<i32> = rle.iter().flat_map(|(value, count)| std::iter::repeat(*value).take(*count)).collect();
+            assert_eq!(expanded, vec_ref);
+            assert!(rle.windows(2).all(|w| w[0].0 < w[1].0));
+        }
+        
+        assert_eq!(sort_rle(&mut Vec::<i32>::new(), &|a, b| a.cmp(b)), vec![]);
+    }
+    
+    // without the depth limit, the killer input makes each partition split off one element,
+    // and the recursion goes O(n) deep, which overflows the stack
+    #[test]
+    fn test_sort_rle_killer_input() {
+        let len: usize = 100000;
+        let n_log_n = len * (usize::BITS - len.leading_zeros()) as usize;
+        let killer = generate_killer_input(len, |arr, compare| { sort_rle(arr, &compare); });
+        
+        let comparisons = count_comparisons(&killer, |arr, compare| {
+            let rle = sort_rle(arr, &compare);
+            assert_eq!(rle, (0..len).map(|value| (value, 1)).collect::<Vec<(usize, usize)>>());
+        });
+        // both the partitions before falling back and the ones in intro_sort go up to 2 log n levels deep
+        assert!(comparisons < 10 * n_log_n, "{} comparisons on the killer input", comparisons);
+    }
+    
+    #[test]
+    fn test_adaptive_quick_sort() {
+        let mut rng = create_rng();
+        
+        // cover the sizes of all pivot strategies
+        let sizes = [0, 1, 2, 3, 5, 10, 63, 64, 65, 100, 1000, 10000, 65535, 65536, 100000];
+        for size in sizes {
+            let max = rng.gen_range(1..1000000);
+            let mut vec: Vec<i32> = (0..size).map(|_| rng.gen_range(0..max)).collect();
+            let mut vec_ref = vec.clone();
+            
+            adaptive_quick_sort(&mut vec, &|a, b| a.cmp(b));
+            
+            vec_ref.sort();
+            assert_same_order(&vec, &vec_ref);
         }
+        
+        // already sorted and reversed
+        let mut vec: Vec<i32> = (0..100000).collect();
+        adaptive_quick_sort(&mut vec, &|a, b| a.cmp(b));
+        assert!(vec.windows(2).all(|w| w[0] <= w[1]));
+        let mut vec: Vec<i32> = (0..100000).rev().collect();
+        adaptive_quick_sort(&mut vec, &|a, b| a.cmp(b));
+        assert!(vec.windows(2).all(|w| w[0] <= w[1]));
+    }
+    
+    #[test]
+    fn test_sort_detect_change() {
+        let mut rng = create_rng();
+        
+        let count: Cell<usize> = Cell::new(0);
+        let counting_compare = |a: &i32, b: &i32| {
+            count.set(count.get() + 1);
+            a.cmp(b)
+        };
+        
+        let mut vec: Vec<i32> = (0..1000).map(|i| i / 3).collect();
+        assert!(!sort_detect_change(&mut vec, &counting_compare));
+        assert_eq!(count.get(), 999);
+        
+        let mut vec = random_vec(&mut rng);
+        let mut vec_ref = vec.clone();
+        vec_ref.sort();
+        assert!(sort_detect_change(&mut vec, &counting_compare));
+        assert_same_order(&vec, &vec_ref);
+        
+        // sorted now
+        assert!(!sort_detect_change(&mut vec, &counting_compare));
+        
+        assert!(!sort_detect_change(&mut Vec::<i32>::new(), &counting_compare));
+    }
+    
+    #[test]
+    fn test_quick_sort_debug() {
+        let mut rng = create_rng();
+        let mut vec = random_vec(&mut rng);
+        let mut vec_ref = vec.clone();
+        quick_sort_debug(&mut vec, &|a, b| a.cmp(b));
+        vec_ref.sort();
+        assert_same_order(&vec, &vec_ref);
+    }
+    
+    #[test]
+    #[cfg(debug_assertions)]
+    #[should_panic(expected = "not sorted after quick sort")]
+    fn test_quick_sort_debug_broken_comparator() {
+        // the order flips after some comparisons, like sorting by a key that changes during sorting
+        let count: Cell<usize> = Cell::new(0);
+        let broken_compare = |a: &i32, b: &i32| {
+            count.set(count.get() + 1);
+            if count.get() < 300 {
+                a.cmp(b)
+            } else {
+                b.cmp(a)
+            }
+        };
+        let mut vec: Vec<i32> = (0..100).rev().collect();
+        quick_sort_debug(&mut vec, &broken_compare);
     }
     
     fn create_rng() -> StdRng {
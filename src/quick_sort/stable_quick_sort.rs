@@ -49,6 +49,11 @@ fn functional_style_stable_quick_sort<Element: Clone, Comparator>(
 
 #[cfg(test)]
 mod tests {
+    use rand::{Rng, SeedableRng};
+    use rand::prelude::StdRng;
+    
+    use crate::util::test_util::assert_stable;
+    
     use super::*;
     
     #[test]
@@ -66,4 +71,20 @@ mod tests {
         
         assert_eq!(sorted, vec);
     }
-}
\ No newline at end of file
+    
+    #[test]
+    fn test_functional_style_stable_quick_sort_stability() {
+        let mut rng: StdRng = SeedableRng::seed_from_u64(123456);
+        
+        for _i in 0..100 {
+            let len = rng.gen_range(0..1000);
+            let max = rng.gen_range(1..50);
+            // (key, tag), only key is compared
+            let vec: Vec<(i32, usize)> = (0..len).map(|tag| (rng.gen_range(0..max), tag)).collect();
+            
+            let sorted = functional_style_stable_quick_sort(vec.as_slice(), &|a, b| a.0.cmp(&b.0));
+            
+            assert_stable(&vec, &sorted);
+        }
+    }
+}
@@ -0,0 +1,144 @@
+use std::cmp::Ordering;
+
+use crate::algo::midpoint::midpoint;
+
+// when binary_search in std found consecutive equal elements, it may not return the leftmost one.
+// this function will return the leftmost one.
+pub fn binary_search_leftmost<Element, Comparator>(
+    arr: &[Element], compare: &Comparator, target: &Element,
+) -> usize
+    where
+        Comparator: Fn(&Element, &Element) -> Ordering
+{
+    match arr.binary_search_by(|probe| compare(probe, target)) {
+        Ok(pos) => {
+            // if there are no more element on the left, it's the leftmost
+            if pos == 0 {
+                return pos;
+            }
+            
+            // if the left element is not equal, it's the leftmost
+            if compare(&arr[pos - 1], target) != Ordering::Equal {
+                return pos;
+            }
+            
+            // the left element exists and is equal.
+            // there may be many equal elements on the left,
+            // so use a recursive binary search, instead of a linear search.
+            binary_search_leftmost(&arr[0..pos], compare, target)
+        }
+        // equal element not found, return the insertion index
+        Err(pos) => pos
+    }
+}
+
+// the mirror of binary_search_leftmost.
+// it returns the index after the rightmost equal element,
+// which is the insertion index that puts the target after all the elements that equal it.
+pub fn binary_search_rightmost<Element, Comparator>(
+    arr: &[Element], compare: &Comparator, target: &Element,
+) -> usize
+    where
+        Comparator: Fn(&Element, &Element) -> Ordering
+{
+    match arr.binary_search_by(|probe| compare(probe, target)) {
+        Ok(pos) => {
+            // if there are no more element on the right, it's the rightmost
+            if pos + 1 == arr.len() {
+                return pos + 1;
+            }
+            
+            // if the right element is not equal, it's the rightmost
+            if compare(&arr[pos + 1], target) != Ordering::Equal {
+                return pos + 1;
+            }
+            
+            // the right element exists and is equal.
+            // search in the right side recursively, the result is relative to the sub-slice.
+            pos + 1 + binary_search_rightmost(&arr[(pos + 1)..], compare, target)
+        }
+        // equal element not found, return the insertion index
+        Err(pos) => pos
+    }
+}
+
+// The predicate should be true for a prefix of the slice and false for the rest.
+// Returns the length of that prefix, which is the index of the first false, or arr.len() if all true.
+// For a false-then-true predicate, negate it to get the index of the first true.
+// Same as slice::partition_point, O(log n) calls of the predicate.
+// If the predicate is not monotone, the result is a boundary of some true-false change, but not specified which.
+pub fn partition_point<Element>(arr: &[Element], pred: impl Fn(&Element) -> bool) -> usize {
+    // invariant: arr[..low] are all true, arr[high..] are all false
+    let mut low = 0;
+    let mut high = arr.len();
+    
+    while low < high {
+        let mid = midpoint(low, high);
+        if pred(&arr[mid]) {
+            low = mid + 1;
+        } else {
+            high = mid;
+        }
+    }
+    
+    low
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    
+    #[test]
+    fn test_binary_search_leftmost() {
+        let arr = [1, 2, 2, 2, 3, 4, 5, 6, 7, 8, 9];
+        
+        assert_eq!(binary_search_leftmost(&arr, &|a, b| a.cmp(b), &2), 1);
+        assert_eq!(binary_search_leftmost(&arr, &|a, b| a.cmp(b), &3), 4);
+        assert_eq!(binary_search_leftmost(&arr, &|a, b| a.cmp(b), &9), 10);
+        assert_eq!(binary_search_leftmost(&arr, &|a, b| a.cmp(b), &0), 0);
+        assert_eq!(binary_search_leftmost(&arr, &|a, b| a.cmp(b), &10), 11);
+    }
+    
+    #[test]
+    fn test_binary_search_rightmost() {
+        let arr = [1, 2, 2, 2, 3, 4, 5, 6, 7, 8, 9, 9];
+        
+        assert_eq!(binary_search_rightmost(&arr, &|a, b| a.cmp(b), &2), 4);
+        assert_eq!(binary_search_rightmost(&arr, &|a, b| a.cmp(b), &1), 1);
+        assert_eq!(binary_search_rightmost(&arr, &|a, b| a.cmp(b), &9), 12);
+        assert_eq!(binary_search_rightmost(&arr, &|a, b| a.cmp(b), &0), 0);
+        assert_eq!(binary_search_rightmost(&arr, &|a, b| a.cmp(b), &10), 12);
+        
+        let all_equal = [5; 100];
+        assert_eq!(binary_search_rightmost(&all_equal, &|a, b| a.cmp(b), &5), 100);
+        assert_eq!(binary_search_leftmost(&all_equal, &|a, b| a.cmp(b), &5), 0);
+    }
+    
+    #[test]
+    fn test_partition_point() {
+        let arr = [1, 2, 2, 2, 3, 4, 5, 6, 7, 8, 9];
+        
+        // all true
+        assert_eq!(partition_point(&arr, |x| *x < 100), 11);
+        // all false
+        assert_eq!(partition_point(&arr, |x| *x < 0), 0);
+        // the boundary in the middle
+        assert_eq!(partition_point(&arr, |x| *x < 2), 1);
+        assert_eq!(partition_point(&arr, |x| *x <= 2), 4);
+        assert_eq!(partition_point(&arr, |x| *x < 9), 10);
+        // false then true, by negating
+        let is_large = |x: &i32| *x >= 5;
+        assert_eq!(partition_point(&arr, |x| !is_large(x)), 6);
+        
+        let empty: [i32; 0] = [];
+        assert_eq!(partition_point(&empty, |x| *x < 5), 0);
+        
+        // same as std, and consistent with the comparator-based searches
+        let compare = |a: &i32, b: &i32| a.cmp(b);
+        for target in 0..11 {
+            assert_eq!(partition_point(&arr, |x| *x < target), arr.partition_point(|x| *x < target));
+            assert_eq!(partition_point(&arr, |x| compare(x, &target).is_lt()), binary_search_leftmost(&arr, &compare, &target));
+            assert_eq!(partition_point(&arr, |x| compare(x, &target).is_le()), binary_search_rightmost(&arr, &compare, &target));
+        }
+    }
+}
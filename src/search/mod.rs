@@ -0,0 +1,2 @@
+pub mod binary_search;
+pub mod sorted_slice;
@@ -0,0 +1,239 @@
+use std::cmp::Ordering;
+
+use crate::search::binary_search::{binary_search_leftmost, binary_search_rightmost};
+
+// Insert a value into a sorted Vec, keeping it sorted. Returns the index where it's inserted.
+// The value is placed after all the elements that equal it,
+// so equal elements stay in insertion order (it's stable).
+// Finding the position takes O(log n) comparisons, but the insertion itself moves O(n) elements.
+pub fn insert_sorted_vec<Element, Comparator>(
+    v: &mut Vec<Element>, value: Element, compare: &Comparator,
+) -> usize
+    where
+        Comparator: Fn(&Element, &Element) -> Ordering
+{
+    let index = binary_search_rightmost(v.as_slice(), compare, &value);
+    v.insert(index, value);
+    index
+}
+
+// In a sorted Vec, keep only the last element of each run of equal elements.
+// Vec::dedup_by keeps the first one. Keeping the last is useful when the later entries supersede the former ones.
+// It takes O(n).
+pub fn dedup_keep_last<Element, Comparator>(
+    sorted: &mut Vec<Element>, compare: &Comparator,
+)
+    where
+        Comparator: Fn(&Element, &Element) -> Ordering
+{
+    // dedup_by passes (the current one, the last kept one), and removes the current one if it returns true.
+    // swapping them moves the current one into the kept slot, so the last one of the run survives.
+    sorted.dedup_by(|current, kept| {
+        if compare(current, kept).is_eq() {
+            std::mem::swap(current, kept);
+            true
+        } else {
+            false
+        }
+    });
+}
+
+// The number of elements that are less than the value, in a sorted slice.
+// It's also the rank of the value.
+pub fn count_less_than<Element, Comparator>(
+    arr: &[Element], compare: &Comparator, value: &Element,
+) -> usize
+    where
+        Comparator: Fn(&Element, &Element) -> Ordering
+{
+    binary_search_leftmost(arr, compare, value)
+}
+
+// The number of elements that are equal to the value, in a sorted slice.
+pub fn count_equal<Element, Comparator>(
+    arr: &[Element], compare: &Comparator, value: &Element,
+) -> usize
+    where
+        Comparator: Fn(&Element, &Element) -> Ordering
+{
+    binary_search_rightmost(arr, compare, value) - binary_search_leftmost(arr, compare, value)
+}
+
+// Returns the first index i that arr[i] > arr[i + 1], or None if the slice is sorted.
+pub fn first_unsorted_index<Element, Comparator>(
+    arr: &[Element], compare: &Comparator,
+) -> Option<usize>
+    where
+        Comparator: Fn(&Element, &Element) -> Ordering
+{
+    arr.windows(2).position(|pair| compare(&pair[0], &pair[1]) == Ordering::Greater)
+}
+
+pub fn is_sorted<Element, Comparator>(
+    arr: &[Element], compare: &Comparator,
+) -> bool
+    where
+        Comparator: Fn(&Element, &Element) -> Ordering
+{
+    first_unsorted_index(arr, compare).is_none()
+}
+
+// Iterate the maximal runs of consecutive elements that compare Equal, like slice::chunk_by.
+// On a sorted slice, each run contains all elements equal to each other.
+pub fn group_by_comparator<'a, 'c, Element, Comparator>(
+    arr: &'a [Element], compare: &'c Comparator,
+) -> ComparatorGroupIter<'a, 'c, Element, Comparator>
+    where
+        Comparator: Fn(&Element, &Element) -> Ordering
+{
+    ComparatorGroupIter { remaining: arr, compare }
+}
+
+pub struct ComparatorGroupIter<'a, 'c, Element, Comparator> {
+    remaining: &'a [Element],
+    compare: &'c Comparator,
+}
+
+impl<'a, 'c, Element, Comparator> Iterator for ComparatorGroupIter<'a, 'c, Element, Comparator>
+    where
+        Comparator: Fn(&Element, &Element) -> Ordering
+{
+    type Item = &'a [Element];
+    
+    fn next(&mut self) -> Option<&'a [Element]> {
+        if self.remaining.is_empty() {
+            return None;
+        }
+        
+        let group_len = self.remaining.windows(2)
+            .position(|pair| (self.compare)(&pair[0], &pair[1]) != Ordering::Equal)
+            .map_or(self.remaining.len(), |index| index + 1);
+        
+        let (group, rest) = self.remaining.split_at(group_len);
+        self.remaining = rest;
+        Some(group)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::{Rng, SeedableRng};
+    use rand::prelude::{SliceRandom, StdRng};
+    
+    use super::*;
+    
+    #[test]
+    fn test_insert_sorted_vec() {
+        let mut rng: StdRng = SeedableRng::seed_from_u64(123456);
+        
+        for _i in 0..100 {
+            let len = rng.gen_range(0..500);
+            let max = rng.gen_range(1..50);
+            let mut keys: Vec<i32> = (0..len).map(|_| rng.gen_range(0..max)).collect();
+            keys.shuffle(&mut rng);
+            
+            // (key, insertion order)
+            let mut v: Vec<(i32, usize)> = Vec::new();
+            for (tag, key) in keys.iter().enumerate() {
+                insert_sorted_vec(&mut v, (*key, tag), &|a, b| a.0.cmp(&b.0));
+            }
+            
+            // sorted by key, and equal keys are in insertion order
+            for i in 1..v.len() {
+                assert!(v[i - 1] < v[i]);
+            }
+            
+            keys.sort();
+            assert_eq!(v.iter().map(|e| e.0).collect::<Vec<i32>>(), keys);
+        }
+    }
+    
+    #[test]
+    fn test_dedup_keep_last() {
+        let mut rng: StdRng = SeedableRng::seed_from_u64(123456);
+        
+        for _i in 0..100 {
+            // (key, tag), only key is compared
+            let len = rng.gen_range(0..500);
+            let max = rng.gen_range(1..50);
+            let mut vec: Vec<(i32, usize)> = (0..len).map(|tag| (rng.gen_range(0..max), tag)).collect();
+            // stable sort, so the tags are ascending within each run
+            vec.sort_by_key(|e| e.0);
+            
+            let mut expected: Vec<(i32, usize)> = Vec::new();
+            for e in vec.iter() {
+                match expected.last_mut() {
+                    Some(last) if last.0 == e.0 => *last = *e,
+                    _ => expected.push(*e),
+                }
+            }
+            
+            dedup_keep_last(&mut vec, &|a: &(i32, usize), b: &(i32, usize)| a.0.cmp(&b.0));
+            assert_eq!(vec, expected);
+        }
+        
+        let mut vec = vec![(1, 'a'), (1, 'b'), (2, 'c'), (3, 'd'), (3, 'e'), (3, 'f')];
+        dedup_keep_last(&mut vec, &|a: &(i32, char), b: &(i32, char)| a.0.cmp(&b.0));
+        assert_eq!(vec, vec![(1, 'b'), (2, 'c'), (3, 'f')]);
+    }
+    
+    #[test]
+    fn test_count_less_than_and_count_equal() {
+        let compare = |a: &i32, b: &i32| a.cmp(b);
+        let arr = [1, 2, 2, 2, 5, 5, 7];
+        
+        assert_eq!(count_less_than(&arr, &compare, &2), 1);
+        assert_eq!(count_equal(&arr, &compare, &2), 3);
+        assert_eq!(count_less_than(&arr, &compare, &5), 4);
+        assert_eq!(count_equal(&arr, &compare, &5), 2);
+        assert_eq!(count_less_than(&arr, &compare, &3), 4);
+        assert_eq!(count_equal(&arr, &compare, &3), 0);
+        
+        // outside the range
+        assert_eq!(count_less_than(&arr, &compare, &0), 0);
+        assert_eq!(count_equal(&arr, &compare, &0), 0);
+        assert_eq!(count_less_than(&arr, &compare, &100), 7);
+        assert_eq!(count_equal(&arr, &compare, &100), 0);
+        
+        let empty: [i32; 0] = [];
+        assert_eq!(count_less_than(&empty, &compare, &1), 0);
+        assert_eq!(count_equal(&empty, &compare, &1), 0);
+        
+        let mut rng: StdRng = SeedableRng::seed_from_u64(123456);
+        for _i in 0..100 {
+            let len = rng.gen_range(0..500);
+            let max = rng.gen_range(1..50);
+            let mut arr: Vec<i32> = (0..len).map(|_| rng.gen_range(0..max)).collect();
+            arr.sort();
+            
+            let value = rng.gen_range(-1..=max);
+            assert_eq!(count_less_than(&arr, &compare, &value), arr.iter().filter(|x| **x < value).count());
+            assert_eq!(count_equal(&arr, &compare, &value), arr.iter().filter(|x| **x == value).count());
+        }
+    }
+    
+    #[test]
+    fn test_is_sorted() {
+        let compare = |a: &i32, b: &i32| a.cmp(b);
+        assert!(is_sorted(&[] as &[i32], &compare));
+        assert!(is_sorted(&[1], &compare));
+        assert!(is_sorted(&[1, 1, 2, 3, 3], &compare));
+        assert_eq!(first_unsorted_index(&[1, 2, 5, 4, 3], &compare), Some(2));
+        assert!(!is_sorted(&[2, 1], &compare));
+    }
+    
+    #[test]
+    fn test_group_by_comparator() {
+        let compare = |a: &(i32, char), b: &(i32, char)| a.0.cmp(&b.0);
+        
+        let arr = [(1, 'a'), (1, 'b'), (2, 'c'), (3, 'd'), (3, 'e'), (3, 'f'), (5, 'g')];
+        let groups: Vec<&[(i32, char)]> = group_by_comparator(&arr, &compare).collect();
+        assert_eq!(groups, vec![&arr[0..2], &arr[2..3], &arr[3..6], &arr[6..7]]);
+        
+        assert_eq!(group_by_comparator(&[] as &[(i32, char)], &compare).count(), 0);
+        
+        let all_equal = [(4, 'a'), (4, 'b'), (4, 'c')];
+        let groups: Vec<&[(i32, char)]> = group_by_comparator(&all_equal, &compare).collect();
+        assert_eq!(groups, vec![&all_equal[..]]);
+    }
+}
@@ -0,0 +1,98 @@
+use std::cmp::Ordering;
+
+// Find the minimum and maximum in one pass.
+// It processes elements in pairs: compare the two elements in pair first,
+// then only compare the smaller one with the current minimum and the larger one with the current maximum.
+// It takes about 3n/2 comparisons, instead of 2n comparisons in two separate scans.
+// Same as std's min_by and max_by, if there are multiple equal minimums, it returns the first one,
+// if there are multiple equal maximums, it returns the last one.
+// Returns None if the array is empty.
+pub fn min_max<'a, Element, Comparator>(
+    arr: &'a [Element], compare: &Comparator,
+) -> Option<(&'a Element, &'a Element)>
+    where
+        Comparator: Fn(&Element, &Element) -> Ordering
+{
+    let len = arr.len();
+    if len == 0 {
+        return None;
+    }
+    
+    // if the length is odd, the first element initializes both.
+    // if the length is even, the first pair initializes them.
+    // the remaining elements can be paired.
+    let (mut min, mut max, start) = if len % 2 == 1 {
+        (&arr[0], &arr[0], 1)
+    } else {
+        let (smaller, larger) = order_pair(&arr[0], &arr[1], compare);
+        (smaller, larger, 2)
+    };
+    
+    for pair in arr[start..].chunks_exact(2) {
+        let (smaller, larger) = order_pair(&pair[0], &pair[1], compare);
+        
+        if compare(smaller, min) == Ordering::Less {
+            min = smaller;
+        }
+        
+        if compare(larger, max) != Ordering::Less {
+            max = larger;
+        }
+    }
+    
+    Some((min, max))
+}
+
+// returns (smaller, larger). if they are equal, the first one is considered smaller.
+fn order_pair<'a, Element, Comparator>(
+    a: &'a Element, b: &'a Element, compare: &Comparator,
+) -> (&'a Element, &'a Element)
+    where
+        Comparator: Fn(&Element, &Element) -> Ordering
+{
+    if compare(b, a) == Ordering::Less {
+        (b, a)
+    } else {
+        (a, b)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::{Rng, SeedableRng};
+    use rand::prelude::StdRng;
+    
+    use super::*;
+    
+    #[test]
+    fn test_min_max() {
+        let mut rng: StdRng = SeedableRng::seed_from_u64(123456);
+        
+        for _i in 0..1000 {
+            let len = rng.gen_range(1..200);
+            let max = rng.gen_range(1..50);
+            
+            // (key, index), only key is compared
+            let arr: Vec<(i32, usize)> = (0..len).map(|i| (rng.gen_range(0..max), i)).collect();
+            let compare = |a: &(i32, usize), b: &(i32, usize)| a.0.cmp(&b.0);
+            
+            let (min, max) = min_max(&arr, &compare).unwrap();
+            
+            // it should also pick the same one as std among equal elements
+            assert_eq!(min, arr.iter().min_by(|a, b| compare(a, b)).unwrap());
+            assert_eq!(max, arr.iter().max_by(|a, b| compare(a, b)).unwrap());
+        }
+    }
+    
+    #[test]
+    fn test_min_max_special_cases() {
+        let empty: [i32; 0] = [];
+        assert_eq!(min_max(&empty, &|a: &i32, b: &i32| a.cmp(b)), None);
+        
+        let single = [3];
+        assert_eq!(min_max(&single, &|a: &i32, b: &i32| a.cmp(b)), Some((&3, &3)));
+        
+        let two = [5, 2];
+        assert_eq!(min_max(&two, &|a: &i32, b: &i32| a.cmp(b)), Some((&2, &5)));
+    }
+}
@@ -0,0 +1,2 @@
+pub mod min_max;
+pub mod quick_select;
@@ -0,0 +1,420 @@
+use std::cmp::Ordering;
+use std::ops::Range;
+use std::sync::atomic::AtomicBool;
+
+use crate::quick_sort::partition::{fat_partition_cancellable, fat_partition_no_clone_required};
+use crate::quick_sort::pivot_select::{median_of_medians_pivot, median_of_three_pivot};
+use crate::quick_sort::simple_quick_sort::normal_quick_sort;
+use crate::util::cancellation::{Cancelled, check_cancelled, without_cancellation};
+
+// Quick select. After it, arr[k] is the element that would be at index k if the array is sorted,
+// and arr[..k] <= arr[k] <= arr[(k+1)..].
+// Unlike LazyQuickSorter, it doesn't keep the partition tree, and it's iterative.
+// Average time complexity is O(n).
+pub fn quick_select<Element, Comparator>(
+    arr: &mut [Element], k: usize, compare: &Comparator,
+)
+    where
+        Comparator: Fn(&Element, &Element) -> Ordering
+{
+    without_cancellation(|cancel| quick_select_cancellable(arr, k, compare, cancel));
+}
+
+// Same as quick_select, but it checks the cancel flag before and during each partition.
+pub fn quick_select_cancellable<Element, Comparator>(
+    arr: &mut [Element], k: usize, compare: &Comparator, cancel: &AtomicBool,
+) -> Result<(), Cancelled>
+    where
+        Comparator: Fn(&Element, &Element) -> Ordering
+{
+    assert!(k < arr.len());
+    
+    // the k-th element is in arr[range_left..range_right_exclusive]
+    let mut range_left = 0;
+    let mut range_right_exclusive = arr.len();
+    
+    loop {
+        let range = &mut arr[range_left..range_right_exclusive];
+        
+        if range.len() <= 2 {
+            normal_quick_sort(range, compare);
+            return Ok(());
+        }
+        
+        check_cancelled(cancel)?;
+        
+        let pivot_index = median_of_three_pivot(range, compare);
+        let (l, r) = fat_partition_cancellable(range, compare, pivot_index, cancel)?;
+        
+        if k < range_left + l {
+            range_right_exclusive = range_left + l;
+        } else if k >= range_left + r {
+            range_left += r;
+        } else {
+            // it's in the "equal" region
+            return Ok(());
+        }
+    }
+}
+
+// Introselect. Same as quick_select, but when it iterates too many times
+// (which means the median of three pivots are bad, possibly by adversarial input),
+// it switches to median of medians pivot, to guarantee O(n) in the worst case.
+// This mirrors introsort for selection.
+pub fn introselect<Element, Comparator>(
+    arr: &mut [Element], k: usize, compare: &Comparator,
+)
+    where
+        Comparator: Fn(&Element, &Element) -> Ordering
+{
+    assert!(k < arr.len());
+    
+    // for good pivots, the range shrinks geometrically, so it iterates O(log n) times
+    let depth_limit = 2 * (usize::BITS - arr.len().leading_zeros()) as usize;
+    let mut depth = 0;
+    
+    // the k-th element is in arr[range_left..range_right_exclusive]
+    let mut range_left = 0;
+    let mut range_right_exclusive = arr.len();
+    
+    loop {
+        let range = &mut arr[range_left..range_right_exclusive];
+        
+        if range.len() <= 2 {
+            normal_quick_sort(range, compare);
+            return;
+        }
+        
+        let pivot_index = if depth < depth_limit {
+            median_of_three_pivot(range, compare)
+        } else {
+            median_of_medians_pivot(range, compare)
+        };
+        depth += 1;
+        
+        let (l, r) = fat_partition_no_clone_required(range, compare, pivot_index);
+        
+        if k < range_left + l {
+            range_right_exclusive = range_left + l;
+        } else if k >= range_left + r {
+            range_left += r;
+        } else {
+            // it's in the "equal" region
+            return;
+        }
+    }
+}
+
+// Same as quick_select, and also returns the number of elements equal to the selected one,
+// which is useful for things like weighted median.
+// Each iteration only keeps the part that is strictly less or strictly greater than the pivot,
+// so the elements outside the current range are never equal to the ones inside.
+// When k falls in the equal region of the fat partition, that region contains all the equal elements,
+// and they are contiguous around k.
+pub fn select_nth_with_multiplicity<'a, Element, Comparator>(
+    arr: &'a mut [Element], k: usize, compare: &Comparator,
+) -> (usize, &'a Element)
+    where
+        Comparator: Fn(&Element, &Element) -> Ordering
+{
+    assert!(k < arr.len());
+    
+    // the k-th element is in arr[range_left..range_right_exclusive]
+    let mut range_left = 0;
+    let mut range_right_exclusive = arr.len();
+    
+    loop {
+        let range = &mut arr[range_left..range_right_exclusive];
+        
+        if range.len() <= 2 {
+            normal_quick_sort(range, compare);
+            let selected = &range[k - range_left];
+            let count = range.iter().filter(|e| compare(e, selected) == Ordering::Equal).count();
+            return (count, &arr[k]);
+        }
+        
+        let pivot_index = median_of_three_pivot(range, compare);
+        let (l, r) = fat_partition_no_clone_required(range, compare, pivot_index);
+        
+        if k < range_left + l {
+            range_right_exclusive = range_left + l;
+        } else if k >= range_left + r {
+            range_left += r;
+        } else {
+            // it's in the "equal" region
+            return (r - l, &arr[k]);
+        }
+    }
+}
+
+// Make arr[(k-window)..=(k+window)] sorted and at the same positions as in the fully sorted array.
+// (the window is clamped to the array bounds)
+// The elements on the left of the window are <= the window, and the elements on the right are >= the window,
+// but they are not sorted.
+pub fn sort_around_rank<Element, Comparator>(
+    arr: &mut [Element], k: usize, window: usize, compare: &Comparator,
+)
+    where
+        Comparator: Fn(&Element, &Element) -> Ordering
+{
+    let len = arr.len();
+    assert!(k < len);
+    
+    let window_start = k.saturating_sub(window);
+    // the window may be as large as usize::MAX to mean the whole array, so don't overflow
+    let window_end_exclusive = k.saturating_add(window).saturating_add(1).min(len);
+    
+    // now arr[..window_start] <= arr[window_start] <= arr[window_start..]
+    quick_select(arr, window_start, compare);
+    
+    // now arr[window_start..(window_end_exclusive - 1)] <= arr[window_end_exclusive - 1] <= arr[window_end_exclusive..]
+    quick_select(&mut arr[window_start..], window_end_exclusive - 1 - window_start, compare);
+    
+    // the window contains the right elements, sort it
+    normal_quick_sort(&mut arr[window_start..window_end_exclusive], compare);
+}
+
+// Rearrange the array into n contiguous buckets. Every element in bucket i is <= every element in bucket i+1.
+// The sizes of buckets differ by at most 1. The elements inside a bucket are not sorted.
+// It selects the middle boundary, then recurses on both sides,
+// so it takes O(len * log n) on average, less than a full sort.
+pub fn quantile_buckets<Element, Comparator>(
+    arr: &mut [Element], n: usize, compare: &Comparator,
+) -> Vec<Range<usize>>
+    where
+        Comparator: Fn(&Element, &Element) -> Ordering
+{
+    without_cancellation(|cancel| quantile_buckets_cancellable(arr, n, compare, cancel))
+}
+
+// Same as quantile_buckets, but the selections check the cancel flag.
+pub fn quantile_buckets_cancellable<Element, Comparator>(
+    arr: &mut [Element], n: usize, compare: &Comparator, cancel: &AtomicBool,
+) -> Result<Vec<Range<usize>>, Cancelled>
+    where
+        Comparator: Fn(&Element, &Element) -> Ordering
+{
+    assert!(n > 0);
+    
+    let len = arr.len();
+    let boundaries: Vec<usize> = (0..=n).map(|i| i * len / n).collect();
+    
+    // the first and last boundaries are 0 and len, no need to select
+    select_boundaries(arr, &boundaries[1..n], 0, compare, cancel)?;
+    
+    Ok(boundaries.windows(2).map(|w| w[0]..w[1]).collect())
+}
+
+// make every boundary b satisfy arr[..b] <= arr[b..]
+// arr is a part of the whole array, starting at offset
+fn select_boundaries<Element, Comparator>(
+    arr: &mut [Element], boundaries: &[usize], offset: usize, compare: &Comparator, cancel: &AtomicBool,
+) -> Result<(), Cancelled>
+    where
+        Comparator: Fn(&Element, &Element) -> Ordering
+{
+    if boundaries.is_empty() {
+        return Ok(());
+    }
+    
+    let mid = boundaries.len() / 2;
+    let split = boundaries[mid] - offset;
+    if split < arr.len() {
+        quick_select_cancellable(arr, split, compare, cancel)?;
+    }
+    
+    let (left, right) = arr.split_at_mut(split);
+    select_boundaries(left, &boundaries[..mid], offset, compare, cancel)?;
+    select_boundaries(right, &boundaries[(mid + 1)..], offset + split, compare, cancel)
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::{Rng, SeedableRng};
+    use rand::prelude::StdRng;
+    
+    use crate::util::test_util::{count_comparisons, generate_killer_input};
+    
+    use super::*;
+    
+    #[test]
+    fn test_quick_select() {
+        let mut rng: StdRng = SeedableRng::seed_from_u64(123456);
+        
+        for _i in 0..1000 {
+            let len = rng.gen_range(1..1000);
+            let max = rng.gen_range(1..1000);
+            let mut vec: Vec<i32> = (0..len).map(|_| rng.gen_range(0..max)).collect();
+            let mut vec_for_ref = vec.clone();
+            vec_for_ref.sort();
+            
+            let k = rng.gen_range(0..len);
+            quick_select(&mut vec, k, &|a: &i32, b: &i32| a.cmp(b));
+            
+            assert_eq!(vec[k], vec_for_ref[k]);
+            assert!(vec[..k].iter().all(|x| *x <= vec[k]));
+            assert!(vec[(k + 1)..].iter().all(|x| *x >= vec[k]));
+        }
+    }
+    
+    #[test]
+    fn test_select_nth_with_multiplicity() {
+        let mut rng: StdRng = SeedableRng::seed_from_u64(123456);
+        
+        for _i in 0..1000 {
+            // few distinct values, so that there are many duplicates
+            let len = rng.gen_range(1..1000);
+            let max = rng.gen_range(1..20);
+            let mut vec: Vec<i32> = (0..len).map(|_| rng.gen_range(0..max)).collect();
+            let mut vec_for_ref = vec.clone();
+            vec_for_ref.sort();
+            
+            let k = rng.gen_range(0..len);
+            let (count, selected) = select_nth_with_multiplicity(&mut vec, k, &|a: &i32, b: &i32| a.cmp(b));
+            let selected = *selected;
+            
+            assert_eq!(selected, vec_for_ref[k]);
+            assert_eq!(count, vec_for_ref.iter().filter(|x| **x == selected).count());
+            assert_eq!(vec[k], selected);
+            assert!(vec[..k].iter().all(|x| *x <= vec[k]));
+            assert!(vec[(k + 1)..].iter().all(|x| *x >= vec[k]));
+        }
+        
+        let mut vec = vec![3, 1, 3, 2, 3, 3, 1];
+        assert_eq!(select_nth_with_multiplicity(&mut vec, 0, &|a: &i32, b: &i32| a.cmp(b)), (2, &1));
+        assert_eq!(select_nth_with_multiplicity(&mut vec, 2, &|a: &i32, b: &i32| a.cmp(b)), (1, &2));
+        assert_eq!(select_nth_with_multiplicity(&mut vec, 6, &|a: &i32, b: &i32| a.cmp(b)), (4, &3));
+        assert_eq!(select_nth_with_multiplicity(&mut [5], 0, &|a: &i32, b: &i32| a.cmp(b)), (1, &5));
+    }
+    
+    #[test]
+    fn test_introselect() {
+        let mut rng: StdRng = SeedableRng::seed_from_u64(123456);
+        
+        for _i in 0..1000 {
+            let len = rng.gen_range(1..1000);
+            let max = rng.gen_range(1..1000);
+            let mut vec: Vec<i32> = (0..len).map(|_| rng.gen_range(0..max)).collect();
+            let mut vec_for_ref = vec.clone();
+            vec_for_ref.sort();
+            
+            let k = rng.gen_range(0..len);
+            introselect(&mut vec, k, &|a: &i32, b: &i32| a.cmp(b));
+            
+            assert_eq!(vec[k], vec_for_ref[k]);
+            assert!(vec[..k].iter().all(|x| *x <= vec[k]));
+            assert!(vec[(k + 1)..].iter().all(|x| *x >= vec[k]));
+        }
+    }
+    
+    #[test]
+    fn test_introselect_killer_input() {
+        let len = 5000;
+        let k = len / 2;
+        
+        let quick_select_killer = generate_killer_input(len, |arr, compare| quick_select(arr, k, &compare));
+        let quick_select_comparisons = count_comparisons(&quick_select_killer, |arr, compare| quick_select(arr, k, &compare));
+        let introselect_comparisons_on_quick_select_killer =
+            count_comparisons(&quick_select_killer, |arr, compare| introselect(arr, k, &compare));
+        
+        let introselect_killer = generate_killer_input(len, |arr, compare| introselect(arr, k, &compare));
+        let mut introselect_killer_copy = introselect_killer.clone();
+        let introselect_comparisons =
+            count_comparisons(&introselect_killer, |arr, compare| introselect(arr, k, &compare));
+        
+        // quadratic
+        assert!(
+            quick_select_comparisons > len * len / 10,
+            "quick select on its killer input: {} comparisons", quick_select_comparisons
+        );
+        
+        // linear
+        assert!(
+            introselect_comparisons_on_quick_select_killer < 50 * len,
+            "introselect on quick select killer input: {} comparisons", introselect_comparisons_on_quick_select_killer
+        );
+        assert!(
+            introselect_comparisons < 50 * len,
+            "introselect on its killer input: {} comparisons", introselect_comparisons
+        );
+        
+        introselect(&mut introselect_killer_copy, k, &|a: &usize, b: &usize| a.cmp(b));
+        assert_eq!(introselect_killer_copy[k], k);
+    }
+    
+    #[test]
+    fn test_sort_around_rank() {
+        let mut rng: StdRng = SeedableRng::seed_from_u64(123456);
+        
+        for _i in 0..1000 {
+            let len = rng.gen_range(1..1000);
+            let max = rng.gen_range(1..1000);
+            let mut vec: Vec<i32> = (0..len).map(|_| rng.gen_range(0..max)).collect();
+            let mut vec_for_ref = vec.clone();
+            vec_for_ref.sort();
+            
+            let k = rng.gen_range(0..len);
+            let window = rng.gen_range(0..20);
+            sort_around_rank(&mut vec, k, window, &|a: &i32, b: &i32| a.cmp(b));
+            
+            let window_range = k.saturating_sub(window)..k.saturating_add(window).saturating_add(1).min(len);
+            assert_eq!(vec[window_range.clone()], vec_for_ref[window_range.clone()]);
+            
+            let window_min = vec[window_range.start];
+            let window_max = vec[window_range.end - 1];
+            assert!(vec[..window_range.start].iter().all(|x| *x <= window_min));
+            assert!(vec[window_range.end..].iter().all(|x| *x >= window_max));
+        }
+        
+        // a huge window sorts the whole array
+        let mut vec: Vec<i32> = (0..1000).map(|_| rng.gen_range(0..1000)).collect();
+        let mut vec_for_ref = vec.clone();
+        vec_for_ref.sort();
+        sort_around_rank(&mut vec, 500, usize::MAX, &|a: &i32, b: &i32| a.cmp(b));
+        assert_eq!(vec, vec_for_ref);
+        
+        // the elements far away from the window are not sorted
+        let mut vec: Vec<i32> = (0..10000).map(|_| rng.gen_range(0..1000000)).collect();
+        sort_around_rank(&mut vec, 5000, 10, &|a: &i32, b: &i32| a.cmp(b));
+        assert!(vec[..4990].windows(2).any(|w| w[0] > w[1]));
+        assert!(vec[5011..].windows(2).any(|w| w[0] > w[1]));
+    }
+    
+    #[test]
+    fn test_quantile_buckets() {
+        let mut rng: StdRng = SeedableRng::seed_from_u64(123456);
+        
+        for _i in 0..300 {
+            let len = rng.gen_range(0..1000);
+            let max = rng.gen_range(1..1000);
+            let n = rng.gen_range(1..20);
+            let mut vec: Vec<i32> = (0..len).map(|_| rng.gen_range(0..max)).collect();
+            let mut vec_for_ref = vec.clone();
+            
+            let buckets = quantile_buckets(&mut vec, n, &|a: &i32, b: &i32| a.cmp(b));
+            
+            assert_eq!(buckets.len(), n);
+            assert_eq!(buckets[0].start, 0);
+            assert_eq!(buckets[n - 1].end, len);
+            for pair in buckets.windows(2) {
+                assert_eq!(pair[0].end, pair[1].start);
+                
+                let left_max = vec[pair[0].clone()].iter().max();
+                let right_min = vec[pair[1].clone()].iter().min();
+                if let (Some(left_max), Some(right_min)) = (left_max, right_min) {
+                    assert!(left_max <= right_min);
+                }
+            }
+            
+            // near-equal size
+            let min_size = buckets.iter().map(|r| r.len()).min().unwrap();
+            let max_size = buckets.iter().map(|r| r.len()).max().unwrap();
+            assert!(max_size - min_size <= 1);
+            
+            // it's a permutation of the original
+            vec.sort();
+            vec_for_ref.sort();
+            assert_eq!(vec, vec_for_ref);
+        }
+    }
+}
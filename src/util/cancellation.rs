@@ -0,0 +1,40 @@
+use std::sync::atomic::{AtomicBool, Ordering as AtomicOrdering};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Cancelled;
+
+// The cancellable sorts check the flag at each recursion or partition step, by `check_cancelled(cancel)?`.
+// The flag is set by another thread, so it's only read, and Relaxed is enough.
+// After a cancelled sort, the array is a permutation of the input, but possibly unsorted.
+pub fn check_cancelled(cancel: &AtomicBool) -> Result<(), Cancelled> {
+    if cancel.load(AtomicOrdering::Relaxed) {
+        Err(Cancelled)
+    } else {
+        Ok(())
+    }
+}
+
+// Run a cancellable function with a flag that is never set.
+// The non-cancellable sorts share the implementation with the cancellable ones in this way.
+pub fn without_cancellation<T>(f: impl FnOnce(&AtomicBool) -> Result<T, Cancelled>) -> T {
+    let cancel = AtomicBool::new(false);
+    match f(&cancel) {
+        Ok(result) => result,
+        Err(Cancelled) => unreachable!("the flag is never set"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    
+    #[test]
+    fn test_check_cancelled() {
+        let cancel = AtomicBool::new(false);
+        assert_eq!(check_cancelled(&cancel), Ok(()));
+        cancel.store(true, AtomicOrdering::Relaxed);
+        assert_eq!(check_cancelled(&cancel), Err(Cancelled));
+        
+        assert_eq!(without_cancellation(|cancel| check_cancelled(cancel).map(|_| 1)), 1);
+    }
+}
@@ -0,0 +1,56 @@
+use std::cmp::Ordering;
+
+use crate::quick_sort::simple_quick_sort::normal_quick_sort;
+
+// Compose two comparators. When the primary comparator returns Equal, the secondary comparator decides.
+// The composed comparator can be passed to any sort in this crate.
+pub fn then_compare<'a, Element, Primary, Secondary>(
+    primary: &'a Primary, secondary: &'a Secondary,
+) -> impl Fn(&Element, &Element) -> Ordering + 'a
+    where
+        Primary: Fn(&Element, &Element) -> Ordering,
+        Secondary: Fn(&Element, &Element) -> Ordering,
+{
+    move |a: &Element, b: &Element| primary(a, b).then_with(|| secondary(a, b))
+}
+
+// Sort by the primary comparator, with ties broken by the secondary comparator.
+// It doesn't rely on stability, so it can use the (unstable) quick sort.
+pub fn sort_with_tiebreak<Element, Primary, Secondary>(
+    arr: &mut [Element], primary: &Primary, secondary: &Secondary,
+)
+    where
+        Primary: Fn(&Element, &Element) -> Ordering,
+        Secondary: Fn(&Element, &Element) -> Ordering,
+{
+    normal_quick_sort(arr, &then_compare(primary, secondary));
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::{Rng, SeedableRng};
+    use rand::prelude::StdRng;
+    
+    use super::*;
+    
+    #[test]
+    fn test_sort_with_tiebreak() {
+        let mut rng: StdRng = SeedableRng::seed_from_u64(123456);
+        
+        for _i in 0..100 {
+            let len = rng.gen_range(0..1000);
+            let mut arr: Vec<(i32, i32)> = (0..len).map(|_| (rng.gen_range(0..20), rng.gen_range(0..100))).collect();
+            let mut arr_for_ref = arr.clone();
+            
+            // primary key ascending, secondary key descending
+            sort_with_tiebreak(
+                &mut arr,
+                &|a: &(i32, i32), b: &(i32, i32)| a.0.cmp(&b.0),
+                &|a: &(i32, i32), b: &(i32, i32)| b.1.cmp(&a.1),
+            );
+            arr_for_ref.sort_by(|a, b| a.0.cmp(&b.0).then(b.1.cmp(&a.1)));
+            
+            assert_eq!(arr, arr_for_ref);
+        }
+    }
+}
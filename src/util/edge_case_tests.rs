@@ -0,0 +1,184 @@
+// Edge cases for all the public sorts and selections:
+// empty, single element, two elements, and all-equal arrays.
+
+use std::cmp::Ordering;
+
+use crate::algo::permutation::{argsort, sort_with_indices};
+use crate::data_structure::binary_heap::{heap_sort, is_min_heap, make_heap_in_place, sorted_iter};
+use crate::data_structure::linked_list::MyLinkedList;
+use crate::insertion_sort::bounded_displacement_sort::bounded_displacement_sort;
+use crate::insertion_sort::simple_insertion_sort::insertion_sort;
+use crate::merge_sort::concurrent_merge_sort::{BaseSort, concurrent_merge_sort_with_options, SortStability};
+use crate::merge_sort::simple_merge_sort::{bottom_up_merge_sort_inplace, merge_sort_count_inversions, natural_merge_sort_inplace, simple_merge_sort_inplace, simple_merge_sort_inplace_with_cutoff};
+use crate::merge_sort::tim_sort::tim_sort;
+use crate::quick_sort::lazy_quick_sort::LazyQuickSorter;
+use crate::quick_sort::parallel_bucket_sort::parallel_bucket_sort;
+use crate::quick_sort::parallel_quick_sort::parallel_quick_sort_with_cutoff;
+use crate::quick_sort::partition::{fat_partition, fat_partition_checked, fat_partition_no_clone_required, hoare_partition, lomuto_partition};
+use crate::quick_sort::simple_quick_sort::{adaptive_quick_sort, intro_sort, normal_quick_sort, quick_sort_debug};
+use crate::select::min_max::min_max;
+use crate::select::quick_select::{introselect, quantile_buckets, quick_select, sort_around_rank};
+use crate::util::comparator::sort_with_tiebreak;
+use crate::util::test_util::{verify_partition_fat, verify_partition_hoare, verify_partition_lomuto};
+
+fn edge_case_inputs() -> Vec<Vec<i32>> {
+    vec![
+        vec![],
+        vec![5],
+        vec![1, 2],
+        vec![2, 1],
+        vec![3, 3],
+        vec![1, 1, 1],
+        vec![7; 10],
+        vec![7; 1000],
+    ]
+}
+
+fn compare(a: &i32, b: &i32) -> Ordering {
+    a.cmp(b)
+}
+
+type CompareFn = fn(&i32, &i32) -> Ordering;
+type FatPartitionFn = fn(&mut [i32], &CompareFn, usize) -> (usize, usize);
+
+fn sorted(arr: &[i32]) -> Vec<i32> {
+    let mut result = arr.to_vec();
+    result.sort();
+    result
+}
+
+fn check_in_place_sort(name: &str, sort: impl Fn(&mut [i32])) {
+    for input in edge_case_inputs() {
+        let mut vec = input.clone();
+        sort(&mut vec);
+        assert_eq!(vec, sorted(&input), "{} failed on {:?}", name, input);
+    }
+}
+
+#[test]
+fn test_in_place_sorts_edge_cases() {
+    check_in_place_sort("normal_quick_sort", |arr| normal_quick_sort(arr, &compare));
+    check_in_place_sort("adaptive_quick_sort", |arr| adaptive_quick_sort(arr, &compare));
+    check_in_place_sort("intro_sort", |arr| intro_sort(arr, &compare));
+    check_in_place_sort("quick_sort_debug", |arr| quick_sort_debug(arr, &compare));
+    check_in_place_sort("parallel_bucket_sort", |arr| parallel_bucket_sort(arr, &compare, 4));
+    check_in_place_sort("parallel_quick_sort_with_cutoff", |arr| parallel_quick_sort_with_cutoff(arr, &compare, 0));
+    check_in_place_sort("simple_merge_sort_inplace", |arr| simple_merge_sort_inplace(arr, &compare));
+    check_in_place_sort("simple_merge_sort_inplace_with_cutoff", |arr| {
+        simple_merge_sort_inplace_with_cutoff(arr, &compare, 0)
+    });
+    check_in_place_sort("bottom_up_merge_sort_inplace", |arr| bottom_up_merge_sort_inplace(arr, &compare));
+    check_in_place_sort("natural_merge_sort_inplace", |arr| natural_merge_sort_inplace(arr, &compare));
+    check_in_place_sort("merge_sort_count_inversions", |arr| { merge_sort_count_inversions(arr, &compare); });
+    check_in_place_sort("concurrent_merge_sort_with_options", |arr| {
+        concurrent_merge_sort_with_options(arr, &compare, 4, 1, SortStability::Stable, BaseSort::Merge)
+    });
+    check_in_place_sort("tim_sort", |arr| tim_sort(arr, &compare));
+    check_in_place_sort("heap_sort", |arr| heap_sort(arr, &compare));
+    check_in_place_sort("insertion_sort", |arr| insertion_sort(arr, &compare));
+    check_in_place_sort("bounded_displacement_sort", |arr| bounded_displacement_sort(arr, &compare, arr.len()));
+    check_in_place_sort("sort_with_tiebreak", |arr| sort_with_tiebreak(arr, &compare, &compare));
+    check_in_place_sort("LazyQuickSorter::sort_all", |arr| LazyQuickSorter::new(arr, &compare).sort_all());
+    check_in_place_sort("MyLinkedList::sort_by", |arr| {
+        let mut list = MyLinkedList::new();
+        arr.iter().for_each(|x| { list.push_back(*x); });
+        list.sort_by(&compare);
+        let result: Vec<i32> = list.iter().copied().collect();
+        arr.copy_from_slice(&result);
+    });
+    check_in_place_sort("MyLinkedList::merge_sort_by", |arr| {
+        let mut list = MyLinkedList::new();
+        arr.iter().for_each(|x| { list.push_back(*x); });
+        list.merge_sort_by(&compare);
+        let result: Vec<i32> = list.iter().copied().collect();
+        arr.copy_from_slice(&result);
+    });
+}
+
+#[test]
+fn test_non_mutating_sorts_edge_cases() {
+    for input in edge_case_inputs() {
+        let expected = sorted(&input);
+        
+        let result: Vec<i32> = sorted_iter(&input, &compare).copied().collect();
+        assert_eq!(result, expected, "sorted_iter failed on {:?}", input);
+        
+        let result: Vec<i32> = argsort(&input, &compare).into_iter().map(|i| input[i]).collect();
+        assert_eq!(result, expected, "argsort failed on {:?}", input);
+        
+        let result: Vec<i32> = sort_with_indices(&input, &compare).into_iter().map(|(_, e)| *e).collect();
+        assert_eq!(result, expected, "sort_with_indices failed on {:?}", input);
+        
+        let mut heap = input.clone();
+        make_heap_in_place(&mut heap, &compare);
+        assert!(is_min_heap(&heap, &compare), "make_heap_in_place failed on {:?}", input);
+    }
+}
+
+#[test]
+fn test_selections_edge_cases() {
+    for input in edge_case_inputs() {
+        let expected = sorted(&input);
+        
+        assert_eq!(
+            min_max(&input, &compare).map(|(min, max)| (*min, *max)),
+            expected.first().copied().zip(expected.last().copied()),
+            "min_max failed on {:?}", input
+        );
+        
+        for n in 1..4 {
+            let mut vec = input.clone();
+            let buckets = quantile_buckets(&mut vec, n, &compare);
+            assert_eq!(buckets.len(), n);
+            assert_eq!(sorted(&vec), expected, "quantile_buckets failed on {:?}", input);
+        }
+        
+        // selecting requires a valid k, so it doesn't apply to empty input
+        for k in 0..input.len() {
+            let mut vec = input.clone();
+            quick_select(&mut vec, k, &compare);
+            assert_eq!(vec[k], expected[k], "quick_select failed on {:?}", input);
+            
+            let mut vec = input.clone();
+            introselect(&mut vec, k, &compare);
+            assert_eq!(vec[k], expected[k], "introselect failed on {:?}", input);
+            
+            let mut vec = input.clone();
+            sort_around_rank(&mut vec, k, 1, &compare);
+            assert_eq!(vec[k], expected[k], "sort_around_rank failed on {:?}", input);
+            
+            let mut vec = input.clone();
+            assert_eq!(*LazyQuickSorter::new(&mut vec, &compare).at(k), expected[k], "LazyQuickSorter::at failed on {:?}", input);
+        }
+    }
+}
+
+#[test]
+fn test_partitions_edge_cases() {
+    for input in edge_case_inputs() {
+        for pivot_index in 0..input.len() {
+            let pivot = input[pivot_index];
+            
+            let mut vec = input.clone();
+            let r = lomuto_partition(&mut vec, &compare, pivot_index);
+            assert_eq!(vec[r], pivot);
+            verify_partition_lomuto(&vec, &compare, r);
+            
+            let fat_partitions: [FatPartitionFn; 3] =
+                [fat_partition, fat_partition_no_clone_required, fat_partition_checked];
+            for fat_partition_func in fat_partitions {
+                let mut vec = input.clone();
+                let (l, r) = fat_partition_func(&mut vec, &(compare as CompareFn), pivot_index);
+                assert_eq!(vec[l], pivot);
+                verify_partition_fat(&vec, &compare, l, r);
+            }
+            
+            // both parts of hoare partition are non-empty, so it needs at least 2 elements
+            if input.len() >= 2 {
+                let mut vec = input.clone();
+                let p = hoare_partition(&mut vec, &compare, pivot_index);
+                verify_partition_hoare(&vec, &compare, p);
+            }
+        }
+    }
+}
@@ -0,0 +1,168 @@
+// Fuzz the public sorts with random valid total orders.
+// The elements are 0..domain, and the order is given by a random permutation as the key (rank),
+// so the comparator is consistent but unrelated to the natural order of the numbers.
+
+use std::cmp::Ordering;
+
+use rand::{Rng, SeedableRng};
+use rand::prelude::{SliceRandom, StdRng};
+
+use crate::algo::permutation::{argsort, sort_with_indices};
+use crate::data_structure::binary_heap::{heap_sort, heap_sort_top_k, sorted_iter};
+use crate::data_structure::linked_list::MyLinkedList;
+use crate::insertion_sort::bounded_displacement_sort::bounded_displacement_sort;
+use crate::insertion_sort::simple_insertion_sort::insertion_sort;
+use crate::merge_sort::concurrent_merge_sort::{BaseSort, concurrent_merge_sort, concurrent_merge_sort_with_options, RangePartition, sort_segments, SortStability};
+use crate::merge_sort::simple_merge_sort::{bottom_up_merge_sort_inplace, merge_sort_count_inversions, natural_merge_sort_inplace, simple_merge_sort_inplace, simple_merge_sort_inplace_with_cutoff, simple_merge_sort_iter, simple_merge_sort_requires_clone};
+use crate::merge_sort::tim_sort::tim_sort;
+use crate::quick_sort::lazy_quick_sort::LazyQuickSorter;
+use crate::quick_sort::parallel_bucket_sort::parallel_bucket_sort;
+use crate::quick_sort::parallel_quick_sort::parallel_quick_sort_with_cutoff;
+use crate::quick_sort::simple_quick_sort::{adaptive_quick_sort, intro_sort, normal_quick_sort, normal_quick_sort_recording, quick_sort_debug, sort_rle};
+use crate::util::comparator::sort_with_tiebreak;
+
+// the comparator of the random order
+fn by_rank(rank: &[usize]) -> impl Fn(&usize, &usize) -> Ordering + Send + Sync + '_ {
+    move |a: &usize, b: &usize| rank[*a].cmp(&rank[*b])
+}
+
+// For each trial, it generates a random order and a random input, then checks that
+// the sort result is the input sorted by that order.
+// The sort receives the rank, and sorts the slice by by_rank(rank).
+fn fuzz_sort_with_random_comparators(name: &str, sort: impl Fn(&mut [usize], &[usize])) {
+    let mut rng: StdRng = SeedableRng::seed_from_u64(123456);
+    
+    for _i in 0..30 {
+        // small domain gives many duplicates
+        let domain = rng.gen_range(1..300);
+        let mut rank: Vec<usize> = (0..domain).collect();
+        rank.shuffle(&mut rng);
+        
+        let len = rng.gen_range(0..500);
+        let input: Vec<usize> = (0..len).map(|_| rng.gen_range(0..domain)).collect();
+        
+        // the equal elements are the same number, so stability doesn't affect the result
+        let mut expected = input.clone();
+        expected.sort_by_key(|x| rank[*x]);
+        
+        let mut vec = input.clone();
+        sort(&mut vec, &rank);
+        assert_eq!(vec, expected, "{} failed on {:?} with rank {:?}", name, input, rank);
+    }
+}
+
+fn linked_list_of(arr: &[usize]) -> MyLinkedList<usize> {
+    let mut list = MyLinkedList::new();
+    arr.iter().for_each(|x| { list.push_back(*x); });
+    list
+}
+
+fn copy_from_iter<'a>(arr: &mut [usize], iter: impl Iterator<Item=&'a usize>) {
+    let result: Vec<usize> = iter.copied().collect();
+    arr.copy_from_slice(&result);
+}
+
+#[test]
+fn test_quick_sorts_fuzz() {
+    fuzz_sort_with_random_comparators("normal_quick_sort", |arr, rank| normal_quick_sort(arr, &by_rank(rank)));
+    fuzz_sort_with_random_comparators("adaptive_quick_sort", |arr, rank| adaptive_quick_sort(arr, &by_rank(rank)));
+    fuzz_sort_with_random_comparators("intro_sort", |arr, rank| intro_sort(arr, &by_rank(rank)));
+    fuzz_sort_with_random_comparators("quick_sort_debug", |arr, rank| quick_sort_debug(arr, &by_rank(rank)));
+    fuzz_sort_with_random_comparators("normal_quick_sort_recording", |arr, rank| {
+        normal_quick_sort_recording(arr, &by_rank(rank));
+    });
+    fuzz_sort_with_random_comparators("sort_rle", |arr, rank| {
+        let rle = sort_rle(&mut arr.to_vec(), &by_rank(rank));
+        let result: Vec<usize> = rle.into_iter().flat_map(|(value, count)| std::iter::repeat(value).take(count)).collect();
+        arr.copy_from_slice(&result);
+    });
+    fuzz_sort_with_random_comparators("LazyQuickSorter::sort_all", |arr, rank| {
+        LazyQuickSorter::new(arr, &by_rank(rank)).sort_all();
+    });
+    fuzz_sort_with_random_comparators("parallel_bucket_sort", |arr, rank| {
+        parallel_bucket_sort(arr, &by_rank(rank), 4);
+    });
+    fuzz_sort_with_random_comparators("parallel_quick_sort_with_cutoff", |arr, rank| {
+        parallel_quick_sort_with_cutoff(arr, &by_rank(rank), 10);
+    });
+    fuzz_sort_with_random_comparators("MyLinkedList::sort_by", |arr, rank| {
+        let mut list = linked_list_of(arr);
+        list.sort_by(&by_rank(rank));
+        copy_from_iter(arr, list.iter());
+    });
+    fuzz_sort_with_random_comparators("sort_with_tiebreak", |arr, rank| {
+        sort_with_tiebreak(arr, &by_rank(rank), &|_: &usize, _: &usize| Ordering::Equal);
+    });
+}
+
+#[test]
+fn test_merge_sorts_fuzz() {
+    fuzz_sort_with_random_comparators("simple_merge_sort_inplace", |arr, rank| {
+        simple_merge_sort_inplace(arr, &by_rank(rank));
+    });
+    fuzz_sort_with_random_comparators("simple_merge_sort_inplace_with_cutoff", |arr, rank| {
+        simple_merge_sort_inplace_with_cutoff(arr, &by_rank(rank), 0);
+    });
+    fuzz_sort_with_random_comparators("bottom_up_merge_sort_inplace", |arr, rank| {
+        bottom_up_merge_sort_inplace(arr, &by_rank(rank));
+    });
+    fuzz_sort_with_random_comparators("natural_merge_sort_inplace", |arr, rank| {
+        natural_merge_sort_inplace(arr, &by_rank(rank));
+    });
+    fuzz_sort_with_random_comparators("merge_sort_count_inversions", |arr, rank| {
+        merge_sort_count_inversions(arr, &by_rank(rank));
+    });
+    fuzz_sort_with_random_comparators("simple_merge_sort_requires_clone", |arr, rank| {
+        let result = simple_merge_sort_requires_clone(arr, &by_rank(rank));
+        arr.copy_from_slice(&result);
+    });
+    fuzz_sort_with_random_comparators("simple_merge_sort_iter", |arr, rank| {
+        let compare = by_rank(rank);
+        let result: Vec<usize> = simple_merge_sort_iter(arr, &compare).collect();
+        arr.copy_from_slice(&result);
+    });
+    fuzz_sort_with_random_comparators("tim_sort", |arr, rank| tim_sort(arr, &by_rank(rank)));
+    fuzz_sort_with_random_comparators("sort_segments", |arr, rank| {
+        let len = arr.len();
+        sort_segments(arr, &RangePartition::from_endpoints(vec![0, len]), &by_rank(rank), 4);
+    });
+    fuzz_sort_with_random_comparators("concurrent_merge_sort", |arr, rank| {
+        concurrent_merge_sort(arr, &by_rank(rank), 4);
+    });
+    // the inputs are small, so it needs a low threshold to go through the concurrent phases
+    fuzz_sort_with_random_comparators("concurrent_merge_sort_with_options", |arr, rank| {
+        concurrent_merge_sort_with_options(arr, &by_rank(rank), 4, 1, SortStability::Stable, BaseSort::Merge);
+    });
+    fuzz_sort_with_random_comparators("MyLinkedList::merge_sort_by", |arr, rank| {
+        let mut list = linked_list_of(arr);
+        list.merge_sort_by(&by_rank(rank));
+        copy_from_iter(arr, list.iter());
+    });
+}
+
+#[test]
+fn test_other_sorts_fuzz() {
+    fuzz_sort_with_random_comparators("insertion_sort", |arr, rank| insertion_sort(arr, &by_rank(rank)));
+    fuzz_sort_with_random_comparators("bounded_displacement_sort", |arr, rank| {
+        let len = arr.len();
+        bounded_displacement_sort(arr, &by_rank(rank), len);
+    });
+    fuzz_sort_with_random_comparators("heap_sort", |arr, rank| heap_sort(arr, &by_rank(rank)));
+    fuzz_sort_with_random_comparators("heap_sort_top_k", |arr, rank| {
+        let len = arr.len();
+        heap_sort_top_k(arr, len, &by_rank(rank));
+    });
+    fuzz_sort_with_random_comparators("sorted_iter", |arr, rank| {
+        let input = arr.to_vec();
+        let compare = by_rank(rank);
+        copy_from_iter(arr, sorted_iter(&input, &compare));
+    });
+    fuzz_sort_with_random_comparators("argsort", |arr, rank| {
+        let input = arr.to_vec();
+        copy_from_iter(arr, argsort(&input, &by_rank(rank)).into_iter().map(|i| &input[i]));
+    });
+    fuzz_sort_with_random_comparators("sort_with_indices", |arr, rank| {
+        let input = arr.to_vec();
+        copy_from_iter(arr, sort_with_indices(&input, &by_rank(rank)).into_iter().map(|(_, e)| e));
+    });
+}
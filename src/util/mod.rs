@@ -0,0 +1,8 @@
+pub mod comparator;
+pub mod cancellation;
+#[cfg(test)]
+pub mod test_util;
+#[cfg(test)]
+mod edge_case_tests;
+#[cfg(test)]
+mod fuzz_tests;
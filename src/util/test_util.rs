@@ -0,0 +1,346 @@
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::cell::{Cell, RefCell};
+use std::cmp::Ordering;
+use std::collections::HashMap;
+use std::fmt::Debug;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering as AtomicOrdering};
+
+use rand::{Rng, SeedableRng};
+use rand::prelude::StdRng;
+
+use crate::util::cancellation::Cancelled;
+
+// check that `sorted` is a stable sort of `input`.
+// the elements are (key, tag), only key is compared. the tags should be distinct.
+// for equal keys, the tags should keep their order in the input.
+pub fn assert_stable<K: Ord + Debug>(input: &[(K, usize)], sorted: &[(K, usize)]) {
+    assert_eq!(input.len(), sorted.len(), "length changed");
+    
+    // tag -> index in input
+    let mut original_index: HashMap<usize, usize> = HashMap::new();
+    for (index, (_key, tag)) in input.iter().enumerate() {
+        let old = original_index.insert(*tag, index);
+        assert!(old.is_none(), "duplicate tag {} in input", tag);
+    }
+    
+    let mut visited = vec![false; input.len()];
+    for (key, tag) in sorted {
+        let index = *original_index.get(tag)
+            .unwrap_or_else(|| panic!("tag {} is not in input", tag));
+        assert!(!visited[index], "tag {} appears more than once", tag);
+        visited[index] = true;
+        assert_eq!(&input[index].0, key, "key of tag {} changed", tag);
+    }
+    
+    for pair in sorted.windows(2) {
+        let (key1, tag1) = &pair[0];
+        let (key2, tag2) = &pair[1];
+        assert!(key1 <= key2, "not sorted: {:?} is before {:?}", key1, key2);
+        if key1 == key2 {
+            assert!(
+                original_index[tag1] < original_index[tag2],
+                "not stable: for key {:?}, tag {} is moved before tag {}", key1, tag2, tag1
+            );
+        }
+    }
+}
+
+// check the result of a lomuto-style partition: arr[p] is the pivot, arr[..p] < pivot, arr[p+1..] >= pivot.
+pub fn verify_partition_lomuto<E: Debug, C>(arr: &[E], compare: &C, p: usize)
+    where C: Fn(&E, &E) -> Ordering
+{
+    assert!(p < arr.len(), "pivot position {} out of bound", p);
+    let pivot = &arr[p];
+    for (index, e) in arr.iter().enumerate() {
+        match index.cmp(&p) {
+            Ordering::Less => assert!(
+                compare(e, pivot).is_lt(), "arr[{}] = {:?} in the left part is not < pivot {:?}", index, e, pivot
+            ),
+            Ordering::Greater => assert!(
+                compare(e, pivot).is_ge(), "arr[{}] = {:?} in the right part is not >= pivot {:?}", index, e, pivot
+            ),
+            Ordering::Equal => {}
+        }
+    }
+}
+
+// check the result of a hoare-style partition: both parts are non-empty, and arr[..p] <= arr[p..].
+pub fn verify_partition_hoare<E: Debug, C>(arr: &[E], compare: &C, p: usize)
+    where C: Fn(&E, &E) -> Ordering
+{
+    assert_ne!(p, 0, "the left part is empty");
+    assert!(p < arr.len(), "the right part is empty");
+    let left_max = arr[..p].iter().max_by(|a, b| compare(a, b)).unwrap();
+    let right_min = arr[p..].iter().min_by(|a, b| compare(a, b)).unwrap();
+    assert!(
+        compare(left_max, right_min).is_le(),
+        "left part max {:?} is larger than right part min {:?}", left_max, right_min
+    );
+}
+
+// check the result of a fat partition: arr[..l] < pivot, arr[l..r] == pivot, arr[r..] > pivot.
+pub fn verify_partition_fat<E: Debug, C>(arr: &[E], compare: &C, l: usize, r: usize)
+    where C: Fn(&E, &E) -> Ordering
+{
+    assert!(l < r, "equal region is empty");
+    assert!(r <= arr.len(), "equal region out of bound");
+    let pivot = &arr[l];
+    for (index, e) in arr.iter().enumerate() {
+        let expected = if index < l {
+            Ordering::Less
+        } else if index < r {
+            Ordering::Equal
+        } else {
+            Ordering::Greater
+        };
+        assert_eq!(compare(e, pivot), expected, "arr[{}] = {:?} compared with pivot {:?}", index, e, pivot);
+    }
+}
+
+// Like assert_eq on two sort results, but on failure it reports the first index where they differ,
+// with a few elements around it, instead of printing the whole slices.
+pub fn assert_same_order<T: PartialEq + Debug>(a: &[T], b: &[T]) {
+    let first_difference = a.iter().zip(b.iter()).position(|(x, y)| x != y)
+        .or(if a.len() != b.len() { Some(a.len().min(b.len())) } else { None });
+    
+    if let Some(index) = first_difference {
+        let context_start = index.saturating_sub(3);
+        panic!(
+            "first difference at index {} (lengths {} and {}):\n  a[{}..]: {:?}\n  b[{}..]: {:?}",
+            index, a.len(), b.len(),
+            context_start, &a[context_start..(index + 4).min(a.len())],
+            context_start, &b[context_start..(index + 4).min(b.len())],
+        );
+    }
+}
+
+// check that the partition (or any reordering) doesn't lose or duplicate elements
+pub fn assert_same_elements<E: Ord + Clone + Debug>(original: &[E], result: &[E]) {
+    let mut sorted_original = original.to_vec();
+    sorted_original.sort();
+    let mut sorted_result = result.to_vec();
+    sorted_result.sort();
+    assert_eq!(sorted_original, sorted_result, "not a permutation of the original");
+}
+
+// it's neither Copy nor Clone, only movable.
+// sorting it checks that the sort doesn't rely on cloning elements.
+#[derive(Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub struct NotCloneable(pub Box<i32>);
+
+pub fn to_not_cloneable(arr: &[i32]) -> Vec<NotCloneable> {
+    arr.iter().map(|x| NotCloneable(Box::new(*x))).collect()
+}
+
+// McIlroy's adversary ("A Killer Adversary for Quicksort").
+// the values are decided lazily during comparisons, so that the pivot tends to be the smallest.
+// after running the algorithm (a quick sort or quick select) on it, the decided values form a killer input for that algorithm.
+// returns the killer input.
+pub fn generate_killer_input(len: usize, algorithm: impl Fn(&mut [usize], &dyn Fn(&usize, &usize) -> Ordering)) -> Vec<usize> {
+    let gas = len;
+    let values: RefCell<Vec<usize>> = RefCell::new(vec![gas; len]);
+    let solid_num: Cell<usize> = Cell::new(0);
+    let candidate: Cell<usize> = Cell::new(0);
+    
+    let compare = |x: &usize, y: &usize| -> Ordering {
+        let mut values = values.borrow_mut();
+        if values[*x] == gas && values[*y] == gas {
+            // freeze one of them to the next smallest value
+            let frozen = if *x == candidate.get() { *x } else { *y };
+            values[frozen] = solid_num.get();
+            solid_num.set(solid_num.get() + 1);
+        }
+        if values[*x] == gas {
+            candidate.set(*x);
+        } else if values[*y] == gas {
+            candidate.set(*y);
+        }
+        values[*x].cmp(&values[*y])
+    };
+    
+    let mut indices: Vec<usize> = (0..len).collect();
+    algorithm(&mut indices, &compare);
+    
+    // the remaining gas elements are the largest, the order between them doesn't matter
+    let mut values = values.into_inner();
+    for value in values.iter_mut() {
+        if *value == gas {
+            *value = solid_num.get();
+            solid_num.set(solid_num.get() + 1);
+        }
+    }
+    values
+}
+
+pub fn count_comparisons(vec: &[usize], algorithm: impl Fn(&mut [usize], &dyn Fn(&usize, &usize) -> Ordering)) -> usize {
+    let count: Cell<usize> = Cell::new(0);
+    let compare = |a: &usize, b: &usize| {
+        count.set(count.get() + 1);
+        a.cmp(b)
+    };
+    let mut vec = vec.to_vec();
+    algorithm(&mut vec, &compare);
+    count.get()
+}
+
+// The global allocator of the tests. It's System, but it counts the allocations of a watched size
+// on the current thread, for checking that a temp buffer is freed when a panic unwinds.
+// The counters are thread-local, so the tests running in parallel don't disturb each other.
+struct CountingAllocator;
+
+#[global_allocator]
+static COUNTING_ALLOCATOR: CountingAllocator = CountingAllocator;
+
+thread_local! {
+    // 0 means not watching
+    static WATCHED_SIZE: Cell<usize> = const { Cell::new(0) };
+    static LIVE_WATCHED_ALLOCATIONS: Cell<isize> = const { Cell::new(0) };
+}
+
+fn track_allocation(size: usize, delta: isize) {
+    // try_with, as it may be called when the thread-locals are being destroyed
+    let _ = WATCHED_SIZE.try_with(|watched_size| {
+        if watched_size.get() != 0 && watched_size.get() == size {
+            LIVE_WATCHED_ALLOCATIONS.with(|live| live.set(live.get() + delta));
+        }
+    });
+}
+
+// the default realloc calls alloc and dealloc, so it's also counted
+unsafe impl GlobalAlloc for CountingAllocator {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        track_allocation(layout.size(), 1);
+        System.alloc(layout)
+    }
+    
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        track_allocation(layout.size(), -1);
+        System.dealloc(ptr, layout)
+    }
+}
+
+// Run f and return the number of allocations of the size (in bytes) that f made on this thread but didn't free.
+// Choose a size that no other allocation in f has.
+pub fn count_leaked_allocations(size: usize, f: impl FnOnce()) -> isize {
+    assert!(size != 0);
+    LIVE_WATCHED_ALLOCATIONS.with(|live| live.set(0));
+    WATCHED_SIZE.with(|watched_size| watched_size.set(size));
+    
+    f();
+    
+    WATCHED_SIZE.with(|watched_size| watched_size.set(0));
+    LIVE_WATCHED_ALLOCATIONS.with(|live| live.get())
+}
+
+// The flag is set after this many comparisons, in the middle of sorting a large array.
+const CANCEL_AFTER_COMPARISONS: usize = 100000;
+
+// The merge sorts check the flag between merges. When the flag is set, the merges in progress
+// are of a few thousand elements, and they complete.
+pub const MERGE_COMPARISONS_AFTER_CANCEL: usize = 8192;
+
+// A sift down compares at most twice per level.
+pub const HEAP_SORT_COMPARISONS_AFTER_CANCEL: usize = 2 * usize::BITS as usize;
+
+// Check a cancellable sort. Without cancelling, it sorts.
+// The comparator given to the sort sets the flag after CANCEL_AFTER_COMPARISONS comparisons,
+// then it should return Err(Cancelled), and the array is a permutation of the input.
+// Each thread only finishes its current step (like a partition block, or a small merge) after the flag is set,
+// so the comparisons after it are bounded by max_comparisons_after_cancel, not by the array length.
+pub fn check_cancellable_sort(
+    name: &str, max_comparisons_after_cancel: usize,
+    sort: impl Fn(&mut [i32], &(dyn Fn(&i32, &i32) -> Ordering + Sync), &AtomicBool) -> Result<(), Cancelled>,
+) {
+    let mut rng: StdRng = SeedableRng::seed_from_u64(123456);
+    
+    let input: Vec<i32> = (0..1000000).map(|_| rng.gen_range(0..100000000)).collect();
+    
+    let mut arr = input[..10000].to_vec();
+    assert_eq!(sort(&mut arr, &|a, b| a.cmp(b), &AtomicBool::new(false)), Ok(()), "{}", name);
+    let mut expected = input[..10000].to_vec();
+    expected.sort();
+    assert_same_order(&arr, &expected);
+    
+    let cancel = AtomicBool::new(false);
+    let count = AtomicUsize::new(0);
+    let cancelling_compare = |a: &i32, b: &i32| {
+        if count.fetch_add(1, AtomicOrdering::Relaxed) + 1 == CANCEL_AFTER_COMPARISONS {
+            cancel.store(true, AtomicOrdering::Relaxed);
+        }
+        a.cmp(b)
+    };
+    let mut arr = input.clone();
+    let result = sort(&mut arr, &cancelling_compare, &cancel);
+    assert_eq!(result, Err(Cancelled), "{} completed with {} comparisons", name, count.load(AtomicOrdering::Relaxed));
+    let comparisons_after_cancel = count.load(AtomicOrdering::Relaxed) - CANCEL_AFTER_COMPARISONS;
+    assert!(
+        comparisons_after_cancel <= max_comparisons_after_cancel,
+        "{} made {} comparisons after cancelled", name, comparisons_after_cancel
+    );
+    
+    arr.sort();
+    let mut expected = input;
+    expected.sort();
+    assert_eq!(arr, expected, "{} lost elements after cancelled", name);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    
+    #[test]
+    fn test_assert_same_order() {
+        assert_same_order(&[1, 2, 3], &[1, 2, 3]);
+        assert_same_order::<i32>(&[], &[]);
+    }
+    
+    #[test]
+    #[should_panic(expected = "first difference at index 5 (lengths 8 and 8):\n  a[2..]: [2, 3, 4, 5, 6, 7]\n  b[2..]: [2, 3, 4, 0, 6, 7]")]
+    fn test_assert_same_order_different() {
+        assert_same_order(&[0, 1, 2, 3, 4, 5, 6, 7], &[0, 1, 2, 3, 4, 0, 6, 7]);
+    }
+    
+    #[test]
+    #[should_panic(expected = "first difference at index 2 (lengths 2 and 3)")]
+    fn test_assert_same_order_different_length() {
+        assert_same_order(&[0, 1], &[0, 1, 2]);
+    }
+    
+    #[test]
+    fn test_assert_stable() {
+        let input = vec![(2, 0), (1, 1), (2, 2), (1, 3)];
+        assert_stable(&input, &[(1, 1), (1, 3), (2, 0), (2, 2)]);
+    }
+    
+    #[test]
+    fn test_verify_partitions() {
+        let compare = |a: &i32, b: &i32| a.cmp(b);
+        verify_partition_lomuto(&[1, 0, 2, 3, 2], &compare, 2);
+        verify_partition_hoare(&[1, 2, 2, 3, 2], &compare, 2);
+        verify_partition_fat(&[1, 0, 2, 2, 3], &compare, 2, 4);
+        assert_same_elements(&[3, 1, 2], &[1, 2, 3]);
+    }
+    
+    #[test]
+    #[should_panic(expected = "compared with pivot")]
+    fn test_verify_partition_fat_wrong() {
+        // 2 is in the right part
+        verify_partition_fat(&[1, 0, 2, 2, 2], &|a: &i32, b: &i32| a.cmp(b), 2, 4);
+    }
+    
+    #[test]
+    fn test_count_leaked_allocations() {
+        // an odd size that other allocations are unlikely to have
+        let size = 4567;
+        assert_eq!(count_leaked_allocations(size, || { let _ = vec![0u8; size]; }), 0);
+        assert_eq!(count_leaked_allocations(size, || std::mem::forget(vec![0u8; size])), 1);
+        assert_eq!(count_leaked_allocations(size, || std::mem::forget(vec![0u8; size + 1])), 0);
+    }
+    
+    #[test]
+    #[should_panic(expected = "not stable")]
+    fn test_assert_stable_unstable() {
+        let input = vec![(2, 0), (1, 1), (2, 2), (1, 3)];
+        assert_stable(&input, &[(1, 3), (1, 1), (2, 0), (2, 2)]);
+    }
+}